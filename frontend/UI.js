@@ -11,6 +11,12 @@ export const groupsToRemove = {};
 if (elements.countScore) {
   elements.countScore.style.visibility = "hidden";
 }
+if (elements.resumePlay) {
+  elements.resumePlay.style.visibility = "hidden";
+}
+if (elements.claimForfeit) {
+  elements.claimForfeit.style.visibility = "hidden";
+}
 
 export function displayMatchIdElement() {
   if (elements.matchID) {
@@ -54,6 +60,36 @@ export function createDiv(id, className) {
   return div;
 }
 
+export function renderChat(messages) {
+  if (!elements.chat.messages) return;
+
+  elements.chat.messages.innerHTML = "";
+  for (const message of messages) {
+    const li = document.createElement("li");
+    li.className = `chat-${message.channel}`;
+    li.textContent = `${message.author}: ${message.text}`;
+    elements.chat.messages.appendChild(li);
+  }
+}
+
+// presence is the opponent's seat, null while nobody has taken it
+export function renderOpponentPresence(presence, isGameOver) {
+  if (!elements.opponentStatus) return;
+
+  const abandoned = presence?.status === "abandoned" && !isGameOver;
+  elements.claimForfeit.style.visibility = abandoned ? "visible" : "hidden";
+
+  if (!presence || isGameOver) {
+    elements.opponentStatus.textContent = "";
+  } else if (presence.status === "disconnected") {
+    elements.opponentStatus.textContent = `Opponent disconnected, ${presence.grace_remaining_secs}s to reconnect`;
+  } else if (abandoned) {
+    elements.opponentStatus.textContent = "Opponent left the game";
+  } else {
+    elements.opponentStatus.textContent = "Opponent connected";
+  }
+}
+
 export function showResult(winner) {
   const res = createButton("result", winner);
   elements.infoContainer.innerHTML = "";
@@ -69,6 +105,7 @@ export function handleGameButtonsAfterGame(isGameOver) {
 
   if (!isGameOver) {
     elements.countScore.style.visibility = "visible";
+    showElement(elements.resumePlay);
     if (elements.downloadSGF) {
       elements.downloadSGF.style.visibility = "visible";
     }
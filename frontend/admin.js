@@ -2,26 +2,28 @@ import { createButton } from "./UI.js";
 import { getGamesList, getGamesListAdmin } from "./handlers.js";
 import { createAdminGamesListNode, getElapsedTimeArray } from "./utils.js";
 import {
+  adminHeaders,
   fetchWithErrorHandling,
   getAPIUrl,
   getPlayerSessionToken,
+  promptForAdminToken,
 } from "./utils.js";
 
 console.log("You opened the Admin Panel!");
 // Detect if running locally and set API URL accordingly
 const API_URL = getAPIUrl();
 
-// TODO: investigate how to make this panel password protected
+// When the server has an admin token, the panel asks for it and keeps it for the tab
 
 const resetButton = createButton("admin-button", "Reset backend memory", () => {
   fetch(`${API_URL}/reset-memory`, {
     method: "POST",
-    headers: {
-      "Content-Type": "application/json",
-    },
+    headers: adminHeaders(),
   })
     .then((response) => {
-      if (response.ok) {
+      if (response.status === 401 || response.status === 403) {
+        promptForAdminToken();
+      } else if (response.ok) {
         alert("Backend memory reset successfully!");
       } else {
         alert("Failed to reset backend memory.");
@@ -13,14 +13,22 @@ export const elements = {
   },
   guessStone: document.getElementById("guess-stone-button"),
   countScore: document.getElementById("count-score-button"),
+  resumePlay: document.getElementById("resume-play-button"),
   downloadSGF: document.getElementById("download-sgf"),
   undo: document.getElementById("undo-button"),
   pass: document.getElementById("pass-button"),
   resign: document.getElementById("resign-button"),
+  claimForfeit: document.getElementById("claim-forfeit-button"),
+  opponentStatus: document.getElementById("opponent-status"),
   infoContainer: document.getElementById("info-container"),
   mainBoardButtons: document.getElementById(".main-board-buttons"),
   readyToCountContainer: document.getElementById("ready-to-count-container"),
   stonesInAtari: document.getElementById("stones-in-atari"),
+  chat: {
+    messages: document.getElementById("chat-messages"),
+    input: document.getElementById("chat-input"),
+    send: document.getElementById("chat-send-button"),
+  },
   boards: {
     main: document.getElementById("main-board"),
     black: document.getElementById("black-player-board"),
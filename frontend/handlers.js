@@ -6,19 +6,20 @@ import {
   groupsToRemove,
 } from "./UI.js";
 import {
+  adminHeaders,
   getAPIUrl,
   getMatchString,
   getPlayerColor,
   getPlayerSessionToken,
+  promptForAdminToken,
 } from "./utils.js";
 import { elements } from "./elements.js";
-import { getDeadGroups } from "./script.js";
+import { getSelectionVersion } from "./script.js";
 
 export let addingGuessStone = false;
 export let removingGuessStone = false;
 
 const API_URL = getAPIUrl();
-const playerColor = getPlayerColor();
 
 export let boardState = [];
 
@@ -36,7 +37,7 @@ function resignRequest() {
     },
     body: JSON.stringify({
       match_string: getMatchString(),
-      player: playerColor,
+      session_token: getPlayerSessionToken(),
     }),
   })
     .then((response) => {
@@ -56,25 +57,58 @@ function resignRequest() {
     });
 }
 
+export function claimForfeitButtonHandler() {
+  if (elements.claimForfeit) {
+    elements.claimForfeit.addEventListener("click", claimForfeitRequest);
+  }
+}
+
+function claimForfeitRequest() {
+  fetch(`${API_URL}/claim-forfeit`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({
+      match_string: getMatchString(),
+      session_token: getPlayerSessionToken(),
+    }),
+  })
+    .then((response) => {
+      if (!response.ok) {
+        throw new Error(`HTTP error! Status: ${response.status}`);
+      }
+      return response.json();
+    })
+    .then((data) => {
+      console.log("Claim forfeit response:", data);
+      const res = createButton("result", data.winner);
+      elements.infoContainer.innerHTML = "";
+      elements.infoContainer.appendChild(res);
+    })
+    .catch((error) => {
+      console.error("Error claiming forfeit:", error);
+    });
+}
+
 export function countScoreButtonHandler() {
   if (elements.countScore) {
     elements.countScore.addEventListener("click", () => {
-      countScoreRequest(getDeadGroups());
+      countScoreRequest(getSelectionVersion());
     });
   }
 }
 
-function countScoreRequest(deadGroups) {
+function countScoreRequest(selectionVersion) {
   fetch(`${API_URL}/get-score`, {
     method: "POST",
     headers: {
       "Content-Type": "application/json",
     },
     body: JSON.stringify({
-      player: getPlayerColor(),
       match_string: getMatchString(),
       session_token: getPlayerSessionToken(),
-      groups_to_remove: deadGroups,
+      version: selectionVersion,
     }),
   })
     .then((response) => {
@@ -91,6 +125,73 @@ function countScoreRequest(deadGroups) {
     });
 }
 
+export function resumePlayButtonHandler() {
+  if (elements.resumePlay) {
+    elements.resumePlay.addEventListener("click", resumePlayRequest);
+  }
+}
+
+function resumePlayRequest() {
+  fetch(`${API_URL}/resume-play`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({
+      match_string: getMatchString(),
+      session_token: getPlayerSessionToken(),
+    }),
+  })
+    .then((response) => {
+      if (!response.ok) {
+        throw new Error(`HTTP error! Status: ${response.status}`);
+      }
+      return response.json();
+    })
+    .then((data) => {
+      console.log("Resume play response:", data.message);
+    })
+    .catch((error) => {
+      console.error("Error during resume play:", error);
+    });
+}
+
+export function chatButtonHandler() {
+  if (elements.chat.send) {
+    elements.chat.send.addEventListener("click", sendChatMessageRequest);
+    elements.chat.input.addEventListener("keydown", (event) => {
+      if (event.key === "Enter") sendChatMessageRequest();
+    });
+  }
+}
+
+// Messages come back through sync, so there's nothing to render here
+function sendChatMessageRequest() {
+  const text = elements.chat.input.value;
+  if (!text.trim()) return;
+
+  fetch(`${API_URL}/send-chat-message`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({
+      match_string: getMatchString(),
+      session_token: getPlayerSessionToken(),
+      text: text,
+    }),
+  })
+    .then((response) => {
+      if (!response.ok) {
+        throw new Error(`HTTP error! Status: ${response.status}`);
+      }
+      elements.chat.input.value = "";
+    })
+    .catch((error) => {
+      console.error("Error sending chat message:", error);
+    });
+}
+
 export function downloadSGFButtonHandler() {
   if (elements.downloadSGF) {
     elements.downloadSGF.addEventListener("click", downloadSGFRequest);
@@ -99,13 +200,7 @@ export function downloadSGFButtonHandler() {
 
 async function downloadSGFRequest() {
   const match_string = getMatchString();
-  fetch(`${API_URL}/get-game-record`, {
-    method: "POST",
-    headers: {
-      "Content-type": "application/json",
-    },
-    body: JSON.stringify({ match_string }),
-  })
+  fetch(`${API_URL}/api/v1/games/${encodeURIComponent(match_string)}/sgf`)
     .then((response) => {
       if (!response.ok) {
         throw new Error(`HTTP error! Status: ${response.status}`);
@@ -140,7 +235,7 @@ function passRequest() {
     },
     body: JSON.stringify({
       match_string: getMatchString(),
-      player: playerColor,
+      session_token: getPlayerSessionToken(),
     }),
   })
     .then((response) => {
@@ -151,9 +246,6 @@ function passRequest() {
     })
     .then((data) => {
       console.log("Pass response:", data.message);
-      if (data.message === "It's not your turn to pass!") {
-        return;
-      }
       showStonesInAtari({ black: 0, white: 0 });
       updateTurn(data.current_player);
     })
@@ -162,6 +254,44 @@ function passRequest() {
     });
 }
 
+let answeredUndoRequest = false;
+
+// Asks the player whether to accept the opponent's undo request
+export function handleUndoRequest(pendingUndo) {
+  if (!pendingUndo || pendingUndo === getPlayerColor()) {
+    answeredUndoRequest = false;
+    return;
+  }
+  if (answeredUndoRequest) return;
+
+  answeredUndoRequest = true;
+  const accept = window.confirm(`${pendingUndo} asks to undo their last move`);
+
+  fetch(`${API_URL}/undo-response`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({
+      match_string: getMatchString(),
+      session_token: getPlayerSessionToken(),
+      accept: accept,
+    }),
+  })
+    .then((response) => {
+      if (!response.ok) {
+        throw new Error(`HTTP error! Status: ${response.status}`);
+      }
+      return response.json();
+    })
+    .then((data) => {
+      console.log("Undo response:", data.message);
+    })
+    .catch((error) => {
+      console.error("Error answering undo request:", error);
+    });
+}
+
 export function guessStonesButtonsHandler() {
   if (elements.guessStone) {
     elements.guessStone.addEventListener("click", () => {
@@ -178,7 +308,7 @@ export function guessStonesButtonsHandler() {
 }
 
 export function getGroupRequest(row, col) {
-  fetch(`${API_URL}/get-group`, {
+  return fetch(`${API_URL}/get-group`, {
     method: "POST",
     headers: {
       "Content-Type": "application/json",
@@ -236,11 +366,13 @@ export async function getGamesListAdmin() {
   console.log("admin stuff ran");
   return fetch(`${API_URL}/get-all-games-admin`, {
     method: "POST",
-    headers: {
-      "Content-Type": "application/json",
-    },
+    headers: adminHeaders(),
   })
     .then((response) => {
+      if (response.status === 401 || response.status === 403) {
+        promptForAdminToken();
+        return [];
+      }
       if (!response.ok) {
         throw new Error(`HTTP error! Status: ${response.status}`);
       }
@@ -256,9 +388,8 @@ export async function removeGame(matchString) {
   console.log("Removing game: " + matchString);
   return fetch(`${API_URL}/remove-game`, {
     method: "POST",
-    headers: {
-      "Content-Type": "application/json",
-    },
+    headers: adminHeaders(),
+    body: JSON.stringify({ match_string: matchString }),
   })
     .then((response) => {
       if (!response.ok) {
@@ -0,0 +1,191 @@
+import {
+  fetchWithErrorHandling,
+  getAPIUrl,
+  getLoginToken,
+} from "./utils.js";
+
+const API_URL = getAPIUrl();
+const POLL_INTERVAL_MS = 1000;
+
+// Challenge we posted and are waiting on
+let waitingFor = null;
+
+function postJSON(path, body) {
+  return fetchWithErrorHandling(`${API_URL}${path}`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify(body),
+  });
+}
+
+function getSettings() {
+  return {
+    board_size: parseInt(document.getElementById("board-size-select").value),
+    komi: parseFloat(document.getElementById("komi-input").value),
+  };
+}
+
+function setStatus(text) {
+  document.getElementById("lobby-status").textContent = text;
+}
+
+function showAccount(account) {
+  document.getElementById("account-status").textContent = account
+    ? `Playing as ${account.display_name}`
+    : "Playing as guest";
+  document.getElementById("logout-button").style.display = account
+    ? "inline"
+    : "none";
+}
+
+function handleLoginResponse(data) {
+  localStorage.setItem("loginToken", data.login_token);
+  showAccount(data.account);
+}
+
+function accountRequest(path) {
+  const password = document.getElementById("password-input").value;
+  postJSON(path, {
+    display_name: document.getElementById("display-name-input").value,
+    // Registering without a password leaves only the login token to get back in
+    password: path === "/register" ? password || null : password,
+  })
+    .then(handleLoginResponse)
+    .catch((error) => setStatus(error.message));
+}
+
+async function loadAccount() {
+  const loginToken = getLoginToken();
+  if (!loginToken) return;
+
+  try {
+    showAccount(await postJSON("/get-account", { login_token: loginToken }));
+  } catch (error) {
+    // The token was logged out or the store was reset
+    localStorage.removeItem("loginToken");
+    showAccount(null);
+  }
+}
+
+function goToSeat(join) {
+  localStorage.setItem("sessionToken", join.session_token);
+  window.location.href = join.redirect_url;
+}
+
+// Seats us right away, or keeps polling until someone accepts our challenge
+function handleLobbyResponse(data) {
+  if (data.join) {
+    goToSeat(data.join);
+    return;
+  }
+
+  waitingFor = {
+    challenge_id: data.challenge_id,
+    owner_token: data.owner_token,
+  };
+  document.getElementById("cancel-challenge-button").style.display = "inline";
+  setStatus("Waiting for an opponent...");
+}
+
+async function pollChallengeStatus() {
+  if (!waitingFor) return;
+
+  try {
+    const data = await postJSON("/challenge-status", waitingFor);
+    if (data.join) {
+      waitingFor = null;
+      goToSeat(data.join);
+    }
+  } catch (error) {
+    waitingFor = null;
+    setStatus(error.message);
+  }
+}
+
+async function renderChallenges() {
+  const challenges = await postJSON("/get-open-challenges", {});
+  const ul = document.getElementById("challenges-list");
+  ul.innerHTML = "";
+
+  for (const challenge of challenges) {
+    if (waitingFor && challenge.id === waitingFor.challenge_id) continue;
+
+    const li = document.createElement("li");
+    const { board_size, komi, undo_policy } = challenge.settings;
+    const owner = challenge.owner ? challenge.owner.display_name : "guest";
+    li.textContent = `${owner}: ${board_size}x${board_size}, komi ${komi}, undo ${undo_policy}`;
+    li.onclick = () => {
+      postJSON("/accept-challenge", {
+        challenge_id: challenge.id,
+        login_token: getLoginToken(),
+      })
+        .then(handleLobbyResponse)
+        .catch((error) => setStatus(error.message));
+    };
+    ul.appendChild(li);
+  }
+}
+
+document.addEventListener("DOMContentLoaded", () => {
+  document
+    .getElementById("post-challenge-button")
+    .addEventListener("click", () => {
+      if (waitingFor) return;
+      postJSON("/post-challenge", {
+        settings: getSettings(),
+        login_token: getLoginToken(),
+      })
+        .then(handleLobbyResponse)
+        .catch((error) => setStatus(error.message));
+    });
+
+  document
+    .getElementById("quick-match-button")
+    .addEventListener("click", () => {
+      if (waitingFor) return;
+      postJSON("/quick-match", {
+        settings: getSettings(),
+        login_token: getLoginToken(),
+      })
+        .then(handleLobbyResponse)
+        .catch((error) => setStatus(error.message));
+    });
+
+  document
+    .getElementById("cancel-challenge-button")
+    .addEventListener("click", () => {
+      if (!waitingFor) return;
+      postJSON("/cancel-challenge", waitingFor)
+        .then(() => {
+          waitingFor = null;
+          document.getElementById("cancel-challenge-button").style.display =
+            "none";
+          setStatus("");
+        })
+        .catch((error) => setStatus(error.message));
+    });
+
+  document
+    .getElementById("register-button")
+    .addEventListener("click", () => accountRequest("/register"));
+
+  document
+    .getElementById("login-button")
+    .addEventListener("click", () => accountRequest("/login"));
+
+  document.getElementById("logout-button").addEventListener("click", () => {
+    postJSON("/logout", { login_token: getLoginToken() }).finally(() => {
+      localStorage.removeItem("loginToken");
+      showAccount(null);
+    });
+  });
+
+  loadAccount();
+  renderChallenges();
+  setInterval(() => {
+    pollChallengeStatus();
+    renderChallenges();
+  }, POLL_INTERVAL_MS);
+});
@@ -6,12 +6,14 @@ import {
   handleGameButtonsAfterGame,
   showStonesInAtari,
   showElement,
+  renderOpponentPresence,
   drawGridLines,
   drawStarPoints,
   displayMatchIdElement,
   toggleGroupSelection,
   showResult,
   safeDeleteUIElement,
+  renderChat,
 } from "./UI.js";
 import {
   fetchWithErrorHandling,
@@ -28,11 +30,13 @@ import {
   getPlayerSessionToken,
   navigateToMainBoard,
   redirectToRejoinPage,
+  redirectToSeat,
   getReadyToCountTexts,
   handleReadyToCount,
 } from "./utils.js";
 import {
   resignButtonHandler,
+  claimForfeitButtonHandler,
   countScoreButtonHandler,
   guessStonesButtonsHandler,
   passButtonHandler,
@@ -40,6 +44,9 @@ import {
   removingGuessStone,
   getGroupRequest,
   downloadSGFButtonHandler,
+  handleUndoRequest,
+  resumePlayButtonHandler,
+  chatButtonHandler,
 } from "./handlers.js";
 import { boards, elements } from "./elements.js";
 
@@ -57,8 +64,8 @@ let stonesInAtari = {
 };
 let deadGroupsDuringCounting = [];
 
-export function getDeadGroups() {
-  return deadGroupsDuringCounting.selected;
+export function getSelectionVersion() {
+  return deadGroupsDuringCounting.version;
 }
 
 const API_URL = getAPIUrl();
@@ -178,9 +185,9 @@ function sendGuessStonesToBackend(color, stones) {
       "Content-Type": "application/json",
     },
     body: JSON.stringify({
-      color: color,
       stones: stones,
       match_string: getMatchString(),
+      session_token: getPlayerSessionToken(),
       board_generation_number: boardGenerationNumber,
     }),
   })
@@ -245,7 +252,11 @@ function placeStone(stoneColor, row, col) {
   stone.addEventListener("click", () => {
     if (countingPhase) {
       console.log("Row: " + row + " Col: " + col);
-      deadGroupsDuringCounting = getGroupRequest(row, col);
+      getGroupRequest(row, col).then((data) => {
+        if (data) {
+          deadGroupsDuringCounting = data;
+        }
+      });
     }
   });
 
@@ -295,6 +306,7 @@ function syncBoards() {
         match_string: getMatchString(),
         player: playerColor,
         frontend_board_generation_number: boardGenerationNumber,
+        session_token: getPlayerSessionToken(),
       }),
     })
       .catch((error) => {
@@ -313,6 +325,11 @@ function syncBoards() {
           return;
         }
 
+        renderOpponentPresence(
+          playerColor === "black" ? data.white_presence : data.black_presence,
+          Boolean(data.winner),
+        );
+
         if (!data.should_sync && !countingPhase) {
           console.log("Not syncing boards!");
           setTimeout(sync, retryInterval);
@@ -329,6 +346,7 @@ function syncBoards() {
           body: JSON.stringify({
             match_string: getMatchString(),
             player: playerColor,
+            session_token: getPlayerSessionToken(),
           }),
         })
           .catch((error) => {
@@ -348,10 +366,15 @@ function syncBoards() {
               guessStones.black = data.black_guess_stones;
               guessStones.white = data.white_guess_stones;
               updateTurn(data.current_player);
+
+              if (playerColor !== "spectator") {
+                handleUndoRequest(data.pending_undo);
+              }
             }
 
             updateCaptures(data.black_captures, data.white_captures);
             updateBoard(data.board, data.stones_in_atari);
+            renderChat(data.chat);
 
             if (data.winner) {
               if (playerColor !== "spectator") {
@@ -364,6 +387,13 @@ function syncBoards() {
               return;
             }
 
+            // Someone resumed play, leave the counting board
+            if (countingPhase && !data.counting) {
+              countingPhase = false;
+              redirectToSeat();
+              return;
+            }
+
             if (data.counting) {
               countingPhase = true;
 
@@ -435,7 +465,7 @@ function undoRequest() {
     },
     body: JSON.stringify({
       match_string: getMatchString(),
-      player: playerColor,
+      session_token: getPlayerSessionToken(),
       board_generation_number: boardGenerationNumber,
     }),
   })
@@ -448,10 +478,8 @@ function undoRequest() {
     .then((data) => {
       console.log("Server response:", data.message);
 
-      if (data.message === "It's not your turn to undo!") {
-        return;
-      }
-
+      // Refusals were thrown above. A request the opponent still has to accept
+      // comes back with the board as it is, so it's safe to draw either way
       boardGenerationNumber = data.board_generation_number;
       boardState = data.board;
 
@@ -468,5 +496,8 @@ undoButtonHandler();
 passButtonHandler();
 guessStonesButtonsHandler();
 countScoreButtonHandler();
+resumePlayButtonHandler();
 downloadSGFButtonHandler();
 resignButtonHandler();
+claimForfeitButtonHandler();
+chatButtonHandler();
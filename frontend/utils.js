@@ -205,7 +205,8 @@ export function getAPIUrl() {
     /^10\./.test(host) ||
     /^172\.(1[6-9]|2[0-9]|3[0-1])\./.test(host);
 
-  if (isLocal) {
+  // Served over HTTPS by the server itself, the API lives on the same origin
+  if (isLocal && loc.protocol !== "https:") {
     return "http://localhost:8000";
   }
 
@@ -230,6 +231,30 @@ export function getPlayerSessionToken() {
   return localStorage.getItem("sessionToken") || "";
 }
 
+// Only needed when the server has an admin token configured
+export function adminHeaders() {
+  const headers = { "Content-Type": "application/json" };
+  const adminToken = sessionStorage.getItem("adminToken");
+  if (adminToken) {
+    headers["Authorization"] = `Bearer ${adminToken}`;
+  }
+  return headers;
+}
+
+// Asks for the admin token again after the server refused the stored one
+export function promptForAdminToken() {
+  const adminToken = prompt("Admin token");
+  if (adminToken) {
+    sessionStorage.setItem("adminToken", adminToken);
+    window.location.reload();
+  }
+}
+
+// null when playing as a guest
+export function getLoginToken() {
+  return localStorage.getItem("loginToken");
+}
+
 export function navigateToMainBoard() {
   window.location.href = "/frontend/main.html?match=" + getMatchString();
 }
@@ -284,9 +309,7 @@ export function createAdminGamesListNode(leftText, middleText, actionText) {
   actionDiv.onclick = () => {
     fetchWithErrorHandling(`${API_URL}/remove-game`, {
       method: "POST",
-      headers: {
-        "Content-Type": "application/json",
-      },
+      headers: adminHeaders(),
       body: JSON.stringify({
         match_string: leftText,
       }),
@@ -300,6 +323,22 @@ export function createAdminGamesListNode(leftText, middleText, actionText) {
   return container;
 }
 
+// Sends a player back to their own board, spectators just reload the main board
+export function redirectToSeat() {
+  fetchWithErrorHandling(`${getAPIUrl()}/validate-spectator`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({
+      match_string: getMatchString(),
+      session_token: getPlayerSessionToken(),
+    }),
+  }).then((data) => {
+    window.location.href = data.redirect_url + data.session_token;
+  });
+}
+
 export function redirectToRejoinPage() {
   alert("Game data lost. Please rejoin via login page :)");
   setTimeout(() => {
@@ -0,0 +1,317 @@
+// Lingo:
+//     account - a player identity that outlives rooms, seats point to it
+//     login token - secret returned on register/login, sent instead of the password afterwards.
+//                   Accounts without a password can only be used through their login tokens
+//     bot account - an account played by a program through the bot API. Its login token is
+//                   the bot token, it never has a password
+
+use crate::storage::{load_json, save_json};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use utoipa::ToSchema;
+
+pub const MAX_DISPLAY_NAME_LENGTH: usize = 32;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Account {
+    pub id: String,
+    pub display_name: String,
+    // Argon2 PHC string
+    password_hash: Option<String>,
+    pub created_at: u64,
+    #[serde(default)]
+    pub bot: bool,
+}
+
+impl Account {
+    pub fn public(&self) -> PublicAccount {
+        PublicAccount {
+            id: self.id.clone(),
+            display_name: self.display_name.clone(),
+            bot: self.bot,
+        }
+    }
+}
+
+// What other players get to see about an account
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
+pub struct PublicAccount {
+    pub id: String,
+    pub display_name: String,
+    // Played by a program, so humans know who they're up against
+    pub bot: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AccountError {
+    InvalidName,
+    NameTaken,
+    InvalidCredentials,
+    UnknownToken,
+    Storage(String),
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct AccountStore {
+    accounts: HashMap<String, Account>,
+    // login token -> account id
+    login_tokens: HashMap<String, String>,
+    // Where the store is written after every change, None keeps it in memory only
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+fn normalize_name(display_name: &str) -> String {
+    display_name.trim().to_lowercase()
+}
+
+// Argon2 is slow on purpose, callers keep it off the async executor and out of the
+// account store's lock
+pub fn hash_password(password: &str) -> Result<String, AccountError> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| AccountError::Storage(err.to_string()))
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+impl AccountStore {
+    // Starts empty if the file doesn't exist yet
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut store: AccountStore = load_json(path)?;
+        store.path = Some(path.to_path_buf());
+        Ok(store)
+    }
+
+    fn save(&self) -> Result<(), AccountError> {
+        match &self.path {
+            Some(path) => {
+                save_json(path, self).map_err(|err| AccountError::Storage(err.to_string()))
+            }
+            None => Ok(()),
+        }
+    }
+
+    // The token is dropped again if it can't be saved
+    fn issue_login_token(&mut self, account_id: &str) -> Result<String, AccountError> {
+        let token = uuid::Uuid::new_v4().to_string();
+        self.login_tokens
+            .insert(token.clone(), account_id.to_string());
+        if let Err(err) = self.save() {
+            self.login_tokens.remove(&token);
+            return Err(err);
+        }
+        Ok(token)
+    }
+
+    fn find_by_name(&self, display_name: &str) -> Option<&Account> {
+        let name = normalize_name(display_name);
+        self.accounts
+            .values()
+            .find(|account| normalize_name(&account.display_name) == name)
+    }
+
+    // `password_hash` comes from hash_password
+    pub fn register(
+        &mut self,
+        display_name: &str,
+        password_hash: Option<String>,
+    ) -> Result<(Account, String), AccountError> {
+        self.create(display_name, password_hash, false)
+    }
+
+    // Returns the account and its bot token
+    pub fn register_bot(&mut self, display_name: &str) -> Result<(Account, String), AccountError> {
+        self.create(display_name, None, true)
+    }
+
+    // Names are unique ignoring case, so players can't impersonate each other
+    fn create(
+        &mut self,
+        display_name: &str,
+        password_hash: Option<String>,
+        bot: bool,
+    ) -> Result<(Account, String), AccountError> {
+        let display_name = display_name.trim();
+
+        if display_name.is_empty()
+            || display_name.chars().count() > MAX_DISPLAY_NAME_LENGTH
+            || display_name.chars().any(char::is_control)
+        {
+            return Err(AccountError::InvalidName);
+        }
+
+        if self.find_by_name(display_name).is_some() {
+            return Err(AccountError::NameTaken);
+        }
+
+        let account = Account {
+            id: uuid::Uuid::new_v4().to_string(),
+            display_name: display_name.to_string(),
+            password_hash,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            bot,
+        };
+
+        // Only kept once it's on disk
+        self.accounts.insert(account.id.clone(), account.clone());
+        match self.issue_login_token(&account.id) {
+            Ok(token) => Ok((account, token)),
+            Err(err) => {
+                self.accounts.remove(&account.id);
+                Err(err)
+            }
+        }
+    }
+
+    // Account id and password hash to check a login against with verify_password,
+    // None for unknown names and accounts without a password
+    pub fn credentials(&self, display_name: &str) -> Option<(String, String)> {
+        self.find_by_name(display_name).and_then(|account| {
+            account
+                .password_hash
+                .clone()
+                .map(|hash| (account.id.clone(), hash))
+        })
+    }
+
+    // For an account whose password was already verified
+    pub fn login(&mut self, account_id: &str) -> Result<(Account, String), AccountError> {
+        let account = self
+            .accounts
+            .get(account_id)
+            .cloned()
+            .ok_or(AccountError::InvalidCredentials)?;
+
+        let token = self.issue_login_token(&account.id)?;
+        Ok((account, token))
+    }
+
+    pub fn logout(&mut self, login_token: &str) -> Result<(), AccountError> {
+        let account_id = self
+            .login_tokens
+            .remove(login_token)
+            .ok_or(AccountError::UnknownToken)?;
+        if let Err(err) = self.save() {
+            self.login_tokens
+                .insert(login_token.to_string(), account_id);
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    pub fn account_for_token(&self, login_token: &str) -> Option<&Account> {
+        self.login_tokens
+            .get(login_token)
+            .and_then(|id| self.accounts.get(id))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Account> {
+        self.accounts.get(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::accounts::{hash_password, verify_password, AccountError, AccountStore};
+
+    #[test]
+    fn login_checks_the_hashed_password() {
+        let mut store = AccountStore::default();
+        let hash = hash_password("hunter2").unwrap();
+        let (account, _) = store.register("Alice", Some(hash)).unwrap();
+
+        assert_ne!(account.password_hash.as_deref(), Some("hunter2"));
+        let (account_id, hash) = store.credentials("alice").unwrap();
+        assert!(!verify_password("wrong", &hash));
+        assert!(verify_password("hunter2", &hash));
+
+        let (logged_in, token) = store.login(&account_id).unwrap();
+        assert_eq!(logged_in.id, account.id);
+        assert_eq!(store.account_for_token(&token).unwrap().id, account.id);
+    }
+
+    #[test]
+    fn an_account_that_cant_be_saved_is_not_kept() {
+        let path = std::env::temp_dir()
+            .join(format!("missing-{}", uuid::Uuid::new_v4()))
+            .join("accounts.json");
+
+        let mut store = AccountStore::load(&path).unwrap();
+        assert!(matches!(
+            store.register("Eve", None),
+            Err(AccountError::Storage(_))
+        ));
+        // The name is still free once the disk works again
+        store.path = None;
+        assert!(store.register("Eve", None).is_ok());
+        assert_eq!(store.login_tokens.len(), 1);
+    }
+
+    #[test]
+    fn display_names_are_unique_ignoring_case() {
+        let mut store = AccountStore::default();
+        store.register("Bob", None).unwrap();
+
+        assert_eq!(
+            store.register(" bob ", None).unwrap_err(),
+            AccountError::NameTaken
+        );
+        assert_eq!(
+            store.register("   ", None).unwrap_err(),
+            AccountError::InvalidName
+        );
+    }
+
+    #[test]
+    fn bot_accounts_are_marked_and_share_the_name_space() {
+        let mut store = AccountStore::default();
+        store
+            .register("Dave", Some(hash_password("hunter2").unwrap()))
+            .unwrap();
+        assert_eq!(
+            store.register_bot("dave").unwrap_err(),
+            AccountError::NameTaken
+        );
+
+        let (bot, bot_token) = store.register_bot("Dave's bot").unwrap();
+        assert!(bot.public().bot);
+        assert_eq!(store.account_for_token(&bot_token).unwrap().id, bot.id);
+        // Bots never log in with a password
+        assert!(store.credentials("Dave's bot").is_none());
+    }
+
+    #[test]
+    fn accounts_survive_a_reload() {
+        let path = std::env::temp_dir().join(format!("accounts-{}.json", uuid::Uuid::new_v4()));
+
+        let mut store = AccountStore::load(&path).unwrap();
+        let (account, token) = store.register("Carol", None).unwrap();
+
+        let reloaded = AccountStore::load(&path).unwrap();
+        assert_eq!(
+            reloaded.account_for_token(&token).unwrap().display_name,
+            account.display_name
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
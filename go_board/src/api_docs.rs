@@ -0,0 +1,152 @@
+// OpenAPI description of the HTTP API, generated from the handler annotations in server.rs.
+//
+// Lingo:
+//     spec - the OpenAPI document, served as JSON at /openapi.json
+//     docs - Swagger UI rendering the spec, served under /docs/
+
+use crate::server::*;
+use lazy_static::lazy_static;
+use poem::{
+    async_trait, handler,
+    http::{header, Method, StatusCode},
+    web::Redirect,
+    Endpoint, IntoResponse, Request, Response, Result,
+};
+use std::sync::Arc;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::Config;
+
+pub const SPEC_PATH: &str = "/openapi.json";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Phantom Go",
+        description = "Errors answer with their HTTP status and `{\"code\", \"error\"}`, branch on `code`. Any route can answer 429 `rate_limited`."
+    ),
+    paths(
+        join_game,
+        cell_click,
+        get_dimensions,
+        undo,
+        undo_response,
+        pass,
+        handle_resignation,
+        claim_forfeit,
+        get_group,
+        get_score,
+        resume_play,
+        sync_guess_stones,
+        should_sync,
+        sync_boards,
+        get_game_events,
+        send_game_record,
+        send_chat_message,
+        validate_spectator_open,
+        get_archived_games,
+        send_archived_game_record,
+        get_archived_game_events,
+        post_challenge,
+        get_open_challenges,
+        accept_challenge,
+        challenge_status,
+        cancel_challenge,
+        quick_match,
+        register,
+        login,
+        logout,
+        get_account,
+        get_my_games,
+        get_rating_history,
+        get_leaderboard,
+        get_all_games,
+        get_all_games_admin,
+        healthz,
+        readyz,
+        get_metrics,
+        reset_memory,
+        remove_game,
+        api_v1::list_games,
+        api_v1::get_game,
+        api_v1::list_moves,
+        api_v1::create_move,
+        api_v1::get_sgf,
+        api_v1::create_bot,
+        api_v1::wait_for_turn,
+        api_v1::play_turn,
+    ),
+    tags(
+        (name = "v1", description = "Versioned resource routes, the POST routes at the root are their aliases"),
+        (name = "game", description = "Seats, moves and resignation"),
+        (name = "counting", description = "Marking dead stones after both players passed"),
+        (name = "sync", description = "Polling for board changes"),
+        (name = "chat"),
+        (name = "lobby", description = "Open challenges and quick match"),
+        (name = "accounts"),
+        (name = "bots", description = "Bot accounts and long polling for turns, authorized with `Authorization: Bearer <bot token>`"),
+        (name = "ratings", description = "Elo ratings, one pool per board size and variant"),
+        (name = "archive", description = "Game records"),
+        (name = "admin"),
+    )
+)]
+struct ApiDoc;
+
+lazy_static! {
+    static ref SPEC: String = ApiDoc::openapi()
+        .to_pretty_json()
+        .expect("OpenAPI spec serializes");
+    static ref SWAGGER_CONFIG: Arc<Config<'static>> = Arc::new(Config::from(SPEC_PATH));
+}
+
+#[handler]
+pub fn openapi_spec() -> Response {
+    Response::builder()
+        .content_type("application/json")
+        .body(SPEC.as_str())
+}
+
+// Swagger UI assets are bundled into the binary, like the frontend
+pub struct SwaggerUi;
+
+#[async_trait]
+impl Endpoint for SwaggerUi {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        if req.method() != Method::GET {
+            return Ok(StatusCode::METHOD_NOT_ALLOWED.into());
+        }
+        // Swagger UI loads its assets relative to the page, so it needs the trailing slash
+        let original_path = req.original_uri().path();
+        if !original_path.ends_with('/') && req.uri().path().trim_matches('/').is_empty() {
+            return Ok(Redirect::moved_permanent(format!("{}/", original_path)).into_response());
+        }
+        let path = req.uri().path().trim_start_matches('/');
+
+        match utoipa_swagger_ui::serve(path, SWAGGER_CONFIG.clone()) {
+            Ok(Some(file)) => Ok(Response::builder()
+                .header(header::CONTENT_TYPE, file.content_type)
+                .body(file.bytes.to_vec())),
+            Ok(None) => Ok(Response::builder().status(StatusCode::NOT_FOUND).finish()),
+            Err(_) => Ok(Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .finish()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api_docs::ApiDoc;
+    use utoipa::OpenApi;
+
+    #[test]
+    fn spec_describes_moves_and_their_errors() {
+        let spec = ApiDoc::openapi();
+        let cell_click = spec.paths.paths["/cell-click"].post.as_ref().unwrap();
+
+        assert!(cell_click.request_body.is_some());
+        assert!(cell_click.responses.responses.contains_key("422"));
+        assert!(spec.components.unwrap().schemas.contains_key("ErrorBody"));
+    }
+}
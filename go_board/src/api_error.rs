@@ -0,0 +1,137 @@
+// Errors returned by the HTTP API.
+//
+// Every error is answered with its status code and a JSON body
+// `{"code": "not_your_turn", "error": "It's not your turn"}`. Clients and bots
+// branch on `code`, `error` is meant for people.
+
+use poem::error::ResponseError;
+use poem::http::StatusCode;
+use poem::Response;
+use serde::Serialize;
+use std::fmt;
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ApiError {
+    NotYourTurn,
+    IllegalMove,
+    GameOver,
+    // The game is in the wrong phase for this action, e.g. playing during counting
+    WrongPhase(String),
+    RoomNotFound,
+    NotFound(String),
+    // Missing or unknown credentials
+    Unauthorized(String),
+    // Valid credentials that aren't allowed to do this
+    Forbidden(String),
+    Conflict(String),
+    InvalidRequest(String),
+    RateLimited(String),
+    // The server is shutting down or otherwise can't take this right now
+    Unavailable(String),
+    Internal(String),
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    #[schema(example = "not_your_turn")]
+    code: &'static str,
+    error: String,
+}
+
+impl ApiError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::NotYourTurn => "not_your_turn",
+            ApiError::IllegalMove => "illegal_move",
+            ApiError::GameOver => "game_over",
+            ApiError::WrongPhase(_) => "wrong_phase",
+            ApiError::RoomNotFound => "room_not_found",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::InvalidRequest(_) => "invalid_request",
+            ApiError::RateLimited(_) => "rate_limited",
+            ApiError::Unavailable(_) => "unavailable",
+            ApiError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::NotYourTurn => write!(f, "It's not your turn"),
+            ApiError::IllegalMove => write!(f, "Illegal move"),
+            ApiError::GameOver => write!(f, "Game is already over"),
+            ApiError::RoomNotFound => write!(f, "Game room not found"),
+            ApiError::WrongPhase(msg)
+            | ApiError::NotFound(msg)
+            | ApiError::Unauthorized(msg)
+            | ApiError::Forbidden(msg)
+            | ApiError::Conflict(msg)
+            | ApiError::InvalidRequest(msg)
+            | ApiError::RateLimited(msg)
+            | ApiError::Unavailable(msg)
+            | ApiError::Internal(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ResponseError for ApiError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ApiError::NotYourTurn
+            | ApiError::GameOver
+            | ApiError::WrongPhase(_)
+            | ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::IllegalMove => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::RoomNotFound | ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn as_response(&self) -> Response {
+        let body = ErrorBody {
+            code: self.code(),
+            error: self.to_string(),
+        };
+
+        Response::builder()
+            .status(self.status())
+            .content_type("application/json")
+            .body(serde_json::to_string(&body).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api_error::ApiError;
+    use poem::error::ResponseError;
+    use poem::http::StatusCode;
+
+    #[tokio::test]
+    async fn errors_carry_their_code_and_status() {
+        let response = ApiError::NotYourTurn.as_response();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let body = response.into_body().into_string().await.unwrap();
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["code"], "not_your_turn");
+        assert_eq!(body["error"], "It's not your turn");
+
+        assert_eq!(
+            ApiError::IllegalMove.status(),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(ApiError::RoomNotFound.status(), StatusCode::NOT_FOUND);
+    }
+}
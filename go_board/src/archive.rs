@@ -0,0 +1,159 @@
+// Games the room sweeper moved out of memory, kept on disk so they survive a restart.
+//
+// Lingo:
+//     game id - unique per game, a match string is free again once its room is swept
+//               and the next game under it must not replace this one
+
+use crate::board::{GameEvent, GameResult};
+use crate::storage::{load_json, save_json};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use utoipa::ToSchema;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryReason {
+    Finished,
+    Idle,
+    NeverStarted,
+}
+
+// What's left of a room after the sweeper removes it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArchivedGame {
+    pub game_id: String,
+    pub match_string: String,
+    pub result: Option<GameResult>,
+    pub black_account: Option<String>,
+    pub white_account: Option<String>,
+    pub reason: ExpiryReason,
+    pub archived_at: u64,
+    pub sgf: String,
+    pub events: Vec<GameEvent>,
+}
+
+// An archived game without its record, for listings
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct ArchivedGameSummary {
+    pub game_id: String,
+    pub match_string: String,
+    pub result: Option<GameResult>,
+    pub black_account: Option<String>,
+    pub white_account: Option<String>,
+    pub reason: ExpiryReason,
+    pub archived_at: u64,
+}
+
+impl ArchivedGame {
+    pub fn summary(&self) -> ArchivedGameSummary {
+        ArchivedGameSummary {
+            game_id: self.game_id.clone(),
+            match_string: self.match_string.clone(),
+            result: self.result.clone(),
+            black_account: self.black_account.clone(),
+            white_account: self.white_account.clone(),
+            reason: self.reason,
+            archived_at: self.archived_at,
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ArchiveStore {
+    // Oldest first
+    games: Vec<ArchivedGame>,
+    // Where the store is written after every change, None keeps it in memory only
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl ArchiveStore {
+    // Starts empty if the file doesn't exist yet
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut store: ArchiveStore = load_json(path)?;
+        store.path = Some(path.to_path_buf());
+        Ok(store)
+    }
+
+    // Left to the caller, the sweeper writes a copy so the lock isn't held on the disk
+    pub fn save(&self) -> io::Result<()> {
+        match &self.path {
+            Some(path) => save_json(path, self),
+            None => Ok(()),
+        }
+    }
+
+    // Drops the oldest games past max_games
+    pub fn add(&mut self, games: Vec<ArchivedGame>, max_games: usize) {
+        self.games.extend(games);
+        let excess = self.games.len().saturating_sub(max_games);
+        self.games.drain(..excess);
+    }
+
+    // By game id, or the latest game played under a match string
+    pub fn get(&self, id: &str) -> Option<&ArchivedGame> {
+        self.games
+            .iter()
+            .find(|game| game.game_id == id)
+            .or_else(|| self.games.iter().rev().find(|game| game.match_string == id))
+    }
+
+    // Newest first
+    pub fn games(&self) -> impl Iterator<Item = &ArchivedGame> {
+        self.games.iter().rev()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::archive::{ArchiveStore, ArchivedGame, ExpiryReason};
+
+    fn game(game_id: &str, match_string: &str, archived_at: u64) -> ArchivedGame {
+        ArchivedGame {
+            game_id: game_id.to_string(),
+            match_string: match_string.to_string(),
+            result: None,
+            black_account: None,
+            white_account: None,
+            reason: ExpiryReason::NeverStarted,
+            archived_at,
+            sgf: String::new(),
+            events: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn a_reused_match_string_keeps_both_games() {
+        let mut store = ArchiveStore::default();
+        store.add(vec![game("first", "friday", 1)], 10);
+        store.add(vec![game("second", "friday", 2)], 10);
+
+        assert_eq!(store.get("first").unwrap().archived_at, 1);
+        assert_eq!(store.get("second").unwrap().archived_at, 2);
+        assert_eq!(store.get("friday").unwrap().game_id, "second");
+    }
+
+    #[test]
+    fn the_oldest_games_are_dropped_past_the_cap() {
+        let mut store = ArchiveStore::default();
+        store.add((0..5).map(|i| game(&i.to_string(), "room", i)).collect(), 3);
+
+        let ids: Vec<&str> = store.games().map(|game| game.game_id.as_str()).collect();
+        assert_eq!(ids, ["4", "3", "2"]);
+    }
+
+    #[test]
+    fn the_archive_survives_a_restart() {
+        let path = std::env::temp_dir().join(format!("archive-{}.json", uuid::Uuid::new_v4()));
+
+        let mut store = ArchiveStore::load(&path).unwrap();
+        store.add(vec![game("kept", "room", 1)], 10);
+        store.save().unwrap();
+
+        let reloaded = ArchiveStore::load(&path).unwrap();
+        assert_eq!(reloaded.get("kept").unwrap().match_string, "room");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -2,10 +2,11 @@
 //     islands - sets of groups of Color::Empty from the Board
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::ops::{Deref, DerefMut, Index, IndexMut, RangeInclusive};
 use std::time::SystemTime;
-use std::{io, usize};
+use utoipa::ToSchema;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Color {
@@ -15,25 +16,26 @@ pub enum Color {
     Invalid,
 }
 
-impl Color {
-    pub fn to_string(&self) -> String {
-        match &self {
-            Color::Empty => ".".into(),
-            Color::White => "#".into(),
-            Color::Black => "O".into(),
-            Color::Invalid => "/".into(),
-        }
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            Color::Empty => ".",
+            Color::White => "#",
+            Color::Black => "O",
+            Color::Invalid => "/",
+        };
+        write!(f, "{}", symbol)
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Debug, Serialize)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize, ToSchema)]
 pub enum Player {
     White,
     Black,
 }
 
 impl Player {
-    fn to_color(&self) -> Color {
+    fn to_color(self) -> Color {
         match self {
             Player::Black => Color::Black,
             Player::White => Color::White,
@@ -46,47 +48,46 @@ impl Player {
             Player::White => Player::Black,
         }
     }
+}
 
-    pub fn to_string(self) -> String {
+impl fmt::Display for Player {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Player::Black => "black".to_string(),
-            Player::White => "white".to_string(),
-        }
-    }
-
-    pub fn from_string(s: &str) -> Self {
-        match s.to_lowercase().as_str() {
-            "black" => Player::Black,
-            // Not sure what happens for "spectator" yet, but player boards are a priority
-            _ => Player::White,
+            Player::Black => write!(f, "black"),
+            Player::White => write!(f, "white"),
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
 pub enum GameResult {
     Points(Player, f32),
     Resignation(Player),
+    // Winner, the opponent abandoned their seat
+    Forfeit(Player),
     Draw,
 }
 
-impl GameResult {
-    pub fn to_string(&self) -> String {
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            GameResult::Draw => String::from("D R A W !"),
+            GameResult::Draw => write!(f, "D R A W !"),
             GameResult::Points(player, result) => match player {
-                Player::Black => format!("Black +{}", result),
-                Player::White => format!("White +{}", result),
+                Player::Black => write!(f, "Black +{}", result),
+                Player::White => write!(f, "White +{}", result),
             },
             GameResult::Resignation(player) => match player {
-                Player::Black => format!("White + R"),
-                Player::White => format!("Black + R"),
+                Player::Black => write!(f, "White + R"),
+                Player::White => write!(f, "Black + R"),
             },
+            GameResult::Forfeit(winner) => write!(f, "{} + F", winner),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ToSchema,
+)]
 pub struct Loc {
     pub row: usize,
     pub col: usize,
@@ -167,19 +168,28 @@ impl Loc {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct Move {
     pub player: Player,
     pub loc: Loc,
 }
 
+// Everything that happened in the game, in order. Unlike game_history,
+// undone moves stay here and the undo itself is recorded
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum GameEvent {
+    Move(Move),
+    Undo(Move),
+    ResumePlay,
+}
+
 #[derive(Clone, Copy, PartialEq)]
 struct BoardSize {
     rows: usize,
     cols: usize,
 }
 
-#[derive(Clone, PartialEq, Debug, Serialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, ToSchema)]
 pub struct GroupsInAtari {
     pub black: HashSet<Vec<Loc>>,
     pub white: HashSet<Vec<Loc>>,
@@ -194,7 +204,7 @@ impl GroupsInAtari {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
 pub struct StonesInAtari {
     pub black: usize,
     pub white: usize,
@@ -304,6 +314,9 @@ pub struct Board {
     pub new_groups_in_atari: GroupsInAtari,
     pub stones_in_atari: StonesInAtari,
     pub game_history: Vec<Move>,
+    pub events: Vec<GameEvent>,
+    // Length of game_history each time players left counting to play on
+    play_resumed_at: Vec<usize>,
     current_player: Player,
     komi: f32,
     black_captures: isize,
@@ -325,6 +338,8 @@ impl Board {
             new_groups_in_atari: GroupsInAtari::new(),
             stones_in_atari: StonesInAtari::new(),
             game_history: vec![],
+            events: vec![],
+            play_resumed_at: vec![],
             current_player: Player::Black,
             komi,
             black_captures: 0,
@@ -345,12 +360,31 @@ impl Board {
         board
     }
 
-    fn reset(&self) -> Self {
-        return Board::new(self.fields.len(), self.fields[0].len(), self.komi);
+    // A position set up directly instead of played, like a bot's guess at the hidden board.
+    // Captures are (black, white)
+    pub fn from_position(
+        fields: Goban,
+        current_player: Player,
+        komi: f32,
+        captures: (isize, isize),
+    ) -> Self {
+        let mut board = Board::new(fields.len(), fields[0].len(), komi);
+        board.fields = fields;
+        board.current_player = current_player;
+        (board.black_captures, board.white_captures) = captures;
+        board
     }
 
-    pub fn get_game_history(&self) -> &Vec<Move> {
-        &self.game_history
+    pub fn komi(&self) -> f32 {
+        self.komi
+    }
+
+    pub fn set_komi(&mut self, komi: f32) {
+        self.komi = komi;
+    }
+
+    fn reset(&self) -> Self {
+        Board::new(self.fields.len(), self.fields[0].len(), self.komi)
     }
 
     pub fn get_current_player(&self) -> Player {
@@ -358,8 +392,7 @@ impl Board {
     }
 
     pub fn get_winner(&self) -> Option<GameResult> {
-        let winner = self.winner.clone();
-        winner
+        self.winner.clone()
     }
 
     pub fn set_winner(&mut self, score: GameResult) {
@@ -378,17 +411,6 @@ impl Board {
         self.fields[loc.row][loc.col] = color;
     }
 
-    pub fn to_string(&self) -> String {
-        let mut board_string = String::new();
-        for row in &self.fields {
-            for field in row {
-                board_string.push_str(&format!("{} ", field.to_string()));
-            }
-            board_string += "\n";
-        }
-        board_string
-    }
-
     pub fn get_black_captures(&self) -> isize {
         self.black_captures
     }
@@ -477,28 +499,7 @@ impl Board {
         (black_points, white_points)
     }
 
-    #[allow(dead_code)]
-    fn remove_dead_stones_for_counting(&mut self) {
-        loop {
-            println!("\nRemove dead stones or input 'r' to calculate the result:\n");
-            println!("{}", self.to_string());
-
-            let player_input = self::take_player_input();
-            match player_input.as_str() {
-                "r" => break,
-                _ => match Loc::from_string(&player_input) {
-                    None => {
-                        println!("\nInvalid location :c\nInput one of the group's stone's location to remove it!");
-                        continue;
-                    }
-                    Some(group_to_remove_loc) => self.remove_group(group_to_remove_loc),
-                },
-            }
-        }
-    }
-
     pub fn count_score(&mut self) -> GameResult {
-        // self.remove_dead_stones_for_counting();
         let all_points = self.count_board_points();
         let black_total_points: f32 = all_points.0 as f32 + self.black_captures as f32;
         let white_total_points: f32 = all_points.1 as f32 + self.white_captures as f32 + self.komi;
@@ -508,29 +509,14 @@ impl Board {
         }
 
         let black_won = black_total_points > white_total_points;
-        let result: GameResult;
 
         if black_won {
-            result = GameResult::Points(Player::Black, black_total_points - white_total_points);
+            GameResult::Points(Player::Black, black_total_points - white_total_points)
         } else {
-            result = GameResult::Points(Player::White, white_total_points - black_total_points);
+            GameResult::Points(Player::White, white_total_points - black_total_points)
         }
-
-        result
-    }
-
-    pub fn handle_resignation(&mut self, player: Player) -> GameResult {
-        let result = GameResult::Resignation(player);
-        println!("{} resigned!", player.to_string());
-        result
-    }
-
-    #[allow(dead_code)]
-    fn board_position_is_reapated(&self, board: Board) -> bool {
-        self.fields == board.fields
     }
 
-    #[allow(dead_code)]
     fn move_is_valid(&mut self, mv: &Move) -> bool {
         if mv.loc.is_pass() {
             return true;
@@ -545,18 +531,14 @@ impl Board {
             return false;
         }
 
-        let mut potential_board = self.clone();
+        let mut potential_board = self.scratch_copy();
         potential_board.unsafe_play(mv);
-        println!("{}", potential_board.to_string());
 
         // If the group has been removed after the move, it was a suicidcal move
         let move_is_suicidal = potential_board.get(mv.loc) == Color::Empty;
         let board_is_repeated = self.snapshots.contains(&potential_board.fields);
         let ko_capture_is_illegal = potential_board.check_illegal_ko_recapture();
 
-        println!("Move is valid: {}", !move_is_suicidal && !board_is_repeated);
-        println!("illegal ko recapture: {}", ko_capture_is_illegal);
-
         if !ko_capture_is_illegal && !move_is_suicidal {
             if board_is_repeated {
                 let board_was_repeated_before =
@@ -574,6 +556,31 @@ impl Board {
         false
     }
 
+    // Enough of the board to try a move on. The ko check only looks at the last few
+    // snapshots and nothing else of the history is needed, so it isn't copied
+    fn scratch_copy(&self) -> Board {
+        let recent = self.snapshot_history.len().saturating_sub(4);
+        Board {
+            board_size: self.board_size,
+            fields: self.fields.clone(),
+            snapshots: HashSet::new(),
+            repeated_snapshots: HashSet::new(),
+            snapshot_history: self.snapshot_history[recent..].to_vec(),
+            groups_in_atari: GroupsInAtari::new(),
+            new_groups_in_atari: GroupsInAtari::new(),
+            stones_in_atari: StonesInAtari::new(),
+            game_history: vec![],
+            events: vec![],
+            play_resumed_at: vec![],
+            current_player: self.current_player,
+            komi: self.komi,
+            black_captures: self.black_captures,
+            white_captures: self.white_captures,
+            winner: None,
+            last_move_timestamp: self.last_move_timestamp,
+        }
+    }
+
     fn capture_surrounding_dead_stones(&mut self, mv: &Move) {
         let group = self.group_stones(mv.loc);
         let opponent_stones = self.get_adjacent_opponent_stones(group);
@@ -587,16 +594,21 @@ impl Board {
 
     fn update_groups_in_atari(&mut self) {
         let mut groups_in_atari: HashSet<Vec<Loc>> = HashSet::new();
+        // Each group is only flood filled once, from its first stone
+        let mut seen: HashSet<Loc> = HashSet::new();
         // Only iterate over the playable area (skip sentinel border)
         let rows = self.fields.len();
         let cols = self.fields[0].len();
         for row in 1..rows - 1 {
             for col in 1..cols - 1 {
                 let loc = Loc { row, col };
-                if self.get(loc) != Color::Empty {
-                    if self.count_liberties(loc) == 1 {
-                        groups_in_atari.insert(self.group_stones(loc));
-                    }
+                if self.get(loc) == Color::Empty || seen.contains(&loc) {
+                    continue;
+                }
+                let group = self.group_stones(loc);
+                seen.extend(group.iter().copied());
+                if self.get_group_liberties(group.clone()).len() == 1 {
+                    groups_in_atari.insert(group);
                 }
             }
         }
@@ -623,11 +635,11 @@ impl Board {
         let current_move_snapshot = &self.snapshot_history[self.snapshot_history.len() - 1];
         let previous_move_snapshot = &self.snapshot_history[self.snapshot_history.len() - 3];
 
-        return current_move_snapshot == previous_move_snapshot;
+        current_move_snapshot == previous_move_snapshot
     }
 
     fn unsafe_play(&mut self, mv: &Move) {
-        self.game_history.push(mv.clone());
+        self.game_history.push(*mv);
 
         if mv.loc.is_pass() {
             self.current_player = self.current_player.opponent();
@@ -648,7 +660,6 @@ impl Board {
         self.snapshot_history.push(self.fields.clone());
     }
 
-    #[allow(dead_code)]
     pub fn play(&mut self, mv: &Move) {
         if self.move_is_valid(mv) {
             self.last_move_timestamp = SystemTime::now();
@@ -656,6 +667,7 @@ impl Board {
             let old_groups_in_atari = self.groups_in_atari.clone();
 
             self.unsafe_play(mv);
+            self.events.push(GameEvent::Move(*mv));
             self.update_groups_in_atari();
 
             let new_groups_in_atari = &self.groups_in_atari;
@@ -742,7 +754,7 @@ impl Board {
         liberties
     }
 
-    fn count_liberties(&self, loc: Loc) -> usize {
+    pub fn count_liberties(&self, loc: Loc) -> usize {
         let group = self.group_stones(loc);
         let liberties: HashSet<Loc> = self.get_group_liberties(group);
         liberties.len()
@@ -762,19 +774,45 @@ impl Board {
     }
 
     pub fn undo(&mut self) {
-        if self.game_history.len() == 0 {
+        if self.game_history.is_empty() {
             return;
         }
 
         let mut new_game_history = self.game_history.clone();
-        new_game_history.pop();
+        let undone_move = new_game_history.pop().unwrap();
+        let mut events = std::mem::take(&mut self.events);
 
+        let play_resumed_at = std::mem::take(&mut self.play_resumed_at);
         *self = self.reset();
 
         for mv in &new_game_history {
             self.play(mv);
         }
+        // A resume stays unless the undo went back to before it
+        self.play_resumed_at = play_resumed_at
+            .into_iter()
+            .filter(|&resumed_at| resumed_at <= new_game_history.len())
+            .collect();
+
+        // Replaying rebuilt the events from scratch, keep the full log instead
+        events.push(GameEvent::Undo(undone_move));
+        self.events = events;
+    }
+    // The game as it stood after the first `moves` moves, used for delayed spectator views
+    pub fn position_after(&self, moves: usize) -> Board {
+        let mut board = self.reset();
+        for mv in self.game_history.iter().take(moves) {
+            board.play(mv);
+        }
+        board.play_resumed_at = self
+            .play_resumed_at
+            .iter()
+            .copied()
+            .filter(|&resumed_at| resumed_at <= moves)
+            .collect();
+        board
     }
+
     // When the argument is (self), not (&self), cloning the board will be needed at every iteration of the while loop
     pub fn last_two_moves_are_pass(&self) -> bool {
         if self.game_history.len() > 1 {
@@ -784,6 +822,19 @@ impl Board {
         false
     }
 
+    // Both players passed, and both passes came after play was last resumed
+    pub fn is_counting(&self) -> bool {
+        let resumed_at = self.play_resumed_at.last().copied().unwrap_or(0);
+        let moves_since_resume = self.game_history.len() - resumed_at;
+        self.last_two_moves_are_pass() && moves_since_resume >= 2
+    }
+
+    // Leaves counting without undoing the passes, used when players disagree about dead stones
+    pub fn resume_play(&mut self) {
+        self.play_resumed_at.push(self.game_history.len());
+        self.events.push(GameEvent::ResumePlay);
+    }
+
     fn convert_num_to_sgf_char(num: usize) -> String {
         match num {
             1 => "a",
@@ -810,30 +861,54 @@ impl Board {
         format!("{}{}", col, row)
     }
 
-    pub fn get_game_sgf(&self) -> String {
-        let mut sgf = format!("(;GM[1]KM[{}]", self.komi);
+    // Comments are keyed by the number of moves played before them, 0 being the root node
+    pub fn get_game_sgf_with_comments(&self, comments: &HashMap<usize, Vec<String>>) -> String {
+        let comment = |move_number: usize| {
+            comments
+                .get(&move_number)
+                .map(|lines| format!("C[{}]", escape_sgf_text(&lines.join("\n"))))
+                .unwrap_or_default()
+        };
+
+        // Without the sentinels, SGF writes columns first when the board isn't square
+        let (rows, cols) = (self.board_size.rows - 2, self.board_size.cols - 2);
+        let size = if rows == cols {
+            rows.to_string()
+        } else {
+            format!("{}:{}", cols, rows)
+        };
+
+        let mut sgf = format!("(;GM[1]SZ[{}]KM[{}]{}", size, self.komi, comment(0));
         let gh = self.game_history.clone();
 
-        for mv in gh.iter() {
+        for (i, mv) in gh.iter().enumerate() {
             let player = match mv.player {
                 Player::Black => "B",
                 _ => "W",
             };
             let loc = Board::convert_loc_to_sgf_chars(mv.loc);
-            sgf.push_str(&format!(";{}[{}]", player, loc));
+            sgf.push_str(&format!(";{}[{}]{}", player, loc, comment(i + 1)));
         }
 
         format!("{})", sgf)
     }
 }
 
-pub fn take_player_input() -> String {
-    let mut player_input = String::new();
-    io::stdin()
-        .read_line(&mut player_input)
-        .expect("Failed to read input");
-    player_input = player_input.trim().to_string();
-    player_input
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in &self.fields {
+            for field in row {
+                write!(f, "{} ", field)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// `]` and `\` have to be escaped inside SGF property values
+fn escape_sgf_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(']', "\\]")
 }
 
 #[cfg(test)]
@@ -842,9 +917,11 @@ mod tests {
 
     use crate::board::Board;
     use crate::board::Color;
+    use crate::board::GameEvent;
     use crate::board::Loc;
     use crate::board::Move;
     use crate::board::Player;
+    use std::collections::HashMap;
 
     #[test]
     fn stones_have_to_be_placed_on_empty_fields() {
@@ -1239,6 +1316,77 @@ mod tests {
         assert!(board.get(Loc { row: 8, col: 2 }) == Color::Empty);
     }
 
+    #[test]
+    fn komi_can_be_changed_after_the_board_is_set_up() {
+        let mut board = Board::new(7, 7, 1.5);
+        board.set_komi(6.5);
+        assert_eq!(board.komi(), 6.5);
+    }
+
+    #[test]
+    fn a_position_can_be_set_up_without_playing_it() {
+        let mut fields = Board::new(7, 7, 1.5).fields;
+        fields[1][1] = Color::Black;
+        fields[2][2] = Color::White;
+
+        let board = Board::from_position(fields, Player::White, 6.5, (2, 3));
+        assert_eq!(board.get(Loc { row: 1, col: 1 }), Color::Black);
+        assert_eq!(board.get(Loc { row: 2, col: 2 }), Color::White);
+        assert_eq!(board.get_current_player(), Player::White);
+        assert_eq!(board.komi(), 6.5);
+        assert_eq!(board.get_black_captures(), 2);
+        assert_eq!(board.get_white_captures(), 3);
+        assert!(board.game_history.is_empty());
+    }
+
+    #[test]
+    fn a_scratch_copy_keeps_the_position_but_not_the_history() {
+        let mut board = Board::new(7, 7, 1.5);
+        for (player, row, col) in [
+            (Player::Black, 1, 1),
+            (Player::White, 2, 2),
+            (Player::Black, 3, 3),
+            (Player::White, 4, 4),
+            (Player::Black, 5, 5),
+        ] {
+            board.play(&Move {
+                player,
+                loc: Loc { row, col },
+            });
+        }
+
+        let copy = board.scratch_copy();
+        assert!(copy.fields == board.fields);
+        assert_eq!(copy.get_current_player(), board.get_current_player());
+        assert!(copy.game_history.is_empty());
+        // Enough for the ko check, nothing more
+        assert_eq!(copy.snapshot_history.len(), 4);
+    }
+
+    #[test]
+    fn a_group_in_atari_is_listed_once() {
+        let mut board = Board::new(7, 7, 1.5);
+        for (player, row, col) in [
+            (Player::Black, 3, 1),
+            (Player::Black, 3, 2),
+            (Player::White, 2, 1),
+            (Player::White, 2, 2),
+            (Player::White, 4, 1),
+            (Player::White, 4, 2),
+        ] {
+            board.play(&Move {
+                player,
+                loc: Loc { row, col },
+            });
+        }
+
+        // Both black stones share the last liberty at 3, 3
+        let black: Vec<&Vec<Loc>> = board.groups_in_atari.black.iter().collect();
+        assert_eq!(black.len(), 1);
+        assert_eq!(black[0].len(), 2);
+        assert!(board.groups_in_atari.white.is_empty());
+    }
+
     #[test]
     fn groups_removal_is_triggered_when_their_liberties_reach_0() {
         let mut board = Board::new(11, 11, 2.0);
@@ -1353,7 +1501,7 @@ mod tests {
             current_move.loc = current_move_coords;
 
             if board.move_is_valid(&current_move) {
-                test_move_history.push(current_move.clone());
+                test_move_history.push(current_move);
                 board.play(&current_move);
                 current_move.player = current_move.player.opponent();
                 moves_left -= 1;
@@ -1383,7 +1531,6 @@ mod tests {
                 assert_ne!(board.get(current_move.loc), Color::Empty);
                 current_move.player = current_move.player.opponent();
 
-                println!();
                 moves_left -= 1;
             }
         }
@@ -1443,6 +1590,140 @@ mod tests {
         assert!(board.get(Loc { row: 3, col: 1 }) == Color::White);
     }
 
+    #[test]
+    fn undo_is_recorded_as_an_event_instead_of_erasing_the_move() {
+        let mut board = Board::new(7, 7, 2.0);
+
+        let first = Move {
+            player: Player::Black,
+            loc: Loc { row: 1, col: 1 },
+        };
+        let second = Move {
+            player: Player::White,
+            loc: Loc { row: 2, col: 2 },
+        };
+
+        board.play(&first);
+        board.play(&second);
+        board.undo();
+
+        assert_eq!(board.game_history, vec![first]);
+        assert_eq!(
+            board.events,
+            vec![
+                GameEvent::Move(first),
+                GameEvent::Move(second),
+                GameEvent::Undo(second)
+            ]
+        );
+    }
+
+    #[test]
+    fn resumed_play_needs_two_new_passes_to_count_again() {
+        let mut board = Board::new(7, 7, 2.0);
+        let pass = |player| Move {
+            player,
+            loc: Loc::pass(),
+        };
+
+        board.play(&pass(Player::Black));
+        board.play(&pass(Player::White));
+        assert!(board.is_counting());
+
+        board.resume_play();
+        assert!(!board.is_counting());
+
+        board.play(&pass(Player::Black));
+        assert!(!board.is_counting());
+
+        board.play(&pass(Player::White));
+        assert!(board.is_counting());
+        assert!(board.events.contains(&GameEvent::ResumePlay));
+    }
+
+    #[test]
+    fn undoing_a_move_after_resuming_play_stays_out_of_counting() {
+        let mut board = Board::new(7, 7, 2.0);
+        let pass = |player| Move {
+            player,
+            loc: Loc::pass(),
+        };
+
+        board.play(&pass(Player::Black));
+        board.play(&pass(Player::White));
+        board.resume_play();
+        board.play(&Move {
+            player: Player::Black,
+            loc: Loc { row: 1, col: 1 },
+        });
+
+        board.undo();
+        assert!(!board.is_counting());
+        assert!(!board.position_after(2).is_counting());
+        assert!(board.position_after(1).play_resumed_at.is_empty());
+
+        // Taking back a pass from before the resume takes the resume back too
+        board.undo();
+        board.play(&pass(Player::White));
+        assert!(board.is_counting());
+    }
+
+    #[test]
+    fn position_after_replays_only_the_first_moves() {
+        let mut board = Board::new(7, 7, 2.0);
+        let moves = [
+            Move {
+                player: Player::Black,
+                loc: Loc { row: 1, col: 1 },
+            },
+            Move {
+                player: Player::White,
+                loc: Loc { row: 2, col: 2 },
+            },
+        ];
+        for mv in &moves {
+            board.play(mv);
+        }
+
+        let delayed = board.position_after(1);
+
+        assert_eq!(delayed.game_history, moves[..1].to_vec());
+        assert_eq!(delayed.fields[1][1], Color::Black);
+        assert_eq!(delayed.fields[2][2], Color::Empty);
+        assert_eq!(delayed.get_current_player(), Player::White);
+    }
+
+    #[test]
+    fn sgf_comments_are_attached_after_their_move() {
+        let mut board = Board::new(7, 7, 2.0);
+        board.play(&Move {
+            player: Player::Black,
+            loc: Loc { row: 1, col: 1 },
+        });
+
+        let comments =
+            HashMap::from([(0, vec!["gl hf".to_string()]), (1, vec!["a]b".to_string()])]);
+
+        assert_eq!(
+            board.get_game_sgf_with_comments(&comments),
+            "(;GM[1]SZ[5]KM[2]C[gl hf];B[aa]C[a\\]b])"
+        );
+    }
+
+    #[test]
+    fn sgf_records_the_board_size() {
+        let board = Board::new(21, 21, 6.5);
+        assert_eq!(
+            board.get_game_sgf_with_comments(&HashMap::new()),
+            "(;GM[1]SZ[19]KM[6.5])"
+        );
+
+        let board = Board::new(6, 5, 0.5);
+        assert!(board
+            .get_game_sgf_with_comments(&HashMap::new())
+            .starts_with("(;GM[1]SZ[3:4]"));
+    }
+
     #[test]
     fn board_position_cannot_be_repeated() {
         let mut board = Board::new(6, 5, 2.0);
@@ -1657,7 +1938,7 @@ mod tests {
             player: Player::Black,
             loc: Loc { row: 1, col: 1 },
         };
-        let expected_move = current_move.clone();
+        let expected_move = current_move;
         assert_eq!(current_move, expected_move);
 
         current_move = Move {
@@ -0,0 +1,813 @@
+// Computer opponents that take the empty seat of a room.
+//
+// A bot plays by the same rules as a human in its seat: it never sees the opponent's
+// stones, only its own and what the referee announces. It learns the rest the way a
+// human does, by trying a point and being told it's illegal.
+//
+// Lingo:
+//     seat view - everything the human in the seat would know right now
+//     refused - points the referee called illegal since the bot's last move. Usually an
+//               opponent stone, but suicide and ko are refused the same way
+//     suspected - points the bot believes hold an opponent stone from earlier turns:
+//                 old refusals and the points around its own captured stones
+//     determinization - one full board guessed from the seat view, with the opponent's
+//                       stones filled in. The MCTS bot searches many of them as if each
+//                       were the real board and adds up how often it tried each move
+
+use crate::board::{Board, Color, Goban, Loc, Move, Player};
+use crate::config::Config;
+use rand::seq::{IteratorRandom, SliceRandom};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use utoipa::ToSchema;
+
+// Guesses tried per determinization, the one closest to the atari announcements is kept
+const SAMPLE_ATTEMPTS: usize = 8;
+// Chance that a suspected point gets one of the opponent's stones before random points do
+const SUSPECT_WEIGHT: f64 = 0.7;
+// Only worth passing back when the search thinks the game is won as it stands
+const PASS_WIN_RATE: f64 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BotKind {
+    // Picks uniformly among the points it hasn't ruled out
+    Random,
+    // Monte Carlo tree search over guesses at the hidden board
+    Mcts,
+}
+
+// Playable coordinates, without the sentinel border
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Point {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl Point {
+    pub fn loc(self) -> Loc {
+        Loc {
+            row: self.row + 1,
+            col: self.col + 1,
+        }
+    }
+
+    fn from_loc(loc: Loc) -> Self {
+        Point {
+            row: loc.row - 1,
+            col: loc.col - 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BotMove {
+    Play(Point),
+    Pass,
+}
+
+#[derive(Clone, Debug)]
+pub struct SeatView {
+    pub player: Player,
+    pub size: usize,
+    pub komi: f32,
+    pub own_stones: HashSet<Point>,
+    pub refused: HashSet<Point>,
+    // The rest is announced by the referee, like to the humans
+    pub own_last_move: Option<Point>,
+    pub opponent_passed: bool,
+    // Stones each side captured so far
+    pub own_captures: isize,
+    pub opponent_captures: isize,
+    // Every opponent move that wasn't a pass left a stone, unless it was captured since
+    pub opponent_stones: usize,
+    // Stones put in atari by the last move
+    pub own_in_atari: usize,
+    pub opponent_in_atari: usize,
+}
+
+impl SeatView {
+    pub fn new(board: &Board, player: Player, refused: HashSet<Point>) -> Self {
+        let own_color = color_of(player);
+        let size = board.fields.len() - 2;
+        let mut own_stones = HashSet::new();
+        for row in 0..size {
+            for col in 0..size {
+                if board.fields[row + 1][col + 1] == own_color {
+                    own_stones.insert(Point { row, col });
+                }
+            }
+        }
+
+        let (own_captures, opponent_captures) = match player {
+            Player::Black => (board.get_black_captures(), board.get_white_captures()),
+            Player::White => (board.get_white_captures(), board.get_black_captures()),
+        };
+        let (own_in_atari, opponent_in_atari) = match player {
+            Player::Black => (board.stones_in_atari.black, board.stones_in_atari.white),
+            Player::White => (board.stones_in_atari.white, board.stones_in_atari.black),
+        };
+        let opponent_moves = board
+            .game_history
+            .iter()
+            .filter(|mv| mv.player != player && mv.loc != Loc::pass())
+            .count();
+        let own_last_move = board
+            .game_history
+            .iter()
+            .rev()
+            .find(|mv| mv.player == player)
+            .filter(|mv| mv.loc != Loc::pass())
+            .map(|mv| Point::from_loc(mv.loc));
+        let opponent_passed = board
+            .game_history
+            .last()
+            .is_some_and(|mv| mv.player != player && mv.loc == Loc::pass());
+
+        SeatView {
+            player,
+            size,
+            komi: board.komi(),
+            own_stones,
+            refused,
+            own_last_move,
+            opponent_passed,
+            own_captures,
+            opponent_captures,
+            opponent_stones: opponent_moves.saturating_sub(own_captures.max(0) as usize),
+            own_in_atari,
+            opponent_in_atari,
+        }
+    }
+
+    // Nothing played yet, for seats that learn the game from the referee instead of a Board
+    pub fn empty(player: Player, size: usize, komi: f32) -> Self {
+        SeatView {
+            player,
+            size,
+            komi,
+            own_stones: HashSet::new(),
+            refused: HashSet::new(),
+            own_last_move: None,
+            opponent_passed: false,
+            own_captures: 0,
+            opponent_captures: 0,
+            opponent_stones: 0,
+            own_in_atari: 0,
+            opponent_in_atari: 0,
+        }
+    }
+
+    fn points(&self) -> impl Iterator<Item = Point> + '_ {
+        (0..self.size).flat_map(move |row| (0..self.size).map(move |col| Point { row, col }))
+    }
+
+    fn neighbours(&self, point: Point) -> impl Iterator<Item = Point> + '_ {
+        let Point { row, col } = point;
+        [
+            row.checked_sub(1).map(|row| Point { row, col }),
+            (row + 1 < self.size).then_some(Point { row: row + 1, col }),
+            col.checked_sub(1).map(|col| Point { row, col }),
+            (col + 1 < self.size).then_some(Point { row, col: col + 1 }),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    // Surrounded by its own stones only, filling it can't help
+    fn is_own_eye(&self, point: Point) -> bool {
+        self.neighbours(point)
+            .all(|neighbour| self.own_stones.contains(&neighbour))
+    }
+
+    // Points that may still be legal as far as the seat knows
+    pub fn candidates(&self) -> impl Iterator<Item = Point> + '_ {
+        self.points()
+            .filter(|point| !self.own_stones.contains(point) && !self.refused.contains(point))
+            .filter(|point| !self.is_own_eye(*point))
+    }
+}
+
+// What the bot remembers between turns
+#[derive(Clone, Debug, Default)]
+pub struct Memory {
+    own_stones: HashSet<Point>,
+    own_captures: isize,
+    suspected: HashSet<Point>,
+}
+
+impl Memory {
+    // Called with every view the bot thinks about
+    pub fn observe(&mut self, view: &SeatView) {
+        // Whatever took our stones was standing next to them
+        for lost in self.own_stones.difference(&view.own_stones) {
+            self.suspected.extend(view.neighbours(*lost));
+        }
+
+        // Our last move captured, the suspected stones next to it are gone
+        if view.own_captures > self.own_captures {
+            if let Some(last) = view.own_last_move {
+                let mut frontier: Vec<Point> = view.neighbours(last).collect();
+                while let Some(point) = frontier.pop() {
+                    if self.suspected.remove(&point) {
+                        frontier.extend(view.neighbours(point));
+                    }
+                }
+            }
+        }
+
+        self.suspected.extend(view.refused.iter().copied());
+        self.suspected
+            .retain(|point| !view.own_stones.contains(point));
+        self.own_stones = view.own_stones.clone();
+        self.own_captures = view.own_captures;
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MctsConfig {
+    // Playouts per move, split evenly over the determinizations
+    pub playouts: usize,
+    pub determinizations: usize,
+    // UCB1 exploration constant
+    pub exploration: f64,
+}
+
+impl MctsConfig {
+    pub fn from_config(config: &Config) -> Self {
+        MctsConfig {
+            playouts: config.bots.playouts,
+            determinizations: config.bots.determinizations,
+            exploration: 1.0,
+        }
+    }
+}
+
+// A bot and what it remembers of the game so far
+#[derive(Clone, Debug)]
+pub struct Bot {
+    pub kind: BotKind,
+    mcts: MctsConfig,
+    memory: Memory,
+}
+
+impl Bot {
+    pub fn new(kind: BotKind, mcts: MctsConfig) -> Self {
+        Bot {
+            kind,
+            mcts,
+            memory: Memory::default(),
+        }
+    }
+
+    pub fn observe(&mut self, view: &SeatView) {
+        self.memory.observe(view);
+    }
+
+    // CPU bound for the MCTS bot, keep it off the async runtime
+    pub fn choose_move(&self, view: &SeatView, rng: &mut impl Rng) -> BotMove {
+        match self.kind {
+            BotKind::Random => choose_random(view, rng),
+            BotKind::Mcts => choose_mcts(view, &self.memory, &self.mcts, rng),
+        }
+    }
+}
+
+// Passing back ends the game instead of dragging it out against a human who's done
+fn choose_random(view: &SeatView, rng: &mut impl Rng) -> BotMove {
+    if view.opponent_passed {
+        return BotMove::Pass;
+    }
+    view.candidates()
+        .choose(rng)
+        .map_or(BotMove::Pass, BotMove::Play)
+}
+
+fn choose_mcts(
+    view: &SeatView,
+    memory: &Memory,
+    config: &MctsConfig,
+    rng: &mut impl Rng,
+) -> BotMove {
+    let candidates: Vec<Point> = view.candidates().collect();
+    if candidates.is_empty() {
+        return BotMove::Pass;
+    }
+
+    let determinizations = config.determinizations.max(1);
+    let playouts = (config.playouts / determinizations).max(1);
+    // point -> (visits, wins) over every determinization
+    let mut totals: HashMap<Point, (f64, f64)> = HashMap::new();
+    for _ in 0..determinizations {
+        let board = determinize(view, memory, rng);
+        for (loc, (visits, wins)) in search(board, &candidates, playouts, config, rng) {
+            let total = totals.entry(Point::from_loc(loc)).or_default();
+            total.0 += visits;
+            total.1 += wins;
+        }
+    }
+
+    let best = totals
+        .into_iter()
+        .max_by(|(_, a), (_, b)| a.0.total_cmp(&b.0));
+    match best {
+        Some((_, (visits, wins))) if view.opponent_passed && wins / visits >= PASS_WIN_RATE => {
+            BotMove::Pass
+        }
+        Some((point, _)) => BotMove::Play(point),
+        // Every guess had the candidates taken, the referee will tell which are
+        None => candidates
+            .choose(rng)
+            .copied()
+            .map_or(BotMove::Pass, BotMove::Play),
+    }
+}
+
+fn color_of(player: Player) -> Color {
+    match player {
+        Player::Black => Color::Black,
+        Player::White => Color::White,
+    }
+}
+
+// The hidden board while it's being guessed, in playable coordinates
+struct Guess<'a> {
+    view: &'a SeatView,
+    stones: HashMap<Point, Color>,
+}
+
+impl Guess<'_> {
+    fn color(&self, point: Point) -> Color {
+        self.stones.get(&point).copied().unwrap_or(Color::Empty)
+    }
+
+    // Number of liberties of the group at `point`
+    fn liberties(&self, point: Point) -> usize {
+        let color = self.color(point);
+        let mut group = vec![point];
+        let mut seen = HashSet::from([point]);
+        let mut liberties = HashSet::new();
+        while let Some(stone) = group.pop() {
+            for neighbour in self.view.neighbours(stone) {
+                let neighbour_color = self.color(neighbour);
+                if neighbour_color == Color::Empty {
+                    liberties.insert(neighbour);
+                } else if neighbour_color == color && seen.insert(neighbour) {
+                    group.push(neighbour);
+                }
+            }
+        }
+        liberties.len()
+    }
+
+    // Refuses a stone that would leave itself or a neighbouring group without liberties
+    fn try_place(&mut self, point: Point, color: Color) -> bool {
+        if self.color(point) != Color::Empty {
+            return false;
+        }
+        self.stones.insert(point, color);
+        let legal = std::iter::once(point)
+            .chain(self.view.neighbours(point))
+            .filter(|point| self.color(*point) != Color::Empty)
+            .all(|point| self.liberties(point) > 0);
+        if !legal {
+            self.stones.remove(&point);
+        }
+        legal
+    }
+
+    fn stones_in_atari(&self, color: Color) -> usize {
+        self.stones
+            .iter()
+            .filter(|(point, stone)| **stone == color && self.liberties(**point) == 1)
+            .count()
+    }
+
+    fn into_board(self) -> Board {
+        let size = self.view.size;
+        let mut fields = Goban(vec![vec![Color::Invalid; size + 2]; size + 2]);
+        for row in 0..size {
+            for col in 0..size {
+                fields[row + 1][col + 1] = self.color(Point { row, col });
+            }
+        }
+        let captures = match self.view.player {
+            Player::Black => (self.view.own_captures, self.view.opponent_captures),
+            Player::White => (self.view.opponent_captures, self.view.own_captures),
+        };
+        Board::from_position(fields, self.view.player, self.view.komi, captures)
+    }
+}
+
+// One full board consistent with what the seat knows. Refused points are filled first,
+// then suspected ones, then random points. Of a few guesses, the one closest to the
+// atari announcements is kept
+fn determinize(view: &SeatView, memory: &Memory, rng: &mut impl Rng) -> Board {
+    let own_color = color_of(view.player);
+    let opponent_color = color_of(view.player.opponent());
+
+    let mut best: Option<(usize, Guess)> = None;
+    for _ in 0..SAMPLE_ATTEMPTS {
+        let mut guess = Guess {
+            view,
+            stones: view
+                .own_stones
+                .iter()
+                .map(|point| (*point, own_color))
+                .collect(),
+        };
+
+        let mut refused: Vec<Point> = view.refused.iter().copied().collect();
+        refused.shuffle(rng);
+        let mut suspected: Vec<Point> = memory
+            .suspected
+            .iter()
+            .filter(|point| !view.refused.contains(point))
+            .copied()
+            .filter(|_| rng.gen_bool(SUSPECT_WEIGHT))
+            .collect();
+        suspected.shuffle(rng);
+        let mut anywhere: Vec<Point> = view.points().collect();
+        anywhere.shuffle(rng);
+
+        let mut placed = 0;
+        for point in refused.into_iter().chain(suspected).chain(anywhere) {
+            if placed == view.opponent_stones {
+                break;
+            }
+            if guess.try_place(point, opponent_color) {
+                placed += 1;
+            }
+        }
+
+        let miss = view
+            .own_in_atari
+            .saturating_sub(guess.stones_in_atari(own_color))
+            + view
+                .opponent_in_atari
+                .saturating_sub(guess.stones_in_atari(opponent_color));
+        if best.as_ref().is_none_or(|(best_miss, _)| miss < *best_miss) {
+            best = Some((miss, guess));
+        }
+        if miss == 0 {
+            break;
+        }
+    }
+
+    let (_, guess) = best.expect("at least one guess");
+    guess.into_board()
+}
+
+struct Node {
+    // The move that led here, None at the root
+    mv: Option<Move>,
+    visits: f64,
+    // For whoever played `mv`
+    wins: f64,
+    children: Vec<usize>,
+    untried: Vec<Loc>,
+}
+
+impl Node {
+    fn new(mv: Option<Move>, untried: Vec<Loc>) -> Self {
+        Node {
+            mv,
+            visits: 0.0,
+            wins: 0.0,
+            children: Vec::new(),
+            untried,
+        }
+    }
+}
+
+fn is_eye(board: &Board, loc: Loc, color: Color) -> bool {
+    [loc.row - 1, loc.row + 1]
+        .map(|row| board.fields[row][loc.col])
+        .into_iter()
+        .chain([loc.col - 1, loc.col + 1].map(|col| board.fields[loc.row][col]))
+        .all(|neighbour| neighbour == color || neighbour == Color::Invalid)
+}
+
+// Empty points that aren't the mover's own eye, in random order
+fn playable(board: &Board, rng: &mut impl Rng) -> Vec<Loc> {
+    let color = color_of(board.get_current_player());
+    let size = board.fields.len();
+    let mut moves: Vec<Loc> = (1..size - 1)
+        .flat_map(|row| (1..size - 1).map(move |col| Loc { row, col }))
+        .filter(|loc| board.fields[loc.row][loc.col] == Color::Empty && !is_eye(board, *loc, color))
+        .collect();
+    moves.shuffle(rng);
+    moves
+}
+
+// Board::play ignores illegal moves, the history tells whether it was played
+fn try_play(board: &mut Board, loc: Loc) -> Option<Move> {
+    let mv = Move {
+        player: board.get_current_player(),
+        loc,
+    };
+    let moves = board.game_history.len();
+    board.play(&mv);
+    (board.game_history.len() > moves).then_some(mv)
+}
+
+// Random moves until both pass, None is a draw
+fn playout(board: &mut Board, rng: &mut impl Rng) -> Option<Player> {
+    let max_moves = 2 * (board.fields.len() - 2).pow(2);
+    let mut passes = 0;
+    for _ in 0..max_moves {
+        let moves = playable(board, rng);
+        if moves.into_iter().any(|loc| try_play(board, loc).is_some()) {
+            passes = 0;
+            continue;
+        }
+
+        board.play(&Move {
+            player: board.get_current_player(),
+            loc: Loc::pass(),
+        });
+        passes += 1;
+        if passes == 2 {
+            break;
+        }
+    }
+
+    area_winner(board)
+}
+
+// Stones plus the empty points only one colour touches. Random playouts capture a lot,
+// so counting captures like the real game would mostly measure noise. None is a draw
+fn area_winner(board: &Board) -> Option<Player> {
+    let size = board.fields.len();
+    let (mut black, mut white) = (0.0_f32, board.komi());
+    for row in 1..size - 1 {
+        for col in 1..size - 1 {
+            let loc = Loc { row, col };
+            match board.fields[row][col] {
+                Color::Black => black += 1.0,
+                Color::White => white += 1.0,
+                _ if is_eye(board, loc, Color::Black) => black += 1.0,
+                _ if is_eye(board, loc, Color::White) => white += 1.0,
+                _ => {}
+            }
+        }
+    }
+
+    match black.total_cmp(&white) {
+        Ordering::Greater => Some(Player::Black),
+        Ordering::Less => Some(Player::White),
+        Ordering::Equal => None,
+    }
+}
+
+// UCT on one determinization, with the root limited to the seat's candidates.
+// Returns the visits and wins of every root move that was legal on this board
+fn search(
+    root: Board,
+    candidates: &[Point],
+    playouts: usize,
+    config: &MctsConfig,
+    rng: &mut impl Rng,
+) -> HashMap<Loc, (f64, f64)> {
+    let mut root_moves: Vec<Loc> = candidates.iter().map(|point| point.loc()).collect();
+    root_moves.shuffle(rng);
+    let mut nodes = vec![Node::new(None, root_moves)];
+
+    for _ in 0..playouts {
+        let mut board = root.clone();
+        let mut path = vec![0];
+        let mut node = 0;
+
+        // Selection, through fully expanded nodes
+        while nodes[node].untried.is_empty() && !nodes[node].children.is_empty() {
+            let log_visits = nodes[node].visits.ln();
+            let ucb = |child: usize| {
+                let child = &nodes[child];
+                child.wins / child.visits + config.exploration * (log_visits / child.visits).sqrt()
+            };
+            node = nodes[node]
+                .children
+                .iter()
+                .copied()
+                .max_by(|a, b| ucb(*a).total_cmp(&ucb(*b)))
+                .expect("children");
+            if let Some(mv) = nodes[node].mv {
+                board.play(&mv);
+            }
+            path.push(node);
+        }
+
+        // Expansion, with the first untried move that's legal here
+        while let Some(loc) = nodes[node].untried.pop() {
+            if let Some(mv) = try_play(&mut board, loc) {
+                let child = nodes.len();
+                nodes.push(Node::new(Some(mv), playable(&board, rng)));
+                nodes[node].children.push(child);
+                path.push(child);
+                break;
+            }
+        }
+
+        let winner = playout(&mut board, rng);
+        for index in path {
+            let node = &mut nodes[index];
+            node.visits += 1.0;
+            node.wins += match (winner, node.mv) {
+                (None, _) => 0.5,
+                (Some(winner), Some(mv)) if winner == mv.player => 1.0,
+                _ => 0.0,
+            };
+        }
+    }
+
+    nodes[0]
+        .children
+        .iter()
+        .filter_map(|child| {
+            let child = &nodes[*child];
+            child.mv.map(|mv| (mv.loc, (child.visits, child.wins)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{Board, Color, Loc, Move, Player};
+    use crate::bot::{
+        choose_random, determinize, Bot, BotKind, BotMove, MctsConfig, Memory, Point, SeatView,
+    };
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashSet;
+
+    fn play(board: &mut Board, player: Player, row: usize, col: usize) {
+        board.play(&Move {
+            player,
+            loc: Loc {
+                row: row + 1,
+                col: col + 1,
+            },
+        });
+    }
+
+    fn pass(board: &mut Board, player: Player) {
+        board.play(&Move {
+            player,
+            loc: Loc::pass(),
+        });
+    }
+
+    #[test]
+    fn view_holds_only_own_stones() {
+        let mut board = Board::new(7, 7, 1.5);
+        play(&mut board, Player::Black, 0, 0);
+        play(&mut board, Player::White, 2, 2);
+        play(&mut board, Player::Black, 4, 4);
+
+        let view = SeatView::new(&board, Player::White, HashSet::new());
+        assert_eq!(view.size, 5);
+        assert_eq!(view.own_stones, HashSet::from([Point { row: 2, col: 2 }]));
+        assert_eq!(view.opponent_stones, 2);
+        assert_eq!(view.own_last_move, Some(Point { row: 2, col: 2 }));
+        assert!(!view.opponent_passed);
+    }
+
+    #[test]
+    fn random_bot_skips_known_points_and_passes_when_none_are_left() {
+        let mut board = Board::new(5, 5, 1.5);
+        play(&mut board, Player::Black, 0, 0);
+        play(&mut board, Player::White, 2, 2);
+        let mut rng = StdRng::seed_from_u64(7);
+
+        // On a 3x3 board, everything but the center ruled out
+        let mut refused: HashSet<Point> = (0..3)
+            .flat_map(|row| (0..3).map(move |col| Point { row, col }))
+            .filter(|point| *point != Point { row: 1, col: 1 })
+            .collect();
+        let view = SeatView::new(&board, Player::Black, refused.clone());
+        for _ in 0..20 {
+            assert_eq!(
+                choose_random(&view, &mut rng),
+                BotMove::Play(Point { row: 1, col: 1 })
+            );
+        }
+
+        refused.insert(Point { row: 1, col: 1 });
+        let view = SeatView::new(&board, Player::Black, refused);
+        assert_eq!(choose_random(&view, &mut rng), BotMove::Pass);
+    }
+
+    #[test]
+    fn random_bot_passes_back_and_never_fills_its_eyes() {
+        let mut board = Board::new(5, 5, 1.5);
+        play(&mut board, Player::Black, 0, 1);
+        pass(&mut board, Player::White);
+        play(&mut board, Player::Black, 1, 0);
+        play(&mut board, Player::White, 2, 2);
+
+        let view = SeatView::new(&board, Player::Black, HashSet::new());
+        assert!(!view
+            .candidates()
+            .any(|point| point == Point { row: 0, col: 0 }));
+
+        pass(&mut board, Player::Black);
+        let mut rng = StdRng::seed_from_u64(7);
+        let view = SeatView::new(&board, Player::White, HashSet::new());
+        assert!(view.opponent_passed);
+        assert_eq!(choose_random(&view, &mut rng), BotMove::Pass);
+    }
+
+    #[test]
+    fn determinizations_keep_what_the_seat_knows() {
+        let mut board = Board::new(7, 7, 6.5);
+        play(&mut board, Player::Black, 2, 2);
+        play(&mut board, Player::White, 0, 0);
+        play(&mut board, Player::Black, 2, 3);
+        play(&mut board, Player::White, 4, 4);
+        play(&mut board, Player::Black, 1, 1);
+
+        let refused = HashSet::from([Point { row: 2, col: 3 }]);
+        let view = SeatView::new(&board, Player::White, refused);
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..20 {
+            let guess = determinize(&view, &Memory::default(), &mut rng);
+            let stones = |color| {
+                (1..6)
+                    .flat_map(|row| (1..6).map(move |col| (row, col)))
+                    .filter(|(row, col)| guess.fields[*row][*col] == color)
+                    .count()
+            };
+            assert_eq!(stones(Color::White), 2);
+            assert_eq!(stones(Color::Black), 3);
+            // Own stones stay where they are, refused points hold opponent stones
+            assert_eq!(guess.fields[1][1], Color::White);
+            assert_eq!(guess.fields[3][4], Color::Black);
+            assert_eq!(guess.get_current_player(), Player::White);
+        }
+    }
+
+    #[test]
+    fn memory_suspects_whatever_captured_its_stones() {
+        let mut memory = Memory::default();
+        let mut board = Board::new(7, 7, 1.5);
+        play(&mut board, Player::Black, 0, 1);
+        play(&mut board, Player::White, 0, 0);
+        memory.observe(&SeatView::new(&board, Player::White, HashSet::new()));
+
+        play(&mut board, Player::Black, 1, 0);
+        let view = SeatView::new(&board, Player::White, HashSet::new());
+        assert!(view.own_stones.is_empty());
+        memory.observe(&view);
+        assert_eq!(
+            memory.suspected,
+            HashSet::from([Point { row: 0, col: 1 }, Point { row: 1, col: 0 }])
+        );
+    }
+
+    #[test]
+    fn mcts_bot_wins_a_capturing_race() {
+        // Black's corner and White's three stones are both in atari, and black found
+        // White's stones. Whoever captures first wins
+        let mut board = Board::new(7, 7, 0.5);
+        for (row, col) in [
+            (0, 0),
+            (0, 2),
+            (0, 1),
+            (1, 1),
+            (1, 0),
+            (1, 2),
+            (0, 3),
+            (3, 0),
+            (1, 3),
+            (4, 2),
+            (2, 2),
+            (3, 4),
+        ] {
+            let player = board.get_current_player();
+            play(&mut board, player, row, col);
+        }
+
+        let mut bot = Bot::new(
+            BotKind::Mcts,
+            MctsConfig {
+                playouts: 400,
+                determinizations: 2,
+                exploration: 1.0,
+            },
+        );
+        let mut rng = StdRng::seed_from_u64(11);
+        let refused = HashSet::from([
+            Point { row: 0, col: 2 },
+            Point { row: 1, col: 1 },
+            Point { row: 1, col: 2 },
+        ]);
+        let view = SeatView::new(&board, Player::Black, refused);
+        bot.observe(&view);
+        assert_eq!(
+            bot.choose_move(&view, &mut rng),
+            BotMove::Play(Point { row: 2, col: 1 })
+        );
+    }
+}
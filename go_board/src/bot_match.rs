@@ -0,0 +1,146 @@
+// Bots playing each other locally, without a server, to compare them or pick a playout
+// budget. Each bot only gets its own seat view, like on the server.
+//
+//     go_board bot-match --black mcts --white random --games 10 --board-size 9
+
+use crate::board::{Board, GameResult, Loc, Move, Player};
+use crate::bot::{Bot, BotKind, BotMove, MctsConfig, Point, SeatView};
+use crate::config::Config;
+use crate::room_settings::RoomSettings;
+use clap::Args;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashSet;
+use std::time::Instant;
+
+#[derive(Debug, Args)]
+pub struct BotMatchArgs {
+    #[arg(long, value_enum, default_value = "mcts")]
+    black: BotKind,
+    #[arg(long, value_enum, default_value = "random")]
+    white: BotKind,
+    #[arg(long, default_value_t = 1)]
+    games: usize,
+    /// Playable size, without the border
+    #[arg(long, default_value_t = 9)]
+    board_size: usize,
+    #[arg(long, default_value_t = 6.5)]
+    komi: f32,
+    /// MCTS playouts per move, the server's bot setting when left out
+    #[arg(long)]
+    playouts: Option<usize>,
+    /// Makes the games repeatable
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+pub fn run(args: BotMatchArgs, config: &Config) -> Result<(), String> {
+    RoomSettings {
+        board_size: args.board_size,
+        komi: args.komi,
+        ..RoomSettings::default()
+    }
+    .validate()?;
+
+    let mut mcts = MctsConfig::from_config(config);
+    if let Some(playouts) = args.playouts.filter(|playouts| *playouts > 0) {
+        mcts.playouts = playouts;
+    }
+    let mut rng = match args.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    let mut wins = (0, 0);
+    for game in 1..=args.games {
+        let started = Instant::now();
+        let mut black = Bot::new(args.black, mcts);
+        let mut white = Bot::new(args.white, mcts);
+        let (result, moves) =
+            play_game(&mut black, &mut white, args.board_size, args.komi, &mut rng);
+
+        match result {
+            GameResult::Points(Player::Black, _) => wins.0 += 1,
+            GameResult::Points(Player::White, _) => wins.1 += 1,
+            _ => {}
+        }
+        println!(
+            "game {}: {} after {} moves in {:.1}s",
+            game,
+            result,
+            moves,
+            started.elapsed().as_secs_f32()
+        );
+    }
+
+    println!(
+        "black ({:?}) won {}, white ({:?}) won {} of {} games",
+        args.black, wins.0, args.white, wins.1, args.games
+    );
+    Ok(())
+}
+
+// Plays until both pass, then counts without removing dead stones. Returns the result
+// and how many moves were played
+pub fn play_game(
+    black: &mut Bot,
+    white: &mut Bot,
+    board_size: usize,
+    komi: f32,
+    rng: &mut impl Rng,
+) -> (GameResult, usize) {
+    let mut board = Board::new(board_size + 2, board_size + 2, komi);
+    let mut refused: HashSet<Point> = HashSet::new();
+    // Long random endgames can repeat for a while, cut them off
+    let max_moves = 4 * board_size * board_size;
+
+    while !board.is_counting() && board.game_history.len() < max_moves {
+        let player = board.get_current_player();
+        let bot = match player {
+            Player::Black => &mut *black,
+            Player::White => &mut *white,
+        };
+        let view = SeatView::new(&board, player, refused.clone());
+        bot.observe(&view);
+
+        let choice = bot.choose_move(&view, rng);
+        let loc = match choice {
+            BotMove::Play(point) => point.loc(),
+            BotMove::Pass => Loc::pass(),
+        };
+        let moves = board.game_history.len();
+        board.play(&Move { player, loc });
+
+        match choice {
+            BotMove::Play(point) if board.game_history.len() == moves => {
+                refused.insert(point);
+            }
+            _ => refused.clear(),
+        }
+    }
+
+    (board.count_score(), board.game_history.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::{Bot, BotKind, MctsConfig};
+    use crate::bot_match::play_game;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn bots_play_a_game_to_the_end() {
+        let mcts = MctsConfig {
+            playouts: 16,
+            determinizations: 2,
+            exploration: 1.0,
+        };
+        let mut black = Bot::new(BotKind::Mcts, mcts);
+        let mut white = Bot::new(BotKind::Random, mcts);
+        let mut rng = StdRng::seed_from_u64(5);
+
+        let (_, moves) = play_game(&mut black, &mut white, 5, 0.5, &mut rng);
+        assert!((2..=4 * 25).contains(&moves));
+    }
+}
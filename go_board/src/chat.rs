@@ -0,0 +1,199 @@
+// Room chat.
+//
+// Lingo:
+//     players channel - the two seated players talking to each other, spectators can read it
+//     spectators channel - spectators see the whole board, so players can't read this channel
+//                          until the game is over
+
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+pub const MAX_MESSAGE_LENGTH: usize = 300;
+// At most this many messages per sender within the window
+const RATE_LIMIT_MESSAGES: usize = 5;
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatChannel {
+    Players,
+    Spectators,
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct ChatMessage {
+    pub channel: ChatChannel,
+    pub author: String,
+    pub text: String,
+    // Moves played when the message was sent, places it in the game record
+    pub move_number: usize,
+    pub sent_at: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ChatError {
+    Empty,
+    TooLong,
+    RateLimited,
+}
+
+#[derive(Clone, Default)]
+pub struct RoomChat {
+    messages: Vec<ChatMessage>,
+    // sender -> when their recent messages were sent
+    recent: HashMap<String, VecDeque<Instant>>,
+}
+
+impl RoomChat {
+    // `sender` identifies who is rate limited, it's never shown to anyone
+    pub fn post(
+        &mut self,
+        sender: &str,
+        message: ChatMessage,
+        now: Instant,
+    ) -> Result<(), ChatError> {
+        let text = message.text.trim();
+
+        if text.is_empty() {
+            return Err(ChatError::Empty);
+        }
+        if text.chars().count() > MAX_MESSAGE_LENGTH {
+            return Err(ChatError::TooLong);
+        }
+
+        let recent = self.recent.entry(sender.to_string()).or_default();
+        while recent
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= RATE_LIMIT_WINDOW)
+        {
+            recent.pop_front();
+        }
+        if recent.len() >= RATE_LIMIT_MESSAGES {
+            return Err(ChatError::RateLimited);
+        }
+        recent.push_back(now);
+
+        self.messages.push(ChatMessage {
+            text: text.to_string(),
+            ..message
+        });
+        Ok(())
+    }
+
+    pub fn messages(&self) -> &[ChatMessage] {
+        &self.messages
+    }
+
+    pub fn visible_to(&self, can_read_spectators: bool) -> Vec<ChatMessage> {
+        self.messages
+            .iter()
+            .filter(|message| can_read_spectators || message.channel == ChatChannel::Players)
+            .cloned()
+            .collect()
+    }
+}
+
+// SGF comments keyed by the number of moves played before them, 0 being the root node
+pub fn sgf_comments(messages: &[ChatMessage]) -> HashMap<usize, Vec<String>> {
+    let mut comments: HashMap<usize, Vec<String>> = HashMap::new();
+
+    for message in messages {
+        let channel = match message.channel {
+            ChatChannel::Players => "players",
+            ChatChannel::Spectators => "spectators",
+        };
+        comments
+            .entry(message.move_number)
+            .or_default()
+            .push(format!(
+                "[{}] {}: {}",
+                channel, message.author, message.text
+            ));
+    }
+
+    comments
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chat::{ChatChannel, ChatError, ChatMessage, RoomChat, RATE_LIMIT_WINDOW};
+    use std::time::Instant;
+
+    fn message(channel: ChatChannel, text: &str) -> ChatMessage {
+        ChatMessage {
+            channel,
+            author: "black".to_string(),
+            text: text.to_string(),
+            move_number: 0,
+            sent_at: 0,
+        }
+    }
+
+    #[test]
+    fn players_only_see_their_own_channel() {
+        let mut chat = RoomChat::default();
+        let now = Instant::now();
+
+        chat.post("black", message(ChatChannel::Players, "gl hf"), now)
+            .unwrap();
+        chat.post(
+            "spectator",
+            message(ChatChannel::Spectators, "B is dead"),
+            now,
+        )
+        .unwrap();
+
+        assert_eq!(chat.visible_to(false).len(), 1);
+        assert_eq!(chat.visible_to(false)[0].text, "gl hf");
+        assert_eq!(chat.visible_to(true).len(), 2);
+    }
+
+    #[test]
+    fn senders_are_rate_limited_within_the_window() {
+        let mut chat = RoomChat::default();
+        let now = Instant::now();
+
+        for _ in 0..5 {
+            chat.post("black", message(ChatChannel::Players, "hi"), now)
+                .unwrap();
+        }
+
+        assert_eq!(
+            chat.post("black", message(ChatChannel::Players, "hi"), now),
+            Err(ChatError::RateLimited)
+        );
+        assert_eq!(
+            chat.post("white", message(ChatChannel::Players, "hi"), now),
+            Ok(())
+        );
+        assert_eq!(
+            chat.post(
+                "black",
+                message(ChatChannel::Players, "hi"),
+                now + RATE_LIMIT_WINDOW
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn empty_and_long_messages_are_refused() {
+        let mut chat = RoomChat::default();
+        let now = Instant::now();
+
+        assert_eq!(
+            chat.post("black", message(ChatChannel::Players, "   "), now),
+            Err(ChatError::Empty)
+        );
+        assert_eq!(
+            chat.post(
+                "black",
+                message(ChatChannel::Players, &"a".repeat(301)),
+                now
+            ),
+            Err(ChatError::TooLong)
+        );
+    }
+}
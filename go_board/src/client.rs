@@ -0,0 +1,547 @@
+// Terminal client, plays one seat of a room on a running server:
+//
+//     go_board play --server http://localhost:8000 --match friday
+//
+// It only talks HTTP, through /join-game and the long polled /api/v1 turn route, so it
+// knows what any player in the seat knows: its own stones and the referee's announcements.
+//
+// Lingo:
+//     vertex - a point as GTP writes it, column letter without I and row number from the bottom
+//     refused - points the referee turned down this turn, the opponent is probably there
+
+use crate::bot::Point;
+use crate::gtp::{parse_vertex, vertex};
+use clap::Args;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+// Longer than the server holds a turn request open
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+const HELP: &str = "\
+Commands:
+    D4        play there, or mark the group there as dead while counting
+    pass      pass your turn, both players passing starts counting
+    resign    give the game up
+    accept    accept the dead stones while counting
+    resume    leave counting and keep playing
+    y / n     answer your opponent's undo request
+    help      show this
+    quit      leave, rejoin later with --token";
+
+#[derive(Debug, Args)]
+pub struct PlayArgs {
+    /// Server to play on
+    #[arg(long, default_value = "http://localhost:8000")]
+    server: String,
+    /// Room to join, it's created when it doesn't exist yet
+    #[arg(long = "match")]
+    match_string: String,
+    /// Session token of a seat to rejoin
+    #[arg(long)]
+    token: Option<String>,
+    /// Play as an account, so the game counts for its rating
+    #[arg(long, env = "LOGIN_TOKEN", hide_env_values = true)]
+    login_token: Option<String>,
+    /// Board size, only used when this creates the room
+    #[arg(long)]
+    size: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Action {
+    Move,
+    AnswerUndo,
+    Count,
+    Wait,
+    GameOver,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct StonesInAtari {
+    black: usize,
+    white: usize,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Announcement {
+    move_number: usize,
+    player: String,
+    kind: String,
+    captured: usize,
+    stones_in_atari: StonesInAtari,
+}
+
+// The seat's view, as the turn route answers it
+#[derive(Clone, Debug, Deserialize)]
+pub struct Turn {
+    action: Action,
+    board: Vec<Vec<String>>,
+    move_number: usize,
+    black_captures: isize,
+    white_captures: isize,
+    counting_version: usize,
+    winner: Option<String>,
+    dead_stones: Vec<Vec<usize>>,
+    announcements: Vec<Announcement>,
+    next_since: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct JoinResponse {
+    color: String,
+    session_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    code: String,
+    error: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Play(Point),
+    Pass,
+    Resign,
+    Accept,
+    Resume,
+    Answer(bool),
+    Help,
+    Quit,
+}
+
+pub fn parse_command(text: &str, size: usize) -> Result<Command, String> {
+    let text = text.trim().to_lowercase();
+    let command = match text.as_str() {
+        "resign" => Command::Resign,
+        "accept" => Command::Accept,
+        "resume" => Command::Resume,
+        "y" | "yes" => Command::Answer(true),
+        "n" | "no" => Command::Answer(false),
+        "help" | "?" => Command::Help,
+        "quit" | "q" | "exit" => Command::Quit,
+        _ => match parse_vertex(&text, size) {
+            Ok(Some(point)) => Command::Play(point),
+            Ok(None) => Command::Pass,
+            Err(_) => return Err(format!("Can't read '{}', type help", text)),
+        },
+    };
+    Ok(command)
+}
+
+// Own stones are X or O, dead ones lowercase, refused points ?
+pub fn render_board(turn: &Turn, refused: &HashSet<Point>) -> String {
+    let size = turn.board.len();
+    let dead: HashSet<Point> = turn
+        .dead_stones
+        .iter()
+        .filter_map(|stone| match stone[..] {
+            [row, col] => Some(Point { row, col }),
+            _ => None,
+        })
+        .collect();
+
+    let columns: String = (0..size)
+        .map(|col| {
+            let name = vertex(Some(Point { row: 0, col }), size);
+            format!(" {}", &name[..1])
+        })
+        .collect();
+
+    let mut lines = vec![format!("   {}", columns)];
+    for (row, cells) in turn.board.iter().enumerate() {
+        let number = size - row;
+        let cells: String = cells
+            .iter()
+            .enumerate()
+            .map(|(col, color)| {
+                let point = Point { row, col };
+                let symbol = match (color.as_str(), dead.contains(&point)) {
+                    ("black", false) => 'X',
+                    ("white", false) => 'O',
+                    ("black", true) => 'x',
+                    ("white", true) => 'o',
+                    _ if refused.contains(&point) => '?',
+                    _ => '.',
+                };
+                format!(" {}", symbol)
+            })
+            .collect();
+        lines.push(format!("{:>2}{} {}", number, cells, number));
+    }
+    lines.push(format!("   {}", columns));
+    lines.push(format!(
+        "Captures: black {}, white {}",
+        turn.black_captures, turn.white_captures
+    ));
+    lines.join("\n")
+}
+
+fn stones(count: usize) -> &'static str {
+    if count == 1 {
+        "stone"
+    } else {
+        "stones"
+    }
+}
+
+pub fn describe(announcement: &Announcement) -> String {
+    let player = &announcement.player;
+    let mut text = match announcement.kind.as_str() {
+        "played" => format!("{} played", player),
+        "passed" => format!("{} passed", player),
+        "undone" => format!("{}'s last move was taken back", player),
+        "resumed_play" => format!("{} resumed play", player),
+        other => format!("{} {}", player, other),
+    };
+    if announcement.captured > 0 {
+        let captured = announcement.captured;
+        text += &format!(", capturing {} {}", captured, stones(captured));
+    }
+    let atari = &announcement.stones_in_atari;
+    for (color, count) in [("black", atari.black), ("white", atari.white)] {
+        if count > 0 {
+            text += &format!(", {} {} {} in atari", count, color, stones(count));
+        }
+    }
+    format!("Move {}: {}", announcement.move_number, text)
+}
+
+fn request_error(err: ureq::Error) -> ErrorBody {
+    match err {
+        ureq::Error::Status(status, response) => {
+            response.into_json().unwrap_or_else(|_| ErrorBody {
+                code: "unknown".to_string(),
+                error: format!("Server answered {}", status),
+            })
+        }
+        ureq::Error::Transport(transport) => ErrorBody {
+            code: "transport".to_string(),
+            error: transport.to_string(),
+        },
+    }
+}
+
+// Match strings are picked by players, so they may need escaping in a path
+fn path_segment(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+struct Client {
+    agent: ureq::Agent,
+    server: String,
+    match_string: String,
+    session_token: String,
+}
+
+impl Client {
+    fn join(args: &PlayArgs) -> Result<(Self, String), String> {
+        let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+        let server = args.server.trim_end_matches('/').to_string();
+
+        let mut body = json!({
+            "match_string": args.match_string,
+            "session_token": args.token,
+            "is_spectator": false,
+            "login_token": args.login_token,
+        });
+        if let Some(size) = args.size {
+            body["settings"] = json!({ "board_size": size });
+        }
+        let joined: JoinResponse = agent
+            .post(&format!("{}/join-game", server))
+            .send_json(body)
+            .map_err(|err| request_error(err).error)?
+            .into_json()
+            .map_err(|err| err.to_string())?;
+
+        if joined.color == "spectator" {
+            return Err(format!("Both seats in {} are taken", args.match_string));
+        }
+        let client = Client {
+            agent,
+            server,
+            match_string: args.match_string.clone(),
+            session_token: joined.session_token,
+        };
+        Ok((client, joined.color))
+    }
+
+    fn parse<T: DeserializeOwned>(response: ureq::Response) -> Result<T, ErrorBody> {
+        response.into_json().map_err(|err| ErrorBody {
+            code: "invalid_response".to_string(),
+            error: err.to_string(),
+        })
+    }
+
+    fn wait_for_turn(&self, since: usize) -> Result<Turn, ErrorBody> {
+        let response = self
+            .agent
+            .get(&format!(
+                "{}/api/v1/games/{}/turn",
+                self.server,
+                path_segment(&self.match_string)
+            ))
+            .query("since", &since.to_string())
+            .set("Authorization", &format!("Bearer {}", self.session_token))
+            .call()
+            .map_err(request_error)?;
+        Self::parse(response)
+    }
+
+    fn play(&self, point: Option<Point>) -> Result<(), ErrorBody> {
+        let body = match point {
+            Some(point) => json!({ "type": "play", "row": point.row, "col": point.col }),
+            None => json!({ "type": "pass" }),
+        };
+        self.agent
+            .post(&format!(
+                "{}/api/v1/games/{}/turn",
+                self.server,
+                path_segment(&self.match_string)
+            ))
+            .set("Authorization", &format!("Bearer {}", self.session_token))
+            .send_json(body)
+            .map_err(request_error)?;
+        Ok(())
+    }
+
+    // The routes from before /api/v1 take the room and seat in the body
+    fn post(&self, path: &str, mut body: Value) -> Result<(), ErrorBody> {
+        body["match_string"] = json!(self.match_string);
+        body["session_token"] = json!(self.session_token);
+        self.agent
+            .post(&format!("{}{}", self.server, path))
+            .send_json(body)
+            .map_err(request_error)?;
+        Ok(())
+    }
+}
+
+fn hint(action: Action) -> &'static str {
+    match action {
+        Action::Move => "Your move: a point like D4, pass or resign",
+        Action::AnswerUndo => "Your opponent wants to take their move back, y or n",
+        Action::Count => "Counting: a point to mark its group dead, accept or resume",
+        Action::Wait | Action::GameOver => "",
+    }
+}
+
+// None at the end of input
+fn read_line(
+    lines: &mut impl Iterator<Item = io::Result<String>>,
+) -> Result<Option<String>, String> {
+    print!("> ");
+    io::stdout().flush().map_err(|err| err.to_string())?;
+    lines.next().transpose().map_err(|err| err.to_string())
+}
+
+pub fn run(args: PlayArgs) -> Result<(), String> {
+    let (client, color) = Client::join(&args)?;
+    println!(
+        "Playing {} in {}. Rejoin with --token {}",
+        color, args.match_string, client.session_token
+    );
+    println!("Type help for the commands");
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut since = 0;
+    let mut refused: HashSet<Point> = HashSet::new();
+    let mut refused_at_move = 0;
+    let mut waiting = false;
+
+    loop {
+        let turn = client.wait_for_turn(since).map_err(|err| err.error)?;
+        for announcement in &turn.announcements {
+            println!("{}", describe(announcement));
+        }
+        since = turn.next_since;
+        // Refusals only tell something about the position they were made in
+        if turn.move_number != refused_at_move {
+            refused.clear();
+            refused_at_move = turn.move_number;
+        }
+
+        match turn.action {
+            Action::Wait => {
+                if !waiting {
+                    println!("{}", render_board(&turn, &refused));
+                    println!("Waiting for your opponent...");
+                    waiting = true;
+                }
+                continue;
+            }
+            Action::GameOver => {
+                println!("{}", render_board(&turn, &refused));
+                println!(
+                    "Game over: {}",
+                    turn.winner.as_deref().unwrap_or("no result")
+                );
+                return Ok(());
+            }
+            _ => waiting = false,
+        }
+
+        println!("{}", render_board(&turn, &refused));
+        println!("{}", hint(turn.action));
+        let size = turn.board.len();
+
+        loop {
+            let Some(line) = read_line(&mut lines)? else {
+                return Ok(());
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let command = match parse_command(&line, size) {
+                Ok(command) => command,
+                Err(msg) => {
+                    println!("{}", msg);
+                    continue;
+                }
+            };
+
+            let result = match (turn.action, command) {
+                (_, Command::Help) => {
+                    println!("{}", HELP);
+                    continue;
+                }
+                (_, Command::Quit) => return Ok(()),
+                (_, Command::Resign) => client.post("/resign", json!({})),
+                (Action::Move, Command::Play(point)) => {
+                    let result = client.play(Some(point));
+                    if result.as_ref().is_err_and(|err| err.code == "illegal_move") {
+                        refused.insert(point);
+                    }
+                    result
+                }
+                (Action::Move, Command::Pass) => client.play(None),
+                (Action::AnswerUndo, Command::Answer(accept)) => {
+                    client.post("/undo-response", json!({ "accept": accept }))
+                }
+                (Action::Count, Command::Play(point)) => {
+                    client.post("/get-group", json!({ "row": point.row, "col": point.col }))
+                }
+                (Action::Count, Command::Accept) => {
+                    client.post("/get-score", json!({ "version": turn.counting_version }))
+                }
+                (Action::Count, Command::Resume) => client.post("/resume-play", json!({})),
+                _ => {
+                    println!("{}", hint(turn.action));
+                    continue;
+                }
+            };
+
+            // Either way the server has the new state
+            if let Err(err) = result {
+                println!("{}", err.error);
+            }
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::Point;
+    use crate::client::{describe, parse_command, render_board, Announcement, Command, Turn};
+    use std::collections::HashSet;
+
+    fn turn(board: &[&str], dead_stones: Vec<Vec<usize>>) -> Turn {
+        let board: Vec<Vec<String>> = board
+            .iter()
+            .map(|row| {
+                row.chars()
+                    .map(|cell| match cell {
+                        'X' => "black",
+                        'O' => "white",
+                        _ => "empty",
+                    })
+                    .map(str::to_string)
+                    .collect()
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "action": "move",
+            "board": board,
+            "move_number": 3,
+            "black_captures": 1,
+            "white_captures": 0,
+            "counting_version": 0,
+            "winner": null,
+            "dead_stones": dead_stones,
+            "announcements": [],
+            "next_since": 3,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn commands_use_gtp_vertices() {
+        assert_eq!(
+            parse_command("d4", 5),
+            Ok(Command::Play(Point { row: 1, col: 3 }))
+        );
+        assert_eq!(
+            parse_command("J1", 9),
+            Ok(Command::Play(Point { row: 8, col: 8 }))
+        );
+        assert_eq!(parse_command(" Pass ", 5), Ok(Command::Pass));
+        assert_eq!(parse_command("y", 5), Ok(Command::Answer(true)));
+        assert!(parse_command("F1", 5).is_err());
+        assert!(parse_command("hello", 5).is_err());
+    }
+
+    #[test]
+    fn board_shows_coordinates_dead_stones_and_refusals() {
+        let turn = turn(
+            &["X....", ".....", "..X..", ".....", "....X"],
+            vec![vec![4, 4]],
+        );
+        let refused = HashSet::from([Point { row: 2, col: 3 }]);
+
+        let expected = [
+            "    A B C D E",
+            " 5 X . . . . 5",
+            " 4 . . . . . 4",
+            " 3 . . X ? . 3",
+            " 2 . . . . . 2",
+            " 1 . . . . x 1",
+            "    A B C D E",
+            "Captures: black 1, white 0",
+        ]
+        .join("\n");
+        assert_eq!(render_board(&turn, &refused), expected);
+    }
+
+    #[test]
+    fn announcements_never_name_a_point() {
+        let announcement: Announcement = serde_json::from_value(serde_json::json!({
+            "move_number": 12,
+            "player": "white",
+            "kind": "played",
+            "captured": 2,
+            "stones_in_atari": { "black": 1, "white": 0 },
+        }))
+        .unwrap();
+
+        assert_eq!(
+            describe(&announcement),
+            "Move 12: white played, capturing 2 stones, 1 black stone in atari"
+        );
+    }
+}
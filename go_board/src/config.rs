@@ -0,0 +1,622 @@
+// Server configuration, read once on startup.
+//
+// Every setting comes from, highest priority first:
+//     1. a command line flag, e.g. `--bind-addr 127.0.0.1:8000`
+//     2. an environment variable (or .env), e.g. `BIND_ADDR`
+//     3. the TOML file passed with `--config` / CONFIG_PATH, see config.example.toml
+//     4. the defaults below
+//
+// The file can set everything, flags and variables cover the settings changed most per
+// deploy. Anything invalid stops the server before it binds, listing every problem.
+
+use crate::bot_match::BotMatchArgs;
+use crate::client::PlayArgs;
+use crate::gtp::GtpArgs;
+use crate::referee::RefereeArgs;
+use crate::room_settings::RoomSettings;
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tracing_subscriber::EnvFilter;
+
+// Shorter admin tokens are easy to guess
+const MIN_ADMIN_TOKEN_LENGTH: usize = 16;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    // One JSON object per line, for log collectors
+    Json,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind_addr: SocketAddr,
+    // Origins allowed to call the API from another site, empty allows any
+    pub cors_origins: Vec<String>,
+    // Required as a bearer token by the admin routes, which are open when unset
+    pub admin_token: Option<String>,
+    // An EnvFilter directive, e.g. `info` or `go_board=debug,poem=warn`
+    pub log_level: String,
+    pub log_format: LogFormat,
+    pub accounts_path: PathBuf,
+    pub ratings_path: PathBuf,
+    pub archive_path: PathBuf,
+    // Turn off when every client comes through the same proxy address
+    pub spectator_ip_check: bool,
+    // Used when a new room or challenge doesn't send its own settings
+    pub default_room: RoomSettings,
+    pub rooms: RoomsConfig,
+    pub presence: PresenceSection,
+    pub shutdown: ShutdownSection,
+    pub rate_limit: RateLimitSection,
+    pub tls: TlsSection,
+    pub bots: BotSection,
+}
+
+// How long rooms are kept before they're archived
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoomsConfig {
+    pub ttl_finished_secs: u64,
+    pub ttl_idle_secs: u64,
+    pub ttl_never_started_secs: u64,
+    pub sweep_interval_secs: u64,
+    // Archived games past this many are dropped, oldest first
+    pub max_archived_games: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PresenceSection {
+    pub heartbeat_timeout_secs: u64,
+    pub reconnect_grace_secs: u64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownSection {
+    pub timeout_secs: u64,
+    pub flush_dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitSection {
+    pub ip_requests: usize,
+    pub ip_window_secs: u64,
+    pub session_requests: usize,
+    pub session_window_secs: u64,
+    pub new_rooms_requests: usize,
+    pub new_rooms_window_secs: u64,
+    pub max_rooms: usize,
+    pub max_match_string_length: usize,
+}
+
+// HTTPS is served when both paths are set
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsSection {
+    pub cert_path: Option<PathBuf>,
+    pub key_path: Option<PathBuf>,
+    // How often the files are checked for a renewed certificate
+    pub reload_interval_secs: u64,
+}
+
+// How hard the MCTS bot thinks, it runs on the server's CPU
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotSection {
+    // Per move, split over the determinizations
+    pub playouts: usize,
+    // Boards guessed per move
+    pub determinizations: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind_addr: SocketAddr::from(([0, 0, 0, 0], 8000)),
+            cors_origins: Vec::new(),
+            admin_token: None,
+            log_level: "info".to_string(),
+            log_format: LogFormat::Text,
+            accounts_path: PathBuf::from("accounts.json"),
+            ratings_path: PathBuf::from("ratings.json"),
+            archive_path: PathBuf::from("archive.json"),
+            spectator_ip_check: true,
+            default_room: RoomSettings::default(),
+            rooms: RoomsConfig::default(),
+            presence: PresenceSection::default(),
+            shutdown: ShutdownSection {
+                timeout_secs: 30,
+                flush_dir: None,
+            },
+            rate_limit: RateLimitSection::default(),
+            tls: TlsSection::default(),
+            bots: BotSection::default(),
+        }
+    }
+}
+
+impl Default for RoomsConfig {
+    fn default() -> Self {
+        RoomsConfig {
+            ttl_finished_secs: 60 * 60,
+            ttl_idle_secs: 24 * 60 * 60,
+            ttl_never_started_secs: 60 * 60,
+            sweep_interval_secs: 60,
+            max_archived_games: 1000,
+        }
+    }
+}
+
+impl Default for PresenceSection {
+    fn default() -> Self {
+        PresenceSection {
+            heartbeat_timeout_secs: 15,
+            reconnect_grace_secs: 120,
+        }
+    }
+}
+
+impl Default for TlsSection {
+    fn default() -> Self {
+        TlsSection {
+            cert_path: None,
+            key_path: None,
+            reload_interval_secs: 60,
+        }
+    }
+}
+
+// A few seconds per move on 9x9, on one core of a release build
+impl Default for BotSection {
+    fn default() -> Self {
+        BotSection {
+            playouts: 200,
+            determinizations: 8,
+        }
+    }
+}
+
+// Clients poll about twice a second per open tab
+impl Default for RateLimitSection {
+    fn default() -> Self {
+        RateLimitSection {
+            ip_requests: 1200,
+            ip_window_secs: 60,
+            session_requests: 300,
+            session_window_secs: 60,
+            new_rooms_requests: 30,
+            new_rooms_window_secs: 60 * 60,
+            max_rooms: 10_000,
+            max_match_string_length: 64,
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+#[command(name = "go_board", version, about = "Phantom Go server")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// TOML config file, flags and environment variables override it
+    #[arg(long, env = "CONFIG_PATH")]
+    config: Option<PathBuf>,
+    #[arg(long, env = "BIND_ADDR")]
+    bind_addr: Option<SocketAddr>,
+    /// Allowed CORS origins, comma separated
+    #[arg(long, env = "FRONTEND_ORIGIN", value_delimiter = ',')]
+    frontend_origin: Option<Vec<String>>,
+    #[arg(long, env = "ADMIN_TOKEN", hide_env_values = true)]
+    admin_token: Option<String>,
+    #[arg(long, env = "RUST_LOG")]
+    log_level: Option<String>,
+    #[arg(long, env = "LOG_FORMAT")]
+    log_format: Option<LogFormat>,
+    #[arg(long, env = "ACCOUNTS_PATH")]
+    accounts_path: Option<PathBuf>,
+    #[arg(long, env = "RATINGS_PATH")]
+    ratings_path: Option<PathBuf>,
+    #[arg(long, env = "ARCHIVE_PATH")]
+    archive_path: Option<PathBuf>,
+    #[arg(long, env = "SPECTATOR_IP_CHECK")]
+    spectator_ip_check: Option<bool>,
+    #[arg(long, env = "DEFAULT_BOARD_SIZE")]
+    default_board_size: Option<usize>,
+    #[arg(long, env = "DEFAULT_KOMI")]
+    default_komi: Option<f32>,
+    #[arg(long, env = "ROOM_TTL_FINISHED_SECS")]
+    room_ttl_finished_secs: Option<u64>,
+    #[arg(long, env = "ROOM_TTL_IDLE_SECS")]
+    room_ttl_idle_secs: Option<u64>,
+    #[arg(long, env = "ROOM_TTL_NEVER_STARTED_SECS")]
+    room_ttl_never_started_secs: Option<u64>,
+    #[arg(long, env = "ROOM_SWEEP_INTERVAL_SECS")]
+    room_sweep_interval_secs: Option<u64>,
+    #[arg(long, env = "MAX_ARCHIVED_GAMES")]
+    max_archived_games: Option<usize>,
+    #[arg(long, env = "HEARTBEAT_TIMEOUT_SECS")]
+    heartbeat_timeout_secs: Option<u64>,
+    #[arg(long, env = "RECONNECT_GRACE_SECS")]
+    reconnect_grace_secs: Option<u64>,
+    #[arg(long, env = "SHUTDOWN_TIMEOUT_SECS")]
+    shutdown_timeout_secs: Option<u64>,
+    #[arg(long, env = "SHUTDOWN_FLUSH_DIR")]
+    shutdown_flush_dir: Option<PathBuf>,
+    #[arg(long, env = "RATE_LIMIT_IP_REQUESTS")]
+    rate_limit_ip_requests: Option<usize>,
+    #[arg(long, env = "RATE_LIMIT_IP_WINDOW_SECS")]
+    rate_limit_ip_window_secs: Option<u64>,
+    #[arg(long, env = "RATE_LIMIT_SESSION_REQUESTS")]
+    rate_limit_session_requests: Option<usize>,
+    #[arg(long, env = "RATE_LIMIT_SESSION_WINDOW_SECS")]
+    rate_limit_session_window_secs: Option<u64>,
+    #[arg(long, env = "RATE_LIMIT_NEW_ROOMS_REQUESTS")]
+    rate_limit_new_rooms_requests: Option<usize>,
+    #[arg(long, env = "RATE_LIMIT_NEW_ROOMS_WINDOW_SECS")]
+    rate_limit_new_rooms_window_secs: Option<u64>,
+    #[arg(long, env = "MAX_ROOMS")]
+    max_rooms: Option<usize>,
+    #[arg(long, env = "MAX_MATCH_STRING_LENGTH")]
+    max_match_string_length: Option<usize>,
+    /// PEM certificate chain, serves HTTPS together with --tls-key-path
+    #[arg(long, env = "TLS_CERT_PATH")]
+    tls_cert_path: Option<PathBuf>,
+    /// PEM private key
+    #[arg(long, env = "TLS_KEY_PATH")]
+    tls_key_path: Option<PathBuf>,
+    #[arg(long, env = "TLS_RELOAD_INTERVAL_SECS")]
+    tls_reload_interval_secs: Option<u64>,
+    /// MCTS bot playouts per move
+    #[arg(long, env = "BOT_PLAYOUTS")]
+    bot_playouts: Option<usize>,
+    #[arg(long, env = "BOT_DETERMINIZATIONS")]
+    bot_determinizations: Option<usize>,
+}
+
+// Run instead of the server, with the same configuration
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Play bots against each other locally and print the results
+    BotMatch(BotMatchArgs),
+    /// Speak the Go Text Protocol on stdin and stdout
+    Gtp(GtpArgs),
+    /// Referee Phantom Go games between two GTP engines
+    Referee(RefereeArgs),
+    /// Play a game on a server from the terminal
+    Play(PlayArgs),
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, String),
+    Parse(PathBuf, String),
+    // Every problem found, not just the first
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, err) => {
+                write!(f, "can't read config file {}: {}", path.display(), err)
+            }
+            ConfigError::Parse(path, err) => {
+                write!(f, "invalid config file {}: {}", path.display(), err)
+            }
+            ConfigError::Invalid(problems) => {
+                write!(f, "invalid configuration:")?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// Overwrites the setting when the flag or variable was given
+fn set<T>(setting: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *setting = value;
+    }
+}
+
+impl Config {
+    // Exits with clap's usage message on unknown flags or unparsable values
+    pub fn load() -> Result<(Self, Option<Command>), ConfigError> {
+        let _ = dotenv::dotenv();
+        let mut cli = Cli::parse();
+        let command = cli.command.take();
+
+        let mut config = match &cli.config {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+        config.apply(cli);
+        config.validate()?;
+        Ok((config, command))
+    }
+
+    fn from_file(path: &PathBuf) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| ConfigError::Read(path.clone(), err.to_string()))?;
+        Config::from_toml(&contents).map_err(|err| ConfigError::Parse(path.clone(), err))
+    }
+
+    fn from_toml(contents: &str) -> Result<Self, String> {
+        toml::from_str(contents).map_err(|err| err.to_string())
+    }
+
+    fn apply(&mut self, cli: Cli) {
+        set(&mut self.bind_addr, cli.bind_addr);
+        set(&mut self.cors_origins, cli.frontend_origin);
+        set(&mut self.admin_token, cli.admin_token.map(Some));
+        set(&mut self.log_level, cli.log_level);
+        set(&mut self.log_format, cli.log_format);
+        set(&mut self.accounts_path, cli.accounts_path);
+        set(&mut self.ratings_path, cli.ratings_path);
+        set(&mut self.archive_path, cli.archive_path);
+        set(&mut self.spectator_ip_check, cli.spectator_ip_check);
+        set(&mut self.default_room.board_size, cli.default_board_size);
+        set(&mut self.default_room.komi, cli.default_komi);
+
+        let rooms = &mut self.rooms;
+        set(&mut rooms.ttl_finished_secs, cli.room_ttl_finished_secs);
+        set(&mut rooms.ttl_idle_secs, cli.room_ttl_idle_secs);
+        set(
+            &mut rooms.ttl_never_started_secs,
+            cli.room_ttl_never_started_secs,
+        );
+        set(&mut rooms.sweep_interval_secs, cli.room_sweep_interval_secs);
+        set(&mut rooms.max_archived_games, cli.max_archived_games);
+
+        let presence = &mut self.presence;
+        set(
+            &mut presence.heartbeat_timeout_secs,
+            cli.heartbeat_timeout_secs,
+        );
+        set(&mut presence.reconnect_grace_secs, cli.reconnect_grace_secs);
+
+        set(&mut self.shutdown.timeout_secs, cli.shutdown_timeout_secs);
+        set(
+            &mut self.shutdown.flush_dir,
+            cli.shutdown_flush_dir.map(Some),
+        );
+
+        let rate_limit = &mut self.rate_limit;
+        set(&mut rate_limit.ip_requests, cli.rate_limit_ip_requests);
+        set(
+            &mut rate_limit.ip_window_secs,
+            cli.rate_limit_ip_window_secs,
+        );
+        set(
+            &mut rate_limit.session_requests,
+            cli.rate_limit_session_requests,
+        );
+        set(
+            &mut rate_limit.session_window_secs,
+            cli.rate_limit_session_window_secs,
+        );
+        set(
+            &mut rate_limit.new_rooms_requests,
+            cli.rate_limit_new_rooms_requests,
+        );
+        set(
+            &mut rate_limit.new_rooms_window_secs,
+            cli.rate_limit_new_rooms_window_secs,
+        );
+        set(&mut rate_limit.max_rooms, cli.max_rooms);
+        set(
+            &mut rate_limit.max_match_string_length,
+            cli.max_match_string_length,
+        );
+
+        set(&mut self.tls.cert_path, cli.tls_cert_path.map(Some));
+        set(&mut self.tls.key_path, cli.tls_key_path.map(Some));
+        set(
+            &mut self.tls.reload_interval_secs,
+            cli.tls_reload_interval_secs,
+        );
+
+        set(&mut self.bots.playouts, cli.bot_playouts);
+        set(&mut self.bots.determinizations, cli.bot_determinizations);
+
+        // An empty value in .env means unset
+        self.admin_token = self.admin_token.take().filter(|token| !token.is_empty());
+        self.shutdown.flush_dir = self
+            .shutdown
+            .flush_dir
+            .take()
+            .filter(|dir| !dir.as_os_str().is_empty());
+        self.cors_origins.retain(|origin| !origin.trim().is_empty());
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        for origin in &self.cors_origins {
+            let host = origin
+                .strip_prefix("http://")
+                .or_else(|| origin.strip_prefix("https://"));
+            if !host.is_some_and(|host| !host.is_empty() && !host.contains('/')) {
+                problems.push(format!(
+                    "CORS origin `{}` must look like https://example.com, without a path",
+                    origin
+                ));
+            }
+        }
+
+        if self
+            .admin_token
+            .as_ref()
+            .is_some_and(|token| token.len() < MIN_ADMIN_TOKEN_LENGTH)
+        {
+            problems.push(format!(
+                "admin token must be at least {} characters",
+                MIN_ADMIN_TOKEN_LENGTH
+            ));
+        }
+
+        if self.tls.cert_path.is_some() != self.tls.key_path.is_some() {
+            problems.push("TLS needs both a certificate and a key path".to_string());
+        }
+
+        if let Err(err) = EnvFilter::try_new(&self.log_level) {
+            problems.push(format!("log level `{}`: {}", self.log_level, err));
+        }
+
+        if let Err(err) = self.default_room.validate() {
+            problems.push(format!("default room settings: {}", err));
+        }
+
+        let positive = [
+            (
+                "room sweep interval",
+                self.rooms.sweep_interval_secs as usize,
+            ),
+            (
+                "heartbeat timeout",
+                self.presence.heartbeat_timeout_secs as usize,
+            ),
+            ("IP rate limit requests", self.rate_limit.ip_requests),
+            (
+                "IP rate limit window",
+                self.rate_limit.ip_window_secs as usize,
+            ),
+            (
+                "session rate limit requests",
+                self.rate_limit.session_requests,
+            ),
+            (
+                "session rate limit window",
+                self.rate_limit.session_window_secs as usize,
+            ),
+            ("new room rate limit", self.rate_limit.new_rooms_requests),
+            (
+                "new room rate limit window",
+                self.rate_limit.new_rooms_window_secs as usize,
+            ),
+            ("max rooms", self.rate_limit.max_rooms),
+            (
+                "TLS reload interval",
+                self.tls.reload_interval_secs as usize,
+            ),
+            (
+                "max match string length",
+                self.rate_limit.max_match_string_length,
+            ),
+            ("bot playouts", self.bots.playouts),
+            ("bot determinizations", self.bots.determinizations),
+        ];
+        for (name, value) in positive {
+            if value == 0 {
+                problems.push(format!("{} must be greater than 0", name));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+// Called once from main, before anything reads the config
+pub fn init(config: Config) {
+    if CONFIG.set(config).is_err() {
+        panic!("config initialized twice");
+    }
+}
+
+// Falls back to the defaults when nothing was loaded, like in unit tests
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(unloaded)
+}
+
+#[cfg(not(test))]
+fn unloaded() -> Config {
+    Config::default()
+}
+
+// Handler tests share one process, they keep their files out of the source tree and open
+// more rooms than one IP is allowed to
+#[cfg(test)]
+fn unloaded() -> Config {
+    let run = uuid::Uuid::new_v4();
+    let temp_dir = std::env::temp_dir();
+    let mut config = Config {
+        accounts_path: temp_dir.join(format!("accounts-{}.json", run)),
+        ratings_path: temp_dir.join(format!("ratings-{}.json", run)),
+        archive_path: temp_dir.join(format!("archive-{}.json", run)),
+        ..Config::default()
+    };
+    config.rate_limit.new_rooms_requests = usize::MAX;
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Config, ConfigError, LogFormat};
+
+    #[test]
+    fn file_settings_are_merged_over_defaults() {
+        let config = Config::from_toml(
+            r#"
+            bind_addr = "127.0.0.1:9000"
+            cors_origins = ["https://go.example.com"]
+            log_format = "json"
+
+            [default_room]
+            board_size = 9
+
+            [rooms]
+            ttl_idle_secs = 600
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.bind_addr.port(), 9000);
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(config.default_room.board_size, 9);
+        assert_eq!(config.default_room.komi, 1.5);
+        assert_eq!(config.rooms.ttl_idle_secs, 600);
+        assert_eq!(config.rooms.ttl_finished_secs, 60 * 60);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(Config::from_toml("bind_adress = \"127.0.0.1:9000\"").is_err());
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let mut config = Config {
+            cors_origins: vec!["example.com".to_string()],
+            admin_token: Some("short".to_string()),
+            ..Config::default()
+        };
+        config.default_room.board_size = 30;
+        config.rate_limit.max_rooms = 0;
+
+        let Err(ConfigError::Invalid(problems)) = config.validate() else {
+            panic!("config should be invalid");
+        };
+        assert_eq!(problems.len(), 4);
+    }
+}
@@ -0,0 +1,774 @@
+// Go Text Protocol (version 2) over stdin and stdout, so GTP tools and GUIs can drive the
+// rules engine and the bots:
+//
+//     go_board gtp --bot mcts
+//
+// The standard commands work on the full board, like a referee. genmove still only shows
+// the bot its own seat and retries when its move is illegal there, so it plays Phantom Go
+// against whatever drives the other color.
+//
+// Phantom extensions:
+//     phantom-announcements - what the referee announced after the last move, three lines:
+//                             `captured <vertex>...`, `atari black <n> white <n>` and
+//                             `illegal <vertex>...` for the attempts genmove had refused
+//     phantom-seat <color> - from now on the engine only plays <color> and only learns what
+//                            a player in that seat would. The board it was told about is
+//                            forgotten. The other commands then mean:
+//                                genmove <color> - the move is taken as played until refused
+//                                play <opponent> pass - the opponent passed
+//     phantom-illegal - the last generated move was refused, genmove again
+//     phantom-opponent_moved - the opponent placed a stone somewhere
+//     phantom-captured <vertex>... - stones the last move took off the board, of either color
+//     phantom-atari <color> <n> - <n> stones of <color> were put in atari by the last move
+
+use crate::board::{Board, Color, GameResult, Loc, Move, Player};
+use crate::bot::{Bot, BotKind, BotMove, MctsConfig, Point, SeatView};
+use crate::config::Config;
+use crate::room_settings::{MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use clap::Args;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+// GTP skips I so it isn't mistaken for J
+const COLUMNS: &str = "ABCDEFGHJKLMNOPQRST";
+
+const COMMANDS: [&str; 21] = [
+    "protocol_version",
+    "name",
+    "version",
+    "known_command",
+    "list_commands",
+    "quit",
+    "boardsize",
+    "clear_board",
+    "komi",
+    "play",
+    "genmove",
+    "undo",
+    "showboard",
+    "final_score",
+    "loadsgf",
+    "phantom-announcements",
+    "phantom-seat",
+    "phantom-illegal",
+    "phantom-opponent_moved",
+    "phantom-captured",
+    "phantom-atari",
+];
+
+#[derive(Debug, Args)]
+pub struct GtpArgs {
+    /// Plays genmove
+    #[arg(long, value_enum, default_value = "mcts")]
+    bot: BotKind,
+    /// MCTS playouts per move, the server's bot setting when left out
+    #[arg(long)]
+    playouts: Option<usize>,
+    /// Makes the bot's moves repeatable
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+pub fn run(args: GtpArgs, config: &Config) -> Result<(), String> {
+    let mut mcts = MctsConfig::from_config(config);
+    if let Some(playouts) = args.playouts.filter(|playouts| *playouts > 0) {
+        mcts.playouts = playouts;
+    }
+    let rng = match args.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let room = &config.default_room;
+    let mut engine = Engine::new(room.board_size, room.komi, args.bot, mcts, rng);
+
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|err| format!("can't read stdin: {}", err))?;
+        let Some(response) = engine.handle_line(&line) else {
+            continue;
+        };
+        write!(stdout, "{}", response)
+            .and_then(|_| stdout.flush())
+            .map_err(|err| format!("can't write stdout: {}", err))?;
+        if engine.quit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// What the referee announced after the last move on the full board
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Announcements {
+    captured: Vec<Point>,
+    atari: (usize, usize),
+    illegal: Vec<Point>,
+}
+
+// The engine's side of the game when it only plays one seat
+struct PhantomSeat {
+    view: SeatView,
+    opponent_moves: usize,
+    // The view before the last generated move, put back when the referee refuses it
+    before_move: Option<SeatView>,
+}
+
+impl PhantomSeat {
+    fn new(player: Player, size: usize, komi: f32) -> Self {
+        PhantomSeat {
+            view: SeatView::empty(player, size, komi),
+            opponent_moves: 0,
+            before_move: None,
+        }
+    }
+
+    fn own_move(&mut self, choice: BotMove) {
+        self.before_move = Some(self.view.clone());
+        self.view.own_last_move = match choice {
+            BotMove::Play(point) => {
+                self.view.own_stones.insert(point);
+                Some(point)
+            }
+            BotMove::Pass => None,
+        };
+        self.view.opponent_passed = false;
+        self.clear_atari();
+    }
+
+    fn refuse(&mut self) -> Result<(), String> {
+        let tried = self.view.own_last_move;
+        let (Some(before), Some(point)) = (self.before_move.take(), tried) else {
+            return Err("no move to refuse".to_string());
+        };
+        self.view = before;
+        self.view.refused.insert(point);
+        Ok(())
+    }
+
+    fn opponent_move(&mut self, passed: bool) {
+        self.before_move = None;
+        self.view.refused.clear();
+        self.view.opponent_passed = passed;
+        if !passed {
+            self.opponent_moves += 1;
+        }
+        self.clear_atari();
+        self.count_opponent_stones();
+    }
+
+    fn captured(&mut self, points: &[Point]) {
+        self.before_move = None;
+        for point in points {
+            if self.view.own_stones.remove(point) {
+                self.view.opponent_captures += 1;
+            } else {
+                self.view.own_captures += 1;
+            }
+        }
+        self.count_opponent_stones();
+    }
+
+    fn atari(&mut self, player: Player, stones: usize) {
+        self.before_move = None;
+        if player == self.view.player {
+            self.view.own_in_atari = stones;
+        } else {
+            self.view.opponent_in_atari = stones;
+        }
+    }
+
+    fn clear_atari(&mut self) {
+        self.view.own_in_atari = 0;
+        self.view.opponent_in_atari = 0;
+    }
+
+    fn count_opponent_stones(&mut self) {
+        let captured = self.view.own_captures.max(0) as usize;
+        self.view.opponent_stones = self.opponent_moves.saturating_sub(captured);
+    }
+}
+
+pub struct Engine {
+    size: usize,
+    komi: f32,
+    board: Board,
+    announcements: Announcements,
+    seat: Option<PhantomSeat>,
+    kind: BotKind,
+    mcts: MctsConfig,
+    black: Bot,
+    white: Bot,
+    rng: StdRng,
+    quit: bool,
+}
+
+impl Engine {
+    pub fn new(size: usize, komi: f32, kind: BotKind, mcts: MctsConfig, rng: StdRng) -> Self {
+        Engine {
+            size,
+            komi,
+            board: Board::new(size + 2, size + 2, komi),
+            announcements: Announcements::default(),
+            seat: None,
+            kind,
+            mcts,
+            black: Bot::new(kind, mcts),
+            white: Bot::new(kind, mcts),
+            rng,
+            quit: false,
+        }
+    }
+
+    // The full response to one line of input, None for empty lines and comments
+    pub fn handle_line(&mut self, line: &str) -> Option<String> {
+        let line: String = line
+            .chars()
+            .filter(|c| !c.is_control() || *c == '\t')
+            .map(|c| if c == '\t' { ' ' } else { c })
+            .collect();
+        let line = line.split('#').next().unwrap_or_default();
+        let mut words = line.split_whitespace().peekable();
+        let id = words
+            .next_if(|word| word.chars().all(|c| c.is_ascii_digit()))
+            .unwrap_or_default()
+            .to_string();
+        let command = words.next()?;
+        let args: Vec<&str> = words.collect();
+
+        Some(match self.handle(command, &args) {
+            Ok(response) => format!("={} {}\n\n", id, response),
+            Err(err) => format!("?{} {}\n\n", id, err),
+        })
+    }
+
+    fn handle(&mut self, command: &str, args: &[&str]) -> Result<String, String> {
+        let arg = |index: usize| args.get(index).copied().ok_or("syntax error");
+
+        match command {
+            "protocol_version" => Ok("2".to_string()),
+            "name" => Ok("go_board".to_string()),
+            "version" => Ok(env!("CARGO_PKG_VERSION").to_string()),
+            "known_command" => Ok(COMMANDS.contains(&arg(0)?).to_string()),
+            "list_commands" => Ok(COMMANDS.join("\n")),
+            "quit" => {
+                self.quit = true;
+                Ok(String::new())
+            }
+            "boardsize" => {
+                let size: usize = arg(0)?.parse().map_err(|_| "syntax error")?;
+                if !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&size) {
+                    return Err("unacceptable size".to_string());
+                }
+                self.size = size;
+                self.clear();
+                Ok(String::new())
+            }
+            "clear_board" => {
+                self.clear();
+                Ok(String::new())
+            }
+            "komi" => {
+                self.komi = arg(0)?.parse().map_err(|_| "syntax error")?;
+                self.board.set_komi(self.komi);
+                if let Some(seat) = &mut self.seat {
+                    seat.view.komi = self.komi;
+                }
+                Ok(String::new())
+            }
+            "play" => {
+                let player = parse_color(arg(0)?)?;
+                let point = parse_vertex(arg(1)?, self.size)?;
+                self.play(player, point)?;
+                Ok(String::new())
+            }
+            "genmove" => {
+                let player = parse_color(arg(0)?)?;
+                let point = self.genmove(player)?;
+                Ok(vertex(point, self.size))
+            }
+            "undo" => {
+                if self.seat.is_some() || self.board.game_history.is_empty() {
+                    return Err("cannot undo".to_string());
+                }
+                self.board.undo();
+                self.announcements = Announcements::default();
+                Ok(String::new())
+            }
+            "showboard" => Ok(format!("\n{}", self.render())),
+            "final_score" => {
+                if self.seat.is_some() {
+                    return Err("cannot score".to_string());
+                }
+                Ok(match self.board.count_score() {
+                    GameResult::Points(Player::Black, points) => format!("B+{}", points),
+                    GameResult::Points(Player::White, points) => format!("W+{}", points),
+                    _ => "0".to_string(),
+                })
+            }
+            "loadsgf" => {
+                let text = fs::read_to_string(arg(0)?).map_err(|_| "cannot load file")?;
+                let until = match args.get(1) {
+                    Some(number) => Some(number.parse().map_err(|_| "syntax error")?),
+                    None => None,
+                };
+                self.load_sgf(&text, until)?;
+                Ok(String::new())
+            }
+            "phantom-announcements" => {
+                let vertices = |points: &[Point]| {
+                    points
+                        .iter()
+                        .map(|point| format!(" {}", vertex(Some(*point), self.size)))
+                        .collect::<String>()
+                };
+                let announcements = &self.announcements;
+                Ok(format!(
+                    "captured{}\natari black {} white {}\nillegal{}",
+                    vertices(&announcements.captured),
+                    announcements.atari.0,
+                    announcements.atari.1,
+                    vertices(&announcements.illegal)
+                ))
+            }
+            "phantom-seat" => {
+                let player = parse_color(arg(0)?)?;
+                self.clear();
+                self.seat = Some(PhantomSeat::new(player, self.size, self.komi));
+                Ok(String::new())
+            }
+            "phantom-illegal" => {
+                self.seat_mut()?.refuse()?;
+                Ok(String::new())
+            }
+            "phantom-opponent_moved" => {
+                self.seat_mut()?.opponent_move(false);
+                Ok(String::new())
+            }
+            "phantom-captured" => {
+                let size = self.size;
+                let points = args
+                    .iter()
+                    .map(|arg| parse_vertex(arg, size)?.ok_or("syntax error".to_string()))
+                    .collect::<Result<Vec<Point>, String>>()?;
+                self.seat_mut()?.captured(&points);
+                Ok(String::new())
+            }
+            "phantom-atari" => {
+                let player = parse_color(arg(0)?)?;
+                let stones = arg(1)?.parse().map_err(|_| "syntax error")?;
+                self.seat_mut()?.atari(player, stones);
+                Ok(String::new())
+            }
+            _ => Err("unknown command".to_string()),
+        }
+    }
+
+    fn seat_mut(&mut self) -> Result<&mut PhantomSeat, String> {
+        self.seat
+            .as_mut()
+            .ok_or_else(|| "not seated, use phantom-seat first".to_string())
+    }
+
+    // Also leaves the seat, phantom-seat takes it again
+    fn clear(&mut self) {
+        self.board = Board::new(self.size + 2, self.size + 2, self.komi);
+        self.announcements = Announcements::default();
+        self.seat = None;
+        self.black = Bot::new(self.kind, self.mcts);
+        self.white = Bot::new(self.kind, self.mcts);
+    }
+
+    fn play(&mut self, player: Player, point: Option<Point>) -> Result<(), String> {
+        if let Some(seat) = &mut self.seat {
+            return match (player == seat.view.player, point) {
+                (true, point) => {
+                    seat.own_move(point.map_or(BotMove::Pass, BotMove::Play));
+                    Ok(())
+                }
+                (false, None) => {
+                    seat.opponent_move(true);
+                    Ok(())
+                }
+                (false, Some(_)) => Err("the opponent's stones are hidden".to_string()),
+            };
+        }
+
+        let opponent_color = match player {
+            Player::Black => Color::White,
+            Player::White => Color::Black,
+        };
+        let before = self.board.fields.clone();
+        let moves = self.board.game_history.len();
+        let to_move = self.board.get_current_player();
+        self.board.set_current_player(player);
+        self.board.play(&Move {
+            player,
+            loc: point.map_or(Loc::pass(), Point::loc),
+        });
+        if self.board.game_history.len() == moves {
+            self.board.set_current_player(to_move);
+            return Err("illegal move".to_string());
+        }
+
+        let captured = (0..self.size)
+            .flat_map(|row| (0..self.size).map(move |col| Point { row, col }))
+            .filter(|point| {
+                let loc = point.loc();
+                before[loc.row][loc.col] == opponent_color
+                    && self.board.fields[loc.row][loc.col] == Color::Empty
+            })
+            .collect();
+        self.announcements = Announcements {
+            captured,
+            atari: (
+                self.board.stones_in_atari.black,
+                self.board.stones_in_atari.white,
+            ),
+            illegal: Vec::new(),
+        };
+        Ok(())
+    }
+
+    // On the full board the bot is refereed right here, retrying until its move is legal
+    fn genmove(&mut self, player: Player) -> Result<Option<Point>, String> {
+        if let Some(seat) = &mut self.seat {
+            if player != seat.view.player {
+                return Err("not seated as that color".to_string());
+            }
+            let bot = match player {
+                Player::Black => &mut self.black,
+                Player::White => &mut self.white,
+            };
+            bot.observe(&seat.view);
+            let choice = bot.choose_move(&seat.view, &mut self.rng);
+            seat.own_move(choice);
+            return Ok(point_of(choice));
+        }
+
+        let mut refused = HashSet::new();
+        let mut illegal = Vec::new();
+        loop {
+            let view = SeatView::new(&self.board, player, refused.clone());
+            let bot = match player {
+                Player::Black => &mut self.black,
+                Player::White => &mut self.white,
+            };
+            bot.observe(&view);
+            let choice = bot.choose_move(&view, &mut self.rng);
+
+            match self.play(player, point_of(choice)) {
+                Ok(()) => {
+                    self.announcements.illegal = illegal;
+                    return Ok(point_of(choice));
+                }
+                Err(_) => {
+                    // The bot never picks a refused point, and a pass is always legal
+                    let point = point_of(choice).ok_or("illegal pass")?;
+                    refused.insert(point);
+                    illegal.push(point);
+                }
+            }
+        }
+    }
+
+    // Replays the main line, up to but not including move `until`
+    fn load_sgf(&mut self, text: &str, until: Option<usize>) -> Result<(), String> {
+        let game = parse_sgf(text)?;
+        let size = game.size.unwrap_or(self.size);
+        if !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&size) {
+            return Err("unacceptable size".to_string());
+        }
+        self.size = size;
+        if let Some(komi) = game.komi {
+            self.komi = komi;
+        }
+        self.clear();
+
+        let moves = until.map_or(game.moves.len(), |until| until.saturating_sub(1));
+        for (player, point) in game.moves.into_iter().take(moves) {
+            if point.is_some_and(|point| point.row >= size || point.col >= size) {
+                return Err("cannot load file".to_string());
+            }
+            self.play(player, point)
+                .map_err(|_| "illegal move in file".to_string())?;
+        }
+        Ok(())
+    }
+
+    // Rows are numbered from the bottom, like on GTP vertices
+    fn render(&self) -> String {
+        let color = |point: Point| match &self.seat {
+            Some(seat) if seat.view.own_stones.contains(&point) => color_of(seat.view.player),
+            Some(_) => Color::Empty,
+            None => {
+                let loc = point.loc();
+                self.board.fields[loc.row][loc.col]
+            }
+        };
+        let letters: String = COLUMNS
+            .chars()
+            .take(self.size)
+            .map(|letter| format!(" {}", letter))
+            .collect();
+
+        let mut text = format!("  {}\n", letters);
+        for row in 0..self.size {
+            let number = self.size - row;
+            text.push_str(&format!("{:>2}", number));
+            for col in 0..self.size {
+                let symbol = match color(Point { row, col }) {
+                    Color::Black => 'X',
+                    Color::White => 'O',
+                    _ => '.',
+                };
+                text.push_str(&format!(" {}", symbol));
+            }
+            text.push_str(&format!(" {}\n", number));
+        }
+        text.push_str(&format!("  {}", letters));
+        text
+    }
+}
+
+fn color_of(player: Player) -> Color {
+    match player {
+        Player::Black => Color::Black,
+        Player::White => Color::White,
+    }
+}
+
+fn point_of(choice: BotMove) -> Option<Point> {
+    match choice {
+        BotMove::Play(point) => Some(point),
+        BotMove::Pass => None,
+    }
+}
+
+fn parse_color(text: &str) -> Result<Player, String> {
+    match text.to_lowercase().as_str() {
+        "b" | "black" => Ok(Player::Black),
+        "w" | "white" => Ok(Player::White),
+        _ => Err("syntax error".to_string()),
+    }
+}
+
+// None is a pass
+pub fn parse_vertex(text: &str, size: usize) -> Result<Option<Point>, String> {
+    let text = text.to_uppercase();
+    if text == "PASS" {
+        return Ok(None);
+    }
+
+    let mut chars = text.chars();
+    let letter = chars.next().ok_or("syntax error")?;
+    let col = COLUMNS.find(letter).ok_or("syntax error")?;
+    let number: usize = chars.as_str().parse().map_err(|_| "syntax error")?;
+    if col >= size || !(1..=size).contains(&number) {
+        return Err("invalid coordinate".to_string());
+    }
+    Ok(Some(Point {
+        row: size - number,
+        col,
+    }))
+}
+
+pub fn vertex(point: Option<Point>, size: usize) -> String {
+    match point {
+        Some(point) => format!(
+            "{}{}",
+            COLUMNS.chars().nth(point.col).unwrap_or('?'),
+            size - point.row
+        ),
+        None => "pass".to_string(),
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct SgfGame {
+    size: Option<usize>,
+    komi: Option<f32>,
+    moves: Vec<(Player, Option<Point>)>,
+}
+
+// Only what a game record needs: SZ, KM and the moves of the main line. The first
+// variation is the main line, so reading stops where it ends
+fn parse_sgf(text: &str) -> Result<SgfGame, String> {
+    let error = || "cannot load file".to_string();
+    let mut game = SgfGame::default();
+    let mut chars = text.chars().peekable();
+    let mut ident = String::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ')' => break,
+            c if c.is_ascii_uppercase() => ident.push(c),
+            '[' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next().ok_or_else(error)? {
+                        ']' => break,
+                        '\\' => value.push(chars.next().ok_or_else(error)?),
+                        c => value.push(c),
+                    }
+                }
+
+                match ident.as_str() {
+                    "SZ" => game.size = Some(value.trim().parse().map_err(|_| error())?),
+                    "KM" => game.komi = Some(value.trim().parse().map_err(|_| error())?),
+                    "B" | "W" => {
+                        let player = if ident == "B" {
+                            Player::Black
+                        } else {
+                            Player::White
+                        };
+                        game.moves.push((player, sgf_point(&value)));
+                    }
+                    _ => {}
+                }
+                // Further values of the same property follow without the identifier
+                if chars.peek() != Some(&'[') {
+                    ident.clear();
+                }
+            }
+            _ => ident.clear(),
+        }
+    }
+    Ok(game)
+}
+
+// Empty and `tt` are passes
+fn sgf_point(value: &str) -> Option<Point> {
+    let mut chars = value.chars();
+    let col = chars.next()?;
+    let row = chars.next()?;
+    if value == "tt" || !col.is_ascii_lowercase() || !row.is_ascii_lowercase() {
+        return None;
+    }
+    Some(Point {
+        row: (row as u8 - b'a') as usize,
+        col: (col as u8 - b'a') as usize,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::{BotKind, MctsConfig, Point};
+    use crate::gtp::{parse_sgf, parse_vertex, vertex, Engine};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn engine(kind: BotKind) -> Engine {
+        let mcts = MctsConfig {
+            playouts: 16,
+            determinizations: 2,
+            exploration: 1.0,
+        };
+        Engine::new(9, 6.5, kind, mcts, StdRng::seed_from_u64(3))
+    }
+
+    fn send(engine: &mut Engine, line: &str) -> String {
+        engine.handle_line(line).expect("a response")
+    }
+
+    #[test]
+    fn vertices_count_rows_from_the_bottom_and_skip_i() {
+        assert_eq!(parse_vertex("A9", 9), Ok(Some(Point { row: 0, col: 0 })));
+        assert_eq!(parse_vertex("j1", 9), Ok(Some(Point { row: 8, col: 8 })));
+        assert_eq!(parse_vertex("PASS", 9), Ok(None));
+        assert!(parse_vertex("I5", 9).is_err());
+        assert!(parse_vertex("K1", 9).is_err());
+        assert_eq!(vertex(Some(Point { row: 4, col: 7 }), 9), "H5");
+    }
+
+    #[test]
+    fn responses_carry_the_id_and_skip_comments() {
+        let mut engine = engine(BotKind::Random);
+        assert_eq!(send(&mut engine, "7 protocol_version"), "=7 2\n\n");
+        assert_eq!(send(&mut engine, "boardsize 19"), "? unacceptable size\n\n");
+        assert_eq!(send(&mut engine, "frobnicate"), "? unknown command\n\n");
+        assert_eq!(engine.handle_line("# just a comment"), None);
+        assert_eq!(
+            send(&mut engine, "known_command phantom-seat # trailing"),
+            "= true\n\n"
+        );
+    }
+
+    #[test]
+    fn play_announces_captures_and_refuses_occupied_points() {
+        let mut engine = engine(BotKind::Random);
+        for line in ["boardsize 5", "komi 0.5", "play b A4", "play w A5"] {
+            assert_eq!(send(&mut engine, line), "= \n\n");
+        }
+        assert_eq!(
+            send(&mut engine, "phantom-announcements"),
+            "= captured\natari black 0 white 1\nillegal\n\n"
+        );
+        assert_eq!(send(&mut engine, "play b A5"), "? illegal move\n\n");
+
+        send(&mut engine, "play b B5");
+        assert_eq!(
+            send(&mut engine, "phantom-announcements"),
+            "= captured A5\natari black 0 white 0\nillegal\n\n"
+        );
+        assert_eq!(send(&mut engine, "final_score"), "= B+23.5\n\n");
+        send(&mut engine, "undo");
+        assert_eq!(send(&mut engine, "final_score"), "= W+0.5\n\n");
+    }
+
+    #[test]
+    fn genmove_retries_and_passes_when_nothing_is_legal() {
+        let mut engine = engine(BotKind::Random);
+        send(&mut engine, "boardsize 5");
+        // White takes everything but two points, black would have no liberties on either
+        for row in 1..=5 {
+            for col in ["A", "B", "C", "D", "E"] {
+                if (col, row) != ("E", 1) && (col, row) != ("A", 5) {
+                    send(&mut engine, &format!("play w {}{}", col, row));
+                }
+            }
+        }
+
+        assert_eq!(send(&mut engine, "genmove b"), "= pass\n\n");
+        let announcements = send(&mut engine, "phantom-announcements");
+        let illegal = announcements.lines().nth(2).expect("illegal line");
+        assert_eq!(illegal.split_whitespace().count(), 1 + 25);
+    }
+
+    #[test]
+    fn a_seated_engine_learns_only_its_own_moves() {
+        let mut engine = engine(BotKind::Random);
+        send(&mut engine, "boardsize 5");
+        send(&mut engine, "phantom-seat w");
+        send(&mut engine, "phantom-opponent_moved");
+        assert_eq!(
+            send(&mut engine, "play b C3"),
+            "? the opponent's stones are hidden\n\n"
+        );
+
+        let first = send(&mut engine, "genmove w");
+        let first = first.trim_start_matches("= ").trim();
+        send(&mut engine, "phantom-illegal");
+        let second = send(&mut engine, "genmove w");
+        assert_ne!(second.trim_start_matches("= ").trim(), first);
+
+        let seat = engine.seat.as_ref().expect("seated");
+        assert_eq!(seat.view.refused.len(), 1);
+        assert_eq!(seat.view.opponent_stones, 1);
+        assert_eq!(seat.view.own_stones.len(), 1);
+        assert_eq!(send(&mut engine, "undo"), "? cannot undo\n\n");
+    }
+
+    #[test]
+    fn sgf_main_line_is_read_with_size_and_komi() {
+        let game =
+            parse_sgf("(;GM[1]SZ[7]KM[6.5]C[a \\] b];B[aa];W[]((;B[bc])(;B[cc]))").expect("parses");
+        assert_eq!(game.size, Some(7));
+        assert_eq!(game.komi, Some(6.5));
+        assert_eq!(game.moves.len(), 3);
+        assert_eq!(game.moves[1].1, None);
+        assert_eq!(game.moves[2].1, Some(Point { row: 2, col: 1 }));
+    }
+}
@@ -0,0 +1,223 @@
+// Lingo:
+//     challenge - an open invitation to play with given settings, waiting for an opponent
+//     owner token - secret handed to whoever posted the challenge; it becomes their
+//                   session token once the challenge is accepted
+
+use crate::accounts::PublicAccount;
+use crate::room_settings::RoomSettings;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::SystemTime;
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct Challenge {
+    pub id: String,
+    pub settings: RoomSettings,
+    // Posted through the quick-match queue rather than by hand
+    pub quick_match: bool,
+    // None for guests
+    pub owner: Option<PublicAccount>,
+    #[serde(skip)]
+    pub owner_token: String,
+    // Room created when the challenge was accepted
+    #[serde(skip)]
+    pub match_string: Option<String>,
+    #[serde(skip)]
+    created_at: SystemTime,
+}
+
+impl Challenge {
+    pub fn is_open(&self) -> bool {
+        self.match_string.is_none()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LobbyError {
+    NotFound,
+    AlreadyAccepted,
+    NotOwner,
+    OwnChallenge,
+}
+
+#[derive(Default)]
+pub struct Lobby {
+    challenges: HashMap<String, Challenge>,
+}
+
+impl Lobby {
+    pub fn new() -> Self {
+        Lobby::default()
+    }
+
+    pub fn post(
+        &mut self,
+        settings: RoomSettings,
+        quick_match: bool,
+        owner: Option<PublicAccount>,
+    ) -> Challenge {
+        let challenge = Challenge {
+            id: uuid::Uuid::new_v4().to_string(),
+            settings,
+            quick_match,
+            owner,
+            owner_token: uuid::Uuid::new_v4().to_string(),
+            match_string: None,
+            created_at: SystemTime::now(),
+        };
+
+        self.challenges
+            .insert(challenge.id.clone(), challenge.clone());
+        challenge
+    }
+
+    // Oldest first, so whoever has waited longest is seen first
+    pub fn open_challenges(&self) -> Vec<Challenge> {
+        let mut open: Vec<Challenge> = self
+            .challenges
+            .values()
+            .filter(|challenge| challenge.is_open())
+            .cloned()
+            .collect();
+        open.sort_by_key(|challenge| challenge.created_at);
+        open
+    }
+
+    // Oldest open challenge with the same settings, used by quick match.
+    // Never pairs an account with itself
+    pub fn find_compatible(
+        &self,
+        settings: &RoomSettings,
+        account_id: Option<&str>,
+    ) -> Option<String> {
+        self.open_challenges()
+            .into_iter()
+            .filter(|challenge| {
+                account_id.is_none()
+                    || challenge.owner.as_ref().map(|owner| owner.id.as_str()) != account_id
+            })
+            .find(|challenge| &challenge.settings == settings)
+            .map(|challenge| challenge.id)
+    }
+
+    // Marks the challenge as taken by the room with given match_string
+    pub fn accept(
+        &mut self,
+        id: &str,
+        match_string: &str,
+        account_id: Option<&str>,
+    ) -> Result<Challenge, LobbyError> {
+        let challenge = self.challenges.get_mut(id).ok_or(LobbyError::NotFound)?;
+
+        if !challenge.is_open() {
+            return Err(LobbyError::AlreadyAccepted);
+        }
+
+        if account_id.is_some()
+            && challenge.owner.as_ref().map(|owner| owner.id.as_str()) == account_id
+        {
+            return Err(LobbyError::OwnChallenge);
+        }
+
+        challenge.match_string = Some(match_string.to_string());
+        Ok(challenge.clone())
+    }
+
+    // Only the poster can look up the room their challenge ended up in
+    pub fn status(&self, id: &str, owner_token: &str) -> Result<&Challenge, LobbyError> {
+        let challenge = self.challenges.get(id).ok_or(LobbyError::NotFound)?;
+
+        if challenge.owner_token != owner_token {
+            return Err(LobbyError::NotOwner);
+        }
+
+        Ok(challenge)
+    }
+
+    pub fn cancel(&mut self, id: &str, owner_token: &str) -> Result<(), LobbyError> {
+        let challenge = self.status(id, owner_token)?;
+
+        if !challenge.is_open() {
+            return Err(LobbyError::AlreadyAccepted);
+        }
+
+        self.challenges.remove(id);
+        Ok(())
+    }
+
+    pub fn remove_created_before(&mut self, cutoff: SystemTime) {
+        self.challenges
+            .retain(|_, challenge| challenge.created_at >= cutoff);
+    }
+
+    pub fn clear(&mut self) {
+        self.challenges.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::accounts::PublicAccount;
+    use crate::lobby::{Lobby, LobbyError};
+    use crate::room_settings::RoomSettings;
+
+    #[test]
+    fn accepted_challenges_are_no_longer_open() {
+        let mut lobby = Lobby::new();
+        let challenge = lobby.post(RoomSettings::default(), false, None);
+
+        assert_eq!(lobby.open_challenges().len(), 1);
+
+        lobby.accept(&challenge.id, "room", None).unwrap();
+
+        assert!(lobby.open_challenges().is_empty());
+        assert_eq!(
+            lobby.accept(&challenge.id, "other room", None).unwrap_err(),
+            LobbyError::AlreadyAccepted
+        );
+        assert_eq!(
+            lobby
+                .status(&challenge.id, &challenge.owner_token)
+                .unwrap()
+                .match_string,
+            Some("room".to_string())
+        );
+    }
+
+    #[test]
+    fn quick_match_only_pairs_identical_settings() {
+        let mut lobby = Lobby::new();
+        let small = RoomSettings {
+            board_size: 9,
+            ..RoomSettings::default()
+        };
+
+        let owner = PublicAccount {
+            id: "alice".to_string(),
+            display_name: "Alice".to_string(),
+            bot: false,
+        };
+        let waiting = lobby.post(small.clone(), true, Some(owner));
+
+        assert_eq!(lobby.find_compatible(&RoomSettings::default(), None), None);
+        assert_eq!(lobby.find_compatible(&small, Some("alice")), None);
+        assert_eq!(lobby.find_compatible(&small, None), Some(waiting.id));
+    }
+
+    #[test]
+    fn only_the_owner_can_cancel_a_challenge() {
+        let mut lobby = Lobby::new();
+        let challenge = lobby.post(RoomSettings::default(), false, None);
+
+        assert_eq!(
+            lobby.cancel(&challenge.id, "someone else"),
+            Err(LobbyError::NotOwner)
+        );
+        assert_eq!(lobby.cancel(&challenge.id, &challenge.owner_token), Ok(()));
+        assert_eq!(
+            lobby.cancel(&challenge.id, &challenge.owner_token),
+            Err(LobbyError::NotFound)
+        );
+    }
+}
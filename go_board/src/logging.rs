@@ -0,0 +1,89 @@
+// Structured logging through `tracing`.
+//
+// Levels come from the log level setting (RUST_LOG, e.g. `info`, `go_board=debug`).
+// The json log format writes one JSON object per line for log collectors, text writes
+// human readable lines.
+//
+// Every request runs in a `request` span carrying its id, method and path. Handlers
+// fill in `room` and `seat` once they know them, so every event logged while serving
+// the request carries them too.
+
+use crate::config::{Config, LogFormat};
+use poem::{http::HeaderValue, Endpoint, IntoResponse, Request, Response, Result};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+use tracing_subscriber::EnvFilter;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// The level was validated with the rest of the config
+pub fn init(config: &Config) {
+    let filter = EnvFilter::new(&config.log_level);
+    let json = config.log_format == LogFormat::Json;
+
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    if json {
+        subscriber
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .init();
+    } else {
+        subscriber.init();
+    }
+}
+
+// Wraps every route, reusing the caller's X-Request-Id when it sends one
+pub async fn trace_request<E: Endpoint>(ep: Arc<E>, req: Request) -> Result<Response> {
+    let request_id = req
+        .header(REQUEST_ID_HEADER)
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.uri().path(),
+        room = Empty,
+        seat = Empty,
+    );
+
+    async move {
+        let started = Instant::now();
+        let result = ep.call(req).await.map(IntoResponse::into_response);
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        let mut response = match result {
+            Ok(response) => response,
+            Err(err) => err.into_response(),
+        };
+
+        // Clients poll every second, so successful requests only show up at debug
+        let status = response.status();
+        if status.is_server_error() {
+            tracing::error!(status = status.as_u16(), latency_ms, "request failed");
+        } else if status.is_client_error() {
+            tracing::info!(status = status.as_u16(), latency_ms, "request refused");
+        } else {
+            tracing::debug!(status = status.as_u16(), latency_ms, "request finished");
+        }
+
+        if let Ok(value) = HeaderValue::from_str(&request_id) {
+            response.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+        Ok(response)
+    }
+    .instrument(span)
+    .await
+}
+
+pub fn record_room(match_string: &str) {
+    Span::current().record("room", match_string);
+}
+
+pub fn record_seat(seat: impl std::fmt::Debug) {
+    Span::current().record("seat", tracing::field::debug(seat));
+}
@@ -1,12 +1,67 @@
+mod accounts;
+mod api_docs;
+mod api_error;
+mod archive;
 mod board;
+mod bot;
+mod bot_match;
+mod chat;
+mod client;
+mod config;
+mod gtp;
+mod lobby;
+mod logging;
+mod metrics;
+mod presence;
+mod rate_limit;
+mod ratings;
+mod referee;
+mod room_settings;
 mod server;
+mod shutdown;
+mod storage;
+mod tls;
 
+use config::{Command, Config};
 use tokio::task;
 
 #[tokio::main]
 async fn main() {
+    let (config, command) = match Config::load() {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    };
+    config::init(config);
+
+    if let Some(command) = command {
+        let result = match command {
+            Command::BotMatch(args) => bot_match::run(args, config::get()),
+            Command::Gtp(args) => gtp::run(args, config::get()),
+            Command::Referee(args) => referee::run(args),
+            Command::Play(args) => client::run(args),
+        };
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // Spawn the server in the background
-    let server_task = task::spawn(server::start_server());
-    // Wait for the server to stop (it won't unless you terminate the program)
-    let _ = server_task.await;
+    let server_task = task::spawn(server::start_server(config::get()));
+    // Returns after SIGTERM or Ctrl-C once requests are drained and rooms flushed
+    match server_task.await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => {
+            eprintln!("server failed: {}", err);
+            std::process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("server task panicked: {}", err);
+            std::process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,140 @@
+// Prometheus metrics, scraped from /metrics.
+//
+// Counters only ever go up, rates like moves per minute come from PromQL, e.g.
+// `rate(go_moves_total[1m]) * 60`. Room and seat gauges are read from the room store
+// on every scrape rather than kept in sync by hand.
+
+use lazy_static::lazy_static;
+use poem::{Endpoint, IntoResponse, Request, Response, Result};
+use prometheus::{
+    register_histogram, register_histogram_vec, register_int_counter, register_int_counter_vec,
+    register_int_gauge, Encoder, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    TextEncoder,
+};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::board::GameResult;
+
+lazy_static! {
+    pub static ref ACTIVE_ROOMS: IntGauge =
+        register_int_gauge!("go_active_rooms", "Rooms currently in memory").unwrap();
+    pub static ref SEATED_PLAYERS: IntGauge =
+        register_int_gauge!("go_seated_players", "Seats taken across all rooms").unwrap();
+    static ref MOVES: IntCounterVec =
+        register_int_counter_vec!("go_moves_total", "Moves played, by kind", &["kind"]).unwrap();
+    static ref REFUSED_MOVES: IntCounterVec = register_int_counter_vec!(
+        "go_refused_moves_total",
+        "Move attempts refused, by error code",
+        &["code"]
+    )
+    .unwrap();
+    static ref GAMES_FINISHED: IntCounterVec = register_int_counter_vec!(
+        "go_games_finished_total",
+        "Games finished, by how they ended",
+        &["result"]
+    )
+    .unwrap();
+    static ref REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "go_request_duration_seconds",
+        "Time spent serving a request, by route",
+        &["route", "method", "status"]
+    )
+    .unwrap();
+    static ref ROOM_LOCK_WAIT: Histogram = register_histogram!(
+        "go_room_lock_wait_seconds",
+        "Time spent waiting for the room store lock",
+        vec![0.000_01, 0.000_1, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0]
+    )
+    .unwrap();
+    static ref SCRAPES: IntCounter =
+        register_int_counter!("go_metrics_scrapes_total", "Times /metrics was read").unwrap();
+}
+
+pub fn move_played(is_pass: bool) {
+    let kind = if is_pass { "pass" } else { "stone" };
+    MOVES.with_label_values(&[kind]).inc();
+}
+
+pub fn move_refused(code: &str) {
+    REFUSED_MOVES.with_label_values(&[code]).inc();
+}
+
+pub fn game_finished(result: &GameResult) {
+    let result = match result {
+        GameResult::Points(..) => "points",
+        GameResult::Resignation(_) => "resignation",
+        GameResult::Forfeit(_) => "forfeit",
+        GameResult::Draw => "draw",
+    };
+    GAMES_FINISHED.with_label_values(&[result]).inc();
+}
+
+pub fn room_lock_waited(wait: Duration) {
+    ROOM_LOCK_WAIT.observe(wait.as_secs_f64());
+}
+
+// Ids and file names are collapsed, so every room doesn't get its own time series
+fn route_label(path: &str) -> String {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["frontend", ..] => "/frontend".to_string(),
+        ["docs", ..] => "/docs".to_string(),
+        ["api", "v1", "games", _, rest @ ..] => {
+            let mut route = "/api/v1/games/{id}".to_string();
+            for segment in rest {
+                route.push('/');
+                route.push_str(segment);
+            }
+            route
+        }
+        // The POST routes, anything else is likely a scanner
+        [route]
+            if route.len() <= 32 && route.chars().all(|c| c.is_ascii_lowercase() || c == '-') =>
+        {
+            path.to_string()
+        }
+        _ => "other".to_string(),
+    }
+}
+
+pub async fn observe_request<E: Endpoint>(ep: Arc<E>, req: Request) -> Result<Response> {
+    let route = route_label(req.uri().path());
+    let method = req.method().to_string();
+    let started = Instant::now();
+
+    let response = match ep.call(req).await {
+        Ok(response) => response.into_response(),
+        Err(err) => err.into_response(),
+    };
+
+    REQUEST_DURATION
+        .with_label_values(&[&route, &method, response.status().as_str()])
+        .observe(started.elapsed().as_secs_f64());
+    Ok(response)
+}
+
+// Prometheus text exposition format
+pub fn render() -> String {
+    SCRAPES.inc();
+    let mut buffer = Vec::new();
+    let _ = TextEncoder::new().encode(&prometheus::gather(), &mut buffer);
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::route_label;
+
+    #[test]
+    fn room_ids_are_collapsed_in_route_labels() {
+        assert_eq!(route_label("/api/v1/games/abc"), "/api/v1/games/{id}");
+        assert_eq!(
+            route_label("/api/v1/games/abc/moves"),
+            "/api/v1/games/{id}/moves"
+        );
+        assert_eq!(route_label("/frontend/style.css"), "/frontend");
+        assert_eq!(route_label("/cell-click"), "/cell-click");
+        assert_eq!(route_label("/wp-admin/setup.php"), "other");
+    }
+}
@@ -0,0 +1,93 @@
+// Seat presence, driven by the heartbeats players send while polling for sync.
+//
+// Lingo:
+//     connected - heard from the seat within the heartbeat timeout
+//     disconnected - gone quiet, but still within the reconnect grace period
+//     abandoned - the grace period ran out, the opponent can claim a win by forfeit
+
+use crate::config::Config;
+use serde::Serialize;
+use std::time::{Duration, SystemTime};
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug)]
+pub struct PresenceConfig {
+    // How long without a heartbeat before a seat counts as disconnected
+    pub heartbeat_timeout: Duration,
+    // How long a disconnected player has to come back
+    pub reconnect_grace: Duration,
+}
+
+impl PresenceConfig {
+    pub fn from_config(config: &Config) -> Self {
+        PresenceConfig {
+            heartbeat_timeout: Duration::from_secs(config.presence.heartbeat_timeout_secs),
+            reconnect_grace: Duration::from_secs(config.presence.reconnect_grace_secs),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceStatus {
+    Connected,
+    Disconnected,
+    Abandoned,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ToSchema)]
+pub struct SeatPresence {
+    pub status: PresenceStatus,
+    // Seconds left before the seat is abandoned, only while disconnected
+    pub grace_remaining_secs: Option<u64>,
+}
+
+impl SeatPresence {
+    pub fn at(last_seen: SystemTime, now: SystemTime, config: &PresenceConfig) -> Self {
+        let quiet_for = now.duration_since(last_seen).unwrap_or_default();
+        let abandoned_after = config.heartbeat_timeout + config.reconnect_grace;
+
+        if quiet_for <= config.heartbeat_timeout {
+            SeatPresence {
+                status: PresenceStatus::Connected,
+                grace_remaining_secs: None,
+            }
+        } else if quiet_for <= abandoned_after {
+            SeatPresence {
+                status: PresenceStatus::Disconnected,
+                grace_remaining_secs: Some((abandoned_after - quiet_for).as_secs()),
+            }
+        } else {
+            SeatPresence {
+                status: PresenceStatus::Abandoned,
+                grace_remaining_secs: None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::presence::{PresenceConfig, PresenceStatus, SeatPresence};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn seat_is_abandoned_only_after_timeout_and_grace() {
+        let config = PresenceConfig {
+            heartbeat_timeout: Duration::from_secs(10),
+            reconnect_grace: Duration::from_secs(60),
+        };
+        let last_seen = SystemTime::now();
+        let after =
+            |secs| SeatPresence::at(last_seen, last_seen + Duration::from_secs(secs), &config);
+
+        assert_eq!(after(10).status, PresenceStatus::Connected);
+
+        let disconnected = after(30);
+        assert_eq!(disconnected.status, PresenceStatus::Disconnected);
+        assert_eq!(disconnected.grace_remaining_secs, Some(40));
+
+        assert_eq!(after(70).status, PresenceStatus::Disconnected);
+        assert_eq!(after(71).status, PresenceStatus::Abandoned);
+    }
+}
@@ -0,0 +1,228 @@
+// Request rate limits and caps on what a client can make the server hold.
+//
+// Lingo:
+//     window - the limits are sliding windows, like chat: at most `max_requests` in any
+//              `window` long stretch
+//     per IP - every request, counted by the client's address
+//     per session - seat actions (moves, passes, undo, ...), counted by session token
+//     new rooms - rooms created through /join-game, counted per IP, on top of the global
+//                 MAX_ROOMS cap
+//
+// Every refusal is a 429 with code `rate_limited`. The limiter locks are only held
+// for the check itself and never while taking another lock.
+
+use crate::api_error::ApiError;
+use crate::config::{self, Config};
+use lazy_static::lazy_static;
+use poem::error::ResponseError;
+use poem::http::{header, HeaderValue};
+use poem::{Endpoint, IntoResponse, Request, Response, Result};
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Probes and scrapes come from infrastructure at a fixed pace
+const UNLIMITED_PATHS: [&str; 3] = ["/healthz", "/readyz", "/metrics"];
+
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    pub max_requests: usize,
+    pub window: Duration,
+}
+
+impl RateLimit {
+    fn new(max_requests: usize, window_secs: u64) -> Self {
+        RateLimit {
+            max_requests,
+            window: Duration::from_secs(window_secs),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RateLimitConfig {
+    pub per_ip: RateLimit,
+    pub per_session: RateLimit,
+    pub new_rooms_per_ip: RateLimit,
+    pub max_rooms: usize,
+    pub max_match_string_length: usize,
+}
+
+impl RateLimitConfig {
+    pub fn from_config(config: &Config) -> Self {
+        let limits = &config.rate_limit;
+        RateLimitConfig {
+            per_ip: RateLimit::new(limits.ip_requests, limits.ip_window_secs),
+            per_session: RateLimit::new(limits.session_requests, limits.session_window_secs),
+            new_rooms_per_ip: RateLimit::new(
+                limits.new_rooms_requests,
+                limits.new_rooms_window_secs,
+            ),
+            max_rooms: limits.max_rooms,
+            max_match_string_length: limits.max_match_string_length,
+        }
+    }
+}
+
+pub struct RateLimiter {
+    limit: RateLimit,
+    // key -> when its recent requests arrived
+    recent: HashMap<String, VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        RateLimiter {
+            limit,
+            recent: HashMap::new(),
+        }
+    }
+
+    // Counts the request, or says how long until the key may try again
+    pub fn check(&mut self, key: &str, now: Instant) -> Result<(), Duration> {
+        let window = self.limit.window;
+        let recent = self.recent.entry(key.to_string()).or_default();
+        while recent
+            .front()
+            .is_some_and(|seen| now.duration_since(*seen) >= window)
+        {
+            recent.pop_front();
+        }
+
+        if recent.len() >= self.limit.max_requests {
+            let oldest = recent.front().copied().unwrap_or(now);
+            return Err(window.saturating_sub(now.duration_since(oldest)));
+        }
+        recent.push_back(now);
+        Ok(())
+    }
+
+    // Forgets keys with nothing left in their window, so one-off clients don't pile up
+    pub fn prune(&mut self, now: Instant) {
+        let window = self.limit.window;
+        self.recent.retain(|_, recent| {
+            recent
+                .back()
+                .is_some_and(|seen| now.duration_since(*seen) < window)
+        });
+    }
+}
+
+lazy_static! {
+    static ref CONFIG: RateLimitConfig = RateLimitConfig::from_config(config::get());
+    static ref IP_LIMITER: Mutex<RateLimiter> = Mutex::new(RateLimiter::new(CONFIG.per_ip));
+    static ref SESSION_LIMITER: Mutex<RateLimiter> =
+        Mutex::new(RateLimiter::new(CONFIG.per_session));
+    static ref NEW_ROOM_LIMITER: Mutex<RateLimiter> =
+        Mutex::new(RateLimiter::new(CONFIG.new_rooms_per_ip));
+}
+
+// A poisoned limiter lets requests through rather than taking the server down
+fn check(limiter: &Mutex<RateLimiter>, key: &str) -> Result<(), Duration> {
+    match limiter.lock() {
+        Ok(mut limiter) => limiter.check(key, Instant::now()),
+        Err(_) => Ok(()),
+    }
+}
+
+fn ip_key(ip: Option<IpAddr>) -> String {
+    ip.map(|ip| ip.to_string()).unwrap_or_default()
+}
+
+pub async fn limit_requests<E: Endpoint>(ep: Arc<E>, req: Request) -> Result<Response> {
+    if UNLIMITED_PATHS.contains(&req.uri().path()) {
+        return ep.call(req).await.map(IntoResponse::into_response);
+    }
+
+    let ip = req.remote_addr().as_socket_addr().map(|addr| addr.ip());
+    if let Err(retry_after) = check(&IP_LIMITER, &ip_key(ip)) {
+        let mut response =
+            ApiError::RateLimited("Too many requests, slow down".to_string()).as_response();
+        // Rounded up, retrying a fraction of a second early would be refused again
+        let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+        if let Ok(value) = HeaderValue::from_str(&secs.to_string()) {
+            response.headers_mut().insert(header::RETRY_AFTER, value);
+        }
+        return Ok(response);
+    }
+
+    ep.call(req).await.map(IntoResponse::into_response)
+}
+
+pub fn check_session(session_token: &str) -> Result<(), ApiError> {
+    check(&SESSION_LIMITER, session_token).map_err(|_| {
+        ApiError::RateLimited("Too many actions from this seat, slow down".to_string())
+    })
+}
+
+// Called before a request creates a room
+pub fn check_new_room(ip: Option<IpAddr>, room_count: usize) -> Result<(), ApiError> {
+    if room_count >= CONFIG.max_rooms {
+        return Err(ApiError::RateLimited(
+            "The server has too many games, try again later".to_string(),
+        ));
+    }
+    check(&NEW_ROOM_LIMITER, &ip_key(ip))
+        .map_err(|_| ApiError::RateLimited("Too many new games, try again later".to_string()))
+}
+
+pub fn check_match_string(match_string: &str) -> Result<(), ApiError> {
+    let max_length = CONFIG.max_match_string_length;
+    if match_string.chars().count() > max_length {
+        return Err(ApiError::InvalidRequest(format!(
+            "Game name is longer than {} characters",
+            max_length
+        )));
+    }
+    Ok(())
+}
+
+pub fn prune(now: Instant) {
+    for limiter in [&*IP_LIMITER, &*SESSION_LIMITER, &*NEW_ROOM_LIMITER] {
+        if let Ok(mut limiter) = limiter.lock() {
+            limiter.prune(now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rate_limit::{RateLimit, RateLimiter};
+    use std::time::{Duration, Instant};
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(RateLimit {
+            max_requests: 2,
+            window: Duration::from_secs(10),
+        })
+    }
+
+    #[test]
+    fn requests_over_the_limit_wait_for_the_window() {
+        let mut limiter = limiter();
+        let start = Instant::now();
+
+        assert!(limiter.check("a", start).is_ok());
+        assert!(limiter.check("a", start + Duration::from_secs(4)).is_ok());
+        assert_eq!(
+            limiter.check("a", start + Duration::from_secs(6)),
+            Err(Duration::from_secs(4))
+        );
+        // Other keys have their own window
+        assert!(limiter.check("b", start + Duration::from_secs(6)).is_ok());
+        assert!(limiter.check("a", start + Duration::from_secs(10)).is_ok());
+    }
+
+    #[test]
+    fn prune_forgets_idle_keys() {
+        let mut limiter = limiter();
+        let start = Instant::now();
+        limiter.check("a", start).unwrap();
+        limiter.check("b", start + Duration::from_secs(8)).unwrap();
+
+        limiter.prune(start + Duration::from_secs(12));
+        assert_eq!(limiter.recent.len(), 1);
+        assert!(limiter.recent.contains_key("b"));
+    }
+}
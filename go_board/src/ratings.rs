@@ -0,0 +1,283 @@
+// Elo ratings for games between two accounts.
+//
+// Lingo:
+//     pool - players are only rated against others on the same board size and variant,
+//            a 9x9 rating says little about 13x13 strength
+//     rating change - one entry of a player's history, written for every rated game
+
+use crate::board::{GameResult, Player};
+use crate::room_settings::{RoomSettings, Variant};
+use crate::storage::{load_json, save_json};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use utoipa::ToSchema;
+
+pub const INITIAL_RATING: f64 = 1500.0;
+// How far a single game can move a rating
+const K_FACTOR: f64 = 32.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct RatingPool {
+    pub board_size: usize,
+    pub variant: Variant,
+}
+
+impl Default for RatingPool {
+    fn default() -> Self {
+        RatingPool::for_settings(&RoomSettings::default())
+    }
+}
+
+impl RatingPool {
+    pub fn for_settings(settings: &RoomSettings) -> Self {
+        RatingPool {
+            board_size: settings.board_size,
+            variant: settings.variant,
+        }
+    }
+
+    fn key(&self) -> String {
+        format!("{}-{}", self.variant, self.board_size)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct RatingChange {
+    // Empty in entries written before games had ids
+    #[serde(default)]
+    pub game_id: String,
+    pub match_string: String,
+    pub opponent_id: String,
+    // 1 for a win, 0.5 for a draw, 0 for a loss
+    pub score: f64,
+    pub before: f64,
+    pub after: f64,
+    pub rated_at: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PlayerRating {
+    pub rating: f64,
+    pub games: u32,
+    pub history: Vec<RatingChange>,
+}
+
+impl Default for PlayerRating {
+    fn default() -> Self {
+        PlayerRating {
+            rating: INITIAL_RATING,
+            games: 0,
+            history: Vec::new(),
+        }
+    }
+}
+
+// A finished game between two accounts
+pub struct RatedGame {
+    // Unique per game, a match string is reused once its room is swept
+    pub game_id: String,
+    pub match_string: String,
+    pub black_id: String,
+    pub white_id: String,
+    pub result: GameResult,
+    pub rated_at: u64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct RatingStore {
+    // pool key -> account id -> rating
+    pools: HashMap<String, HashMap<String, PlayerRating>>,
+    // Ids of the games already rated, so a result can't be counted twice
+    rated_games: HashSet<String>,
+    // Where the store is written after every change, None keeps it in memory only
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+fn expected_score(rating: f64, opponent_rating: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent_rating - rating) / 400.0))
+}
+
+// Black's score in the game
+fn black_score(result: &GameResult) -> f64 {
+    match result {
+        GameResult::Points(Player::Black, _)
+        | GameResult::Resignation(Player::Black)
+        | GameResult::Forfeit(Player::Black) => 1.0,
+        GameResult::Points(Player::White, _)
+        | GameResult::Resignation(Player::White)
+        | GameResult::Forfeit(Player::White) => 0.0,
+        GameResult::Draw => 0.5,
+    }
+}
+
+impl RatingStore {
+    // Starts empty if the file doesn't exist yet
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut store: RatingStore = load_json(path)?;
+        store.path = Some(path.to_path_buf());
+        Ok(store)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        match &self.path {
+            Some(path) => save_json(path, self),
+            None => Ok(()),
+        }
+    }
+
+    // Returns false if the game was already rated
+    pub fn record_game(&mut self, pool: &RatingPool, game: &RatedGame) -> io::Result<bool> {
+        if !self.rated_games.insert(game.game_id.clone()) {
+            return Ok(false);
+        }
+
+        let (black_id, white_id) = (game.black_id.as_str(), game.white_id.as_str());
+
+        let players = self.pools.entry(pool.key()).or_default();
+        let black_before = players.get(black_id).cloned().unwrap_or_default().rating;
+        let white_before = players.get(white_id).cloned().unwrap_or_default().rating;
+
+        let black_score = black_score(&game.result);
+        let updates = [
+            (black_id, white_id, black_score, black_before, white_before),
+            (
+                white_id,
+                black_id,
+                1.0 - black_score,
+                white_before,
+                black_before,
+            ),
+        ];
+
+        for (id, opponent_id, score, before, opponent_before) in updates {
+            let after = before + K_FACTOR * (score - expected_score(before, opponent_before));
+            let rating = players.entry(id.to_string()).or_default();
+
+            rating.rating = after;
+            rating.games += 1;
+            rating.history.push(RatingChange {
+                game_id: game.game_id.clone(),
+                match_string: game.match_string.clone(),
+                opponent_id: opponent_id.to_string(),
+                score,
+                before,
+                after,
+                rated_at: game.rated_at,
+            });
+        }
+
+        self.save()?;
+        Ok(true)
+    }
+
+    pub fn rating(&self, pool: &RatingPool, account_id: &str) -> Option<&PlayerRating> {
+        self.pools
+            .get(&pool.key())
+            .and_then(|players| players.get(account_id))
+    }
+
+    // Highest rating first
+    pub fn leaderboard(&self, pool: &RatingPool, limit: usize) -> Vec<(String, PlayerRating)> {
+        let mut entries: Vec<(String, PlayerRating)> = self
+            .pools
+            .get(&pool.key())
+            .map(|players| {
+                players
+                    .iter()
+                    .map(|(id, rating)| (id.clone(), rating.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        entries.sort_by(|a, b| b.1.rating.total_cmp(&a.1.rating));
+        entries.truncate(limit);
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{GameResult, Player};
+    use crate::ratings::{RatedGame, RatingPool, RatingStore, INITIAL_RATING};
+
+    fn game(game_id: &str, result: GameResult) -> RatedGame {
+        RatedGame {
+            game_id: game_id.to_string(),
+            match_string: "room".to_string(),
+            black_id: "b".to_string(),
+            white_id: "w".to_string(),
+            result,
+            rated_at: 0,
+        }
+    }
+
+    #[test]
+    fn winner_gains_what_the_loser_loses() {
+        let mut store = RatingStore::default();
+        let pool = RatingPool::default();
+
+        store
+            .record_game(&pool, &game("game", GameResult::Resignation(Player::White)))
+            .unwrap();
+
+        let black = store.rating(&pool, "b").unwrap().rating;
+        let white = store.rating(&pool, "w").unwrap().rating;
+        assert_eq!(white - INITIAL_RATING, 16.0);
+        assert_eq!(black + white, 2.0 * INITIAL_RATING);
+        assert_eq!(store.leaderboard(&pool, 10)[0].0, "w");
+    }
+
+    #[test]
+    fn a_game_is_only_rated_once() {
+        let mut store = RatingStore::default();
+        let pool = RatingPool::default();
+        let result = GameResult::Points(Player::Black, 3.5);
+
+        assert!(store
+            .record_game(&pool, &game("game", result.clone()))
+            .unwrap());
+        assert!(!store.record_game(&pool, &game("game", result)).unwrap());
+        assert_eq!(store.rating(&pool, "b").unwrap().games, 1);
+    }
+
+    #[test]
+    fn games_in_a_reused_room_are_all_rated() {
+        let mut store = RatingStore::default();
+        let pool = RatingPool::default();
+
+        for game_id in ["first", "second"] {
+            assert!(store
+                .record_game(&pool, &game(game_id, GameResult::Draw))
+                .unwrap());
+        }
+        let black = store.rating(&pool, "b").unwrap();
+        assert_eq!(black.games, 2);
+        // The history tells the two games apart, not just their room
+        let game_ids: Vec<&str> = black
+            .history
+            .iter()
+            .map(|change| change.game_id.as_str())
+            .collect();
+        assert_eq!(game_ids, ["first", "second"]);
+    }
+
+    #[test]
+    fn pools_are_kept_apart() {
+        let mut store = RatingStore::default();
+        let small = RatingPool {
+            board_size: 9,
+            ..RatingPool::default()
+        };
+
+        store
+            .record_game(&small, &game("game", GameResult::Draw))
+            .unwrap();
+
+        assert!(store.rating(&small, "b").is_some());
+        assert!(store.rating(&RatingPool::default(), "b").is_none());
+    }
+}
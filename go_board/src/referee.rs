@@ -0,0 +1,418 @@
+// A local Phantom Go referee between two GTP engines, run as child processes:
+//
+//     go_board referee --black "go_board gtp --bot mcts" --white "gnugo --mode gtp"
+//
+// The referee keeps the real board and tells each engine only what its seat may know.
+// Engines that know the phantom-* extensions (see gtp.rs) are seated with phantom-seat and
+// told about refusals, the opponent's hidden moves, captures and ataris. Any other engine
+// only gets the opponent's passes, and a refused move as `undo` plus the opponent's stone
+// played where it tried. It never learns which of its stones were captured.
+//
+// Commands are split on whitespace, there is no shell quoting.
+
+use crate::board::{Board, Color, GameResult, Loc, Move, Player};
+use crate::bot::Point;
+use crate::gtp::{parse_vertex, vertex};
+use crate::room_settings::RoomSettings;
+use clap::Args;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::time::Instant;
+
+#[derive(Debug, Args)]
+pub struct RefereeArgs {
+    /// Command line of the engine playing black
+    #[arg(long)]
+    black: String,
+    /// Command line of the engine playing white
+    #[arg(long)]
+    white: String,
+    #[arg(long, default_value_t = 1)]
+    games: usize,
+    /// Playable size, without the border
+    #[arg(long, default_value_t = 9)]
+    board_size: usize,
+    #[arg(long, default_value_t = 6.5)]
+    komi: f32,
+    /// Writes every game there as game-<n>.sgf
+    #[arg(long)]
+    sgf_dir: Option<PathBuf>,
+}
+
+// Anything that answers GTP commands, the response without its `=`
+pub trait GtpClient {
+    fn command(&mut self, line: &str) -> Result<String, String>;
+}
+
+pub struct GtpProcess {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl GtpProcess {
+    pub fn spawn(command_line: &str) -> Result<Self, String> {
+        let mut words = command_line.split_whitespace();
+        let program = words.next().ok_or("empty engine command")?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("can't start {}: {}", program, err))?;
+
+        let stdin = child.stdin.take().ok_or("engine without stdin")?;
+        let stdout = child.stdout.take().ok_or("engine without stdout")?;
+        Ok(GtpProcess {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+        })
+    }
+}
+
+impl GtpClient for GtpProcess {
+    fn command(&mut self, line: &str) -> Result<String, String> {
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|err| format!("engine stopped listening: {}", err))?;
+
+        // A response ends with an empty line
+        let mut response = String::new();
+        loop {
+            let mut line = String::new();
+            let read = self
+                .stdout
+                .read_line(&mut line)
+                .map_err(|err| format!("can't read the engine: {}", err))?;
+            if read == 0 {
+                return Err("engine exited".to_string());
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() && !response.is_empty() {
+                break;
+            }
+            if !line.is_empty() {
+                response.push_str(line);
+                response.push('\n');
+            }
+        }
+
+        let response = response.trim_end();
+        let body = response.get(1..).unwrap_or_default();
+        // Skips the id, the referee doesn't send any
+        let body = body
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .trim()
+            .to_string();
+        match response.chars().next() {
+            Some('=') => Ok(body),
+            _ => Err(body),
+        }
+    }
+}
+
+impl Drop for GtpProcess {
+    fn drop(&mut self) {
+        let _ = self.command("quit");
+        let _ = self.child.wait();
+    }
+}
+
+// One engine and whether it speaks the phantom extensions
+struct Seat<'a> {
+    player: Player,
+    engine: &'a mut dyn GtpClient,
+    phantom: bool,
+}
+
+impl Seat<'_> {
+    fn send(&mut self, line: &str) -> Result<String, String> {
+        self.engine
+            .command(line)
+            .map_err(|err| format!("{} engine, `{}`: {}", self.player, line, err))
+    }
+}
+
+pub fn run(args: RefereeArgs) -> Result<(), String> {
+    RoomSettings {
+        board_size: args.board_size,
+        komi: args.komi,
+        ..RoomSettings::default()
+    }
+    .validate()?;
+    if let Some(dir) = &args.sgf_dir {
+        fs::create_dir_all(dir)
+            .map_err(|err| format!("can't create {}: {}", dir.display(), err))?;
+    }
+
+    let mut black = GtpProcess::spawn(&args.black)?;
+    let mut white = GtpProcess::spawn(&args.white)?;
+
+    let mut wins = (0, 0);
+    for game in 1..=args.games {
+        let started = Instant::now();
+        let RefereedGame {
+            result,
+            board,
+            mut comments,
+        } = referee_game(&mut black, &mut white, args.board_size, args.komi)?;
+
+        match result {
+            GameResult::Points(Player::Black, _) | GameResult::Resignation(Player::White) => {
+                wins.0 += 1
+            }
+            GameResult::Points(Player::White, _) | GameResult::Resignation(Player::Black) => {
+                wins.1 += 1
+            }
+            _ => {}
+        }
+        println!(
+            "game {}: {} after {} moves in {:.1}s",
+            game,
+            result,
+            board.game_history.len(),
+            started.elapsed().as_secs_f32()
+        );
+
+        if let Some(dir) = &args.sgf_dir {
+            let path = dir.join(format!("game-{}.sgf", game));
+            comments.entry(0).or_default().extend([
+                format!("Black: {}", args.black),
+                format!("White: {}", args.white),
+                format!("Result: {}", result),
+            ]);
+            fs::write(&path, board.get_game_sgf_with_comments(&comments))
+                .map_err(|err| format!("can't write {}: {}", path.display(), err))?;
+        }
+    }
+
+    println!(
+        "black won {}, white won {} of {} games",
+        wins.0, wins.1, args.games
+    );
+    Ok(())
+}
+
+pub struct RefereedGame {
+    pub result: GameResult,
+    // The real board, with both engines' moves
+    pub board: Board,
+    // The refused attempts, keyed like Board::get_game_sgf_with_comments expects
+    pub comments: HashMap<usize, Vec<String>>,
+}
+
+// Plays one game to the end
+pub fn referee_game(
+    black: &mut dyn GtpClient,
+    white: &mut dyn GtpClient,
+    board_size: usize,
+    komi: f32,
+) -> Result<RefereedGame, String> {
+    let mut board = Board::new(board_size + 2, board_size + 2, komi);
+    let mut comments: HashMap<usize, Vec<String>> = HashMap::new();
+    let mut seats = [
+        Seat {
+            player: Player::Black,
+            engine: black,
+            phantom: false,
+        },
+        Seat {
+            player: Player::White,
+            engine: white,
+            phantom: false,
+        },
+    ];
+    for seat in &mut seats {
+        seat.phantom = seat.send("known_command phantom-seat")? == "true";
+        seat.send(&format!("boardsize {}", board_size))?;
+        seat.send("clear_board")?;
+        seat.send(&format!("komi {}", komi))?;
+        if seat.phantom {
+            let color = seat.player.to_string();
+            seat.send(&format!("phantom-seat {}", color))?;
+        }
+    }
+
+    // Long endgames can repeat for a while, cut them off
+    let max_moves = 4 * board_size * board_size;
+    while !board.is_counting() && board.game_history.len() < max_moves {
+        let player = board.get_current_player();
+        let (mover, other) = match player {
+            Player::Black => (0, 1),
+            Player::White => (1, 0),
+        };
+        let color = player.to_string();
+
+        let mut refusals = 0;
+        let played = loop {
+            let response = seats[mover].send(&format!("genmove {}", color))?;
+            if response.eq_ignore_ascii_case("resign") {
+                return Ok(RefereedGame {
+                    result: GameResult::Resignation(player),
+                    board,
+                    comments,
+                });
+            }
+            let point = parse_vertex(&response, board_size)
+                .map_err(|err| format!("{} engine played `{}`: {}", color, response, err))?;
+
+            let before = board.fields.clone();
+            let moves = board.game_history.len();
+            board.play(&Move {
+                player,
+                loc: point.map_or(Loc::pass(), Point::loc),
+            });
+            if board.game_history.len() > moves {
+                break (point, captured(&before, &board, player.opponent()));
+            }
+
+            // Passes are always legal, so a point was refused
+            let tried = vertex(point, board_size);
+            comments
+                .entry(board.game_history.len() + 1)
+                .or_default()
+                .push(format!("{} tried {}", color, tried));
+            let seat = &mut seats[mover];
+            if seat.phantom {
+                seat.send("phantom-illegal")?;
+            } else {
+                seat.send("undo")?;
+                // Fails on suicide and ko refusals, which leave nothing to show
+                let _ = seat.send(&format!("play {} {}", player.opponent(), tried));
+            }
+
+            // An engine that keeps trying refused points would never stop, it passes instead
+            refusals += 1;
+            if refusals > board_size * board_size {
+                seat.send(&format!("play {} pass", color))?;
+                board.play(&Move {
+                    player,
+                    loc: Loc::pass(),
+                });
+                break (None, Vec::new());
+            }
+        };
+
+        let (point, captured) = played;
+        let captured: String = captured
+            .iter()
+            .map(|point| format!(" {}", vertex(Some(*point), board_size)))
+            .collect();
+        let atari = [
+            (Player::Black, board.stones_in_atari.black),
+            (Player::White, board.stones_in_atari.white),
+        ];
+
+        if point.is_none() {
+            seats[other].send(&format!("play {} pass", color))?;
+        } else if seats[other].phantom {
+            seats[other].send("phantom-opponent_moved")?;
+        }
+        for seat in [mover, other] {
+            let seat = &mut seats[seat];
+            if !seat.phantom {
+                continue;
+            }
+            if !captured.is_empty() {
+                seat.send(&format!("phantom-captured{}", captured))?;
+            }
+            for (player, stones) in atari.iter().filter(|(_, stones)| *stones > 0) {
+                seat.send(&format!("phantom-atari {} {}", player, stones))?;
+            }
+        }
+    }
+
+    Ok(RefereedGame {
+        result: board.count_score(),
+        board,
+        comments,
+    })
+}
+
+// Stones of `color`'s player that the last move took off
+fn captured(before: &[Vec<Color>], board: &Board, victim: Player) -> Vec<Point> {
+    let color = match victim {
+        Player::Black => Color::Black,
+        Player::White => Color::White,
+    };
+    let size = board.fields.len() - 2;
+    (0..size)
+        .flat_map(|row| (0..size).map(move |col| Point { row, col }))
+        .filter(|point| {
+            let loc = point.loc();
+            before[loc.row][loc.col] == color && board.fields[loc.row][loc.col] == Color::Empty
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::{BotKind, MctsConfig};
+    use crate::gtp::Engine;
+    use crate::referee::{referee_game, GtpClient};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // An in-process engine that keeps what it was told
+    struct Recorder {
+        engine: Engine,
+        log: Vec<String>,
+    }
+
+    impl Recorder {
+        fn new(seed: u64) -> Self {
+            let mcts = MctsConfig {
+                playouts: 16,
+                determinizations: 2,
+                exploration: 1.0,
+            };
+            Recorder {
+                engine: Engine::new(9, 6.5, BotKind::Random, mcts, StdRng::seed_from_u64(seed)),
+                log: Vec::new(),
+            }
+        }
+    }
+
+    impl GtpClient for Recorder {
+        fn command(&mut self, line: &str) -> Result<String, String> {
+            self.log.push(line.to_string());
+            let response = self.engine.handle_line(line).expect("a response");
+            let body = response[1..].trim().to_string();
+            match response.starts_with('=') {
+                true => Ok(body),
+                false => Err(body),
+            }
+        }
+    }
+
+    #[test]
+    fn engines_are_only_told_their_own_seat() {
+        let mut black = Recorder::new(1);
+        let mut white = Recorder::new(2);
+
+        let game = referee_game(&mut black, &mut white, 5, 0.5).expect("a finished game");
+        let (board, comments) = (game.board, game.comments);
+        assert!(board.is_counting() || board.game_history.len() >= 4 * 25);
+        assert!(black.log.contains(&"phantom-seat black".to_string()));
+
+        // Nobody hears where the opponent played, only that it did
+        for (log, opponent) in [(&black.log, "white"), (&white.log, "black")] {
+            assert!(log
+                .iter()
+                .filter(|line| line.starts_with("play"))
+                .all(|line| line == &format!("play {} pass", opponent)));
+        }
+
+        let refused = black
+            .log
+            .iter()
+            .chain(&white.log)
+            .filter(|line| *line == "phantom-illegal")
+            .count();
+        assert_eq!(refused, comments.values().map(Vec::len).sum::<usize>());
+    }
+}
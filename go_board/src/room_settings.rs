@@ -0,0 +1,124 @@
+use crate::board::Board;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use utoipa::ToSchema;
+
+// The frontend draws at most a 13x13 grid and SGF coordinates are only mapped up to 13
+pub const MIN_BOARD_SIZE: usize = 5;
+pub const MAX_BOARD_SIZE: usize = 13;
+
+// How a room treats undo:
+// - disabled: moves are final
+// - ask: the opponent has to accept, since undoing also changes what they've learned
+// - free: the player who just moved can take it back, meant for casual games, which
+//   are never rated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum UndoPolicy {
+    Disabled,
+    #[default]
+    Ask,
+    Free,
+}
+
+// Only phantom go is played for now, the field is here so challenges already carry it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Variant {
+    #[default]
+    Phantom,
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Variant::Phantom => write!(f, "phantom"),
+        }
+    }
+}
+
+// How far behind the live game spectators are kept
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SpectatorDelay {
+    #[default]
+    None,
+    Moves(usize),
+    Seconds(u64),
+}
+
+pub const MAX_SPECTATOR_DELAY_MOVES: usize = 50;
+pub const MAX_SPECTATOR_DELAY_SECS: u64 = 600;
+
+// Everything that's fixed when a room is created
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct RoomSettings {
+    // Playable size, without the sentinel border
+    pub board_size: usize,
+    pub komi: f32,
+    // Agreed thinking time per player. Stored with the room, the server doesn't run clocks yet
+    pub main_time_secs: Option<u64>,
+    pub variant: Variant,
+    pub undo_policy: UndoPolicy,
+    // When false, nobody can watch until the game is over
+    pub allow_spectators: bool,
+    pub spectator_delay: SpectatorDelay,
+}
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        RoomSettings {
+            board_size: 13,
+            komi: 1.5,
+            main_time_secs: None,
+            variant: Variant::Phantom,
+            undo_policy: UndoPolicy::Ask,
+            allow_spectators: true,
+            spectator_delay: SpectatorDelay::None,
+        }
+    }
+}
+
+impl RoomSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&self.board_size) {
+            return Err(format!(
+                "Board size must be between {} and {}",
+                MIN_BOARD_SIZE, MAX_BOARD_SIZE
+            ));
+        }
+
+        // Komi has to be a whole or half point
+        if !self.komi.is_finite() || self.komi.abs() > 50.0 || (self.komi * 2.0).fract() != 0.0 {
+            return Err("Komi must be a multiple of 0.5 between -50 and 50".to_string());
+        }
+
+        if self.main_time_secs == Some(0) {
+            return Err("Main time must be longer than 0 seconds".to_string());
+        }
+
+        match self.spectator_delay {
+            SpectatorDelay::Moves(moves) if moves > MAX_SPECTATOR_DELAY_MOVES => {
+                return Err(format!(
+                    "Spectator delay can be at most {} moves",
+                    MAX_SPECTATOR_DELAY_MOVES
+                ));
+            }
+            SpectatorDelay::Seconds(secs) if secs > MAX_SPECTATOR_DELAY_SECS => {
+                return Err(format!(
+                    "Spectator delay can be at most {} seconds",
+                    MAX_SPECTATOR_DELAY_SECS
+                ));
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn new_board(&self) -> Board {
+        // Add 2 to each dimension for the sentinels
+        Board::new(self.board_size + 2, self.board_size + 2, self.komi)
+    }
+}
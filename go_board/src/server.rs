@@ -1,53 +1,97 @@
-use crate::board::{Board, Color, GameResult, Loc, Move, Player, StonesInAtari};
+// poem::Error is large by design and every handler returns it
+#![allow(clippy::result_large_err)]
+
+pub mod api_v1;
+
+use crate::accounts::{
+    hash_password, verify_password, Account, AccountError, AccountStore, PublicAccount,
+    MAX_DISPLAY_NAME_LENGTH,
+};
+use crate::api_docs::{openapi_spec, SwaggerUi, SPEC_PATH};
+use crate::api_error::{ApiError, ErrorBody};
+use crate::archive::{ArchiveStore, ArchivedGame, ArchivedGameSummary, ExpiryReason};
+use crate::board::{Board, Color, GameEvent, GameResult, Loc, Move, Player, StonesInAtari};
+use crate::bot::{Bot, BotKind, BotMove, MctsConfig, Point, SeatView};
+use crate::chat::{
+    sgf_comments, ChatChannel, ChatError, ChatMessage, RoomChat, MAX_MESSAGE_LENGTH,
+};
+use crate::config::{self, Config};
+use crate::lobby::{Challenge, Lobby, LobbyError};
+use crate::logging;
+use crate::metrics;
+use crate::presence::{PresenceConfig, PresenceStatus, SeatPresence};
+use crate::rate_limit;
+use crate::ratings::{PlayerRating, RatedGame, RatingPool, RatingStore};
+use crate::room_settings::{RoomSettings, SpectatorDelay, UndoPolicy};
+use crate::shutdown::{self, ShutdownConfig};
+use crate::storage;
+use crate::tls::{self, TlsFiles};
 use lazy_static::lazy_static;
 use poem::{
     async_trait, handler,
     http::{header, Method, StatusCode},
-    listener::TcpListener,
+    listener::{Listener, TcpListener},
     middleware::Cors,
-    web::{Json, Redirect},
+    web::{Json, Redirect, RemoteAddr},
     Endpoint, EndpointExt, Error, Request, Response, Result, Route, Server,
 };
 use rand::random;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
-use std::env;
-use std::sync::Mutex;
+use std::net::IpAddr;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tokio::{
-    spawn,
+    runtime::Handle,
+    spawn, task,
     time::{sleep, Duration},
 };
+use tracing::{debug, error, info, warn, Instrument};
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct JoinGameRequest {
     match_string: String,
     session_token: Option<String>,
     is_spectator: bool,
+    #[serde(default)]
+    login_token: Option<String>,
+    // Only used when this request creates the room
+    #[serde(default = "default_room_settings")]
+    settings: RoomSettings,
+    // Also only used when creating the room, the bot takes the other seat
+    #[serde(default)]
+    bot: Option<BotKind>,
 }
 
-#[derive(Serialize)]
+// Settings sent only in part fill the rest in from the built in defaults
+fn default_room_settings() -> RoomSettings {
+    config::get().default_room.clone()
+}
+
+#[derive(Serialize, Clone, ToSchema)]
 struct JoinGameResponse {
     color: String,
     redirect_url: String,
     session_token: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct BoardDimensions {
     rows: usize,
     cols: usize,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct CellClick {
     pub row: usize,
     pub col: usize,
     match_string: String,
-    session_token: String,
-    board_generation_number: usize,
+    #[serde(default)]
+    session_token: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct PlayerGroupsInAtari {
     groups: HashSet<Vec<Loc>>,
 }
@@ -60,7 +104,7 @@ impl PlayerGroupsInAtari {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct GameState {
     message: String,
     board: Vec<Vec<String>>,
@@ -76,9 +120,12 @@ struct GameState {
     counting: bool,
     winner: Option<String>,
     board_generation_number: usize,
-    rejoin_required: bool,
     groups_selected_during_counting: GroupsToRemove,
     ready_to_count: ReadyToCount,
+    // Color of the player waiting for the opponent to accept their undo
+    pending_undo: Option<String>,
+    // Only sent through sync, filtered for whoever asked
+    chat: Vec<ChatMessage>,
 }
 
 impl GameState {
@@ -100,15 +147,17 @@ impl GameState {
             white_guess_stones: vec![],
             groups_in_atari: PlayerGroupsInAtari::new(),
             stones_in_atari: StonesInAtari::new(),
-            counting: board.last_two_moves_are_pass(),
+            counting: board.is_counting(),
             winner: None,
             board_generation_number,
-            rejoin_required: false,
             groups_selected_during_counting: GroupsToRemove {
                 selected: HashSet::from([vec![Loc::from_string("100, 100").unwrap()]]),
                 toggle: vec![Loc::from_string("100, 100").unwrap()],
+                version: 0,
             },
             ready_to_count: ReadyToCount::new(),
+            pending_undo: None,
+            chat: vec![],
         };
 
         if game_state.counting {
@@ -138,11 +187,6 @@ impl GameState {
         self
     }
 
-    fn with_rejoin_required(mut self) -> Self {
-        self.rejoin_required = true;
-        self
-    }
-
     fn with_groups_selected_during_counting(mut self, groups_to_remove: GroupsToRemove) -> Self {
         self.groups_selected_during_counting = groups_to_remove;
         self
@@ -152,18 +196,55 @@ impl GameState {
         self.ready_to_count = rtc;
         self
     }
+
+    fn with_pending_undo(mut self, pending_undo: Option<UndoRequest>) -> Self {
+        self.pending_undo = pending_undo.map(|request| request.requested_by.to_string());
+        self
+    }
+
+    fn with_chat(mut self, chat: Vec<ChatMessage>) -> Self {
+        self.chat = chat;
+        self
+    }
+
+    // What a seat may see of the board, the frontend only hides stones it was sent
+    fn for_seat(mut self, room: &GameRoom, player: Player) -> Self {
+        let board = seat_board(room, player);
+        self.black_player_board = board.clone();
+        self.white_player_board = board.clone();
+        self.board = board;
+        self
+    }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, ToSchema)]
 struct GuessStonesSync {
-    color: String,
     stones: Vec<Vec<usize>>,
     match_string: String,
+    #[serde(default)]
+    session_token: Option<String>,
 }
 
 #[derive(Clone)]
 struct PlayerSession {
     session_token: String,
+    // Account the seat belongs to, None for guests
+    account_id: Option<String>,
+    // Where the player last joined from, that address can't spectate their live game
+    ip: Option<IpAddr>,
+    // Last heartbeat from the seat's token
+    last_seen: SystemTime,
+}
+
+impl PlayerSession {
+    fn new(session_token: String, account_id: Option<String>, ip: Option<IpAddr>) -> Self {
+        PlayerSession {
+            session_token,
+            account_id,
+            ip,
+            last_seen: SystemTime::now(),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -181,24 +262,192 @@ impl PlayersState {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+struct UndoRequest {
+    requested_by: Player,
+    // Length of the game history when the undo was requested, so a stale request can't undo a newer move
+    move_number: usize,
+}
+
+// Dead stone selection during counting. The server owns it, players only toggle
+// groups and accept a specific version of it
+#[derive(Clone, Debug, Default)]
+struct CountingState {
+    selected: HashSet<Vec<Loc>>,
+    // Bumped on every toggle, so an acceptance can't apply to a selection the player hasn't seen
+    version: usize,
+    black_accepted: Option<usize>,
+    white_accepted: Option<usize>,
+}
+
+impl CountingState {
+    fn toggle(&mut self, group: Vec<Loc>) {
+        if !self.selected.remove(&group) {
+            self.selected.insert(group);
+        }
+        self.version += 1;
+    }
+
+    fn accept(&mut self, player: Player, version: usize) {
+        match player {
+            Player::Black => self.black_accepted = Some(version),
+            Player::White => self.white_accepted = Some(version),
+        }
+    }
+
+    fn ready_to_count(&self) -> ReadyToCount {
+        ReadyToCount {
+            black: self.black_accepted == Some(self.version),
+            white: self.white_accepted == Some(self.version),
+        }
+    }
+
+    fn is_agreed(&self) -> bool {
+        let ready = self.ready_to_count();
+        ready.black && ready.white
+    }
+
+    fn groups_to_remove(&self, toggle: Vec<Loc>) -> GroupsToRemove {
+        GroupsToRemove {
+            selected: self.selected.clone(),
+            toggle,
+            version: self.version,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum AnnouncementKind {
+    Played,
+    Passed,
+    Undone,
+    ResumedPlay,
+}
+
+// What the referee says out loud after an event, never where a stone went
+#[derive(Clone, Debug, Serialize, ToSchema)]
+struct Announcement {
+    // Length of the game history after the event
+    move_number: usize,
+    player: String,
+    kind: AnnouncementKind,
+    // Stones the event took off the board
+    captured: usize,
+    stones_in_atari: StonesInAtari,
+}
+
+fn total_captures(board: &Board) -> isize {
+    board.get_black_captures() + board.get_white_captures()
+}
+
+fn announce(room: &mut GameRoom, player: Player, kind: AnnouncementKind, captures_before: isize) {
+    let captured = total_captures(&room.board) - captures_before;
+    room.announcements.push(Announcement {
+        move_number: room.board.game_history.len(),
+        player: player.to_string(),
+        kind,
+        captured: usize::try_from(captured).unwrap_or(0),
+        stones_in_atari: room.board.stones_in_atari.clone(),
+    });
+}
+
+// A server side player sitting in one of the room's seats
+#[derive(Clone)]
+struct BotSeat {
+    player: Player,
+    brain: Bot,
+    session_token: String,
+    // What the referee refused since the bot last moved, and how many moves were played then
+    refused: HashSet<Point>,
+    refused_at_move: usize,
+}
+
 #[derive(Clone)]
 struct GameRoom {
+    // Unique per game, the match string is reused once the room is swept
+    game_id: String,
     board: Board,
     players: PlayersState,
     game_generation_number: usize,
+    settings: RoomSettings,
+    pending_undo: Option<UndoRequest>,
+    counting: CountingState,
+    finished_at: Option<SystemTime>,
+    chat: RoomChat,
+    // When each move in the game history was played, for spectator delays in seconds
+    move_times: Vec<SystemTime>,
+    bot: Option<BotSeat>,
+    // Everything the referee told both seats, oldest first
+    announcements: Vec<Announcement>,
 }
 
 impl GameRoom {
-    fn new() -> Self {
+    fn new(settings: RoomSettings) -> Self {
         GameRoom {
-            board: Board::new(15, 15, 1.5),
+            game_id: uuid::Uuid::new_v4().to_string(),
+            board: settings.new_board(),
             players: PlayersState::new(),
             game_generation_number: 0,
+            settings,
+            pending_undo: None,
+            counting: CountingState::default(),
+            finished_at: None,
+            chat: RoomChat::default(),
+            move_times: Vec::new(),
+            bot: None,
+            announcements: Vec::new(),
+        }
+    }
+
+    // Which lifetime rule, if any, says this room should be swept
+    fn expiry(&self, lifetime: &RoomLifetime, now: SystemTime) -> Option<ExpiryReason> {
+        let age = |since: SystemTime| now.duration_since(since).unwrap_or_default();
+
+        if let Some(finished_at) = self.finished_at {
+            return (age(finished_at) > lifetime.finished).then_some(ExpiryReason::Finished);
+        }
+
+        // With no moves on the board, the last move timestamp is when the room was created
+        let since_last_move = age(self.board.last_move_timestamp);
+        let never_started = self.board.game_history.is_empty()
+            || self.players.black.is_none()
+            || self.players.white.is_none();
+
+        if never_started && since_last_move > lifetime.never_started {
+            Some(ExpiryReason::NeverStarted)
+        } else if since_last_move > lifetime.idle {
+            Some(ExpiryReason::Idle)
+        } else {
+            None
+        }
+    }
+}
+
+// How long rooms are kept around
+#[derive(Clone, Debug)]
+struct RoomLifetime {
+    finished: Duration,
+    idle: Duration,
+    never_started: Duration,
+    sweep_interval: Duration,
+    max_archived_games: usize,
+}
+
+impl RoomLifetime {
+    fn from_config(config: &Config) -> Self {
+        let rooms = &config.rooms;
+        RoomLifetime {
+            finished: Duration::from_secs(rooms.ttl_finished_secs),
+            idle: Duration::from_secs(rooms.ttl_idle_secs),
+            never_started: Duration::from_secs(rooms.ttl_never_started_secs),
+            sweep_interval: Duration::from_secs(rooms.sweep_interval_secs),
+            max_archived_games: rooms.max_archived_games,
         }
     }
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, ToSchema)]
 struct ReadyToCount {
     black: bool,
     white: bool,
@@ -213,32 +462,29 @@ impl ReadyToCount {
     }
 }
 
+type GuessStones = HashMap<String, (Vec<Vec<usize>>, Vec<Vec<usize>>)>;
+
 lazy_static! {
     static ref GAME_ROOMS: Mutex<HashMap<String, GameRoom>> = Mutex::new(HashMap::new());
-    static ref GUESS_STONES: Mutex<HashMap<String, (Vec<Vec<usize>>, Vec<Vec<usize>>)>> =
-        Mutex::new(HashMap::new());
-    static ref GROUPS_TO_REMOVE: Mutex<HashMap<String, HashSet<Vec<Loc>>>> =
-        Mutex::new(HashMap::new());
-    static ref READY_TO_COUNT: Mutex<HashMap<String, ReadyToCount>> = Mutex::new(HashMap::new());
-}
-
-fn lock_groups_to_remove(
-) -> Result<std::sync::MutexGuard<'static, HashMap<String, HashSet<Vec<Loc>>>>, Error> {
-    GROUPS_TO_REMOVE.lock().map_err(|_| {
-        json_error(
-            "Failed to lock GROUPS_TO_REMOVE",
-            StatusCode::INTERNAL_SERVER_ERROR,
-        )
-    })
-}
-
-fn lock_ready_to_count() -> Result<std::sync::MutexGuard<'static, HashMap<String, ReadyToCount>>> {
-    READY_TO_COUNT.lock().map_err(|_| {
-        json_error(
-            "Failed to lock READY_TO_COUNT",
-            StatusCode::INTERNAL_SERVER_ERROR,
-        )
-    })
+    static ref GUESS_STONES: Mutex<GuessStones> = Mutex::new(HashMap::new());
+    static ref LOBBY: Mutex<Lobby> = Mutex::new(Lobby::new());
+    static ref PRESENCE: PresenceConfig = PresenceConfig::from_config(config::get());
+    // Never locked together with any of the above
+    static ref ACCOUNTS: Mutex<AccountStore> = Mutex::new(
+        AccountStore::load(&config::get().accounts_path)
+            .expect("Failed to load the account store")
+    );
+    // Never locked together with ACCOUNTS, and with GAME_ROOMS only where rate_game runs
+    // outside a runtime. Otherwise it writes it off the request
+    static ref RATINGS: Mutex<RatingStore> = Mutex::new(
+        RatingStore::load(&config::get().ratings_path)
+            .expect("Failed to load the rating store")
+    );
+    // Locked after GAME_ROOMS when both are needed
+    static ref ARCHIVED_GAMES: Mutex<ArchiveStore> = Mutex::new(
+        ArchiveStore::load(&config::get().archive_path)
+            .expect("Failed to load the archive")
+    );
 }
 
 fn color_to_string(color: Color) -> String {
@@ -257,66 +503,311 @@ fn get_playable_dimensions(board: &Board) -> (usize, usize) {
     (total_rows - 2, total_cols - 2)
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct MatchStringPayload {
     match_string: String,
 }
 
-fn lock_rooms() -> Result<std::sync::MutexGuard<'static, HashMap<String, GameRoom>>, Error> {
-    GAME_ROOMS
+fn lock_rooms() -> Result<MutexGuard<'static, HashMap<String, GameRoom>>, Error> {
+    let started = Instant::now();
+    let rooms = GAME_ROOMS
         .lock()
-        .map_err(|_| json_error("Failed to lock rooms", StatusCode::INTERNAL_SERVER_ERROR))
+        .map_err(|_| ApiError::Internal("Failed to lock rooms".to_string()))?;
+    metrics::room_lock_waited(started.elapsed());
+    Ok(rooms)
 }
 
 // Room will be mutable, so we can modify it if it exists
 fn get_room<'a>(
-    rooms: &'a mut std::sync::MutexGuard<'static, HashMap<String, GameRoom>>,
+    rooms: &'a mut MutexGuard<'static, HashMap<String, GameRoom>>,
     match_string: &str,
 ) -> Result<&'a mut GameRoom, Error> {
+    logging::record_room(match_string);
     rooms
         .get_mut(match_string)
-        .ok_or_else(|| json_error("Game room not found", StatusCode::NOT_FOUND))
+        .ok_or_else(|| ApiError::RoomNotFound.into())
 }
 
-fn lock_guess_stones() -> Result<
-    std::sync::MutexGuard<'static, HashMap<String, (Vec<Vec<usize>>, Vec<Vec<usize>>)>>,
-    Error,
-> {
-    GUESS_STONES.lock().map_err(|_| {
-        json_error(
-            "Failed to lock guess stones",
-            StatusCode::INTERNAL_SERVER_ERROR,
-        )
-    })
+fn lock_guess_stones() -> Result<MutexGuard<'static, GuessStones>, Error> {
+    GUESS_STONES
+        .lock()
+        .map_err(|_| ApiError::Internal("Failed to lock guess stones".to_string()).into())
+}
+
+fn lock_archive() -> Result<MutexGuard<'static, ArchiveStore>, Error> {
+    ARCHIVED_GAMES
+        .lock()
+        .map_err(|_| ApiError::Internal("Failed to lock archive".to_string()).into())
+}
+
+// Games in progress can finish while draining, new ones would be cut off
+fn refuse_new_games() -> Result<(), Error> {
+    if shutdown::is_draining() {
+        return Err(ApiError::Unavailable("Server is shutting down".to_string()).into());
+    }
+    Ok(())
+}
+
+// Every way a game can end goes through here, so the sweeper knows when it finished
+// and ratings get updated
+fn finish_game(match_string: &str, room: &mut GameRoom, result: GameResult) {
+    room.board.set_winner(result.clone());
+    let now = SystemTime::now();
+    room.finished_at = Some(now);
+    info!(room = match_string, result = %result, "game finished");
+    metrics::game_finished(&result);
+
+    rate_game(match_string, room, &result, now);
+}
+
+// Only games between two different accounts are rated, and not casual ones where
+// moves can be taken back freely or the opponent is the server's practice bot. The
+// ratings file is written on a blocking thread, the caller holds the rooms lock.
+// Outside a runtime there's nobody to keep waiting, so it's written right away
+fn rate_game(match_string: &str, room: &GameRoom, result: &GameResult, now: SystemTime) {
+    if room.settings.undo_policy == UndoPolicy::Free || room.bot.is_some() {
+        return;
+    }
+
+    let black = room
+        .players
+        .black
+        .as_ref()
+        .and_then(|seat| seat.account_id.clone());
+    let white = room
+        .players
+        .white
+        .as_ref()
+        .and_then(|seat| seat.account_id.clone());
+
+    let (Some(black_id), Some(white_id)) = (black, white) else {
+        return;
+    };
+    if black_id == white_id {
+        return;
+    }
+
+    let pool = RatingPool::for_settings(&room.settings);
+    let game = RatedGame {
+        game_id: room.game_id.clone(),
+        match_string: match_string.to_string(),
+        black_id,
+        white_id,
+        result: result.clone(),
+        rated_at: now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+    };
+
+    match Handle::try_current() {
+        Ok(runtime) => {
+            runtime.spawn_blocking(move || record_rating(&pool, &game));
+        }
+        Err(_) => record_rating(&pool, &game),
+    }
+}
+
+fn record_rating(pool: &RatingPool, game: &RatedGame) {
+    let Ok(mut ratings) = RATINGS.lock() else {
+        error!(
+            room = game.match_string,
+            "failed to lock ratings, game is not rated"
+        );
+        return;
+    };
+
+    if let Err(err) = ratings.record_game(pool, game) {
+        error!(room = game.match_string, %err, "failed to save rating");
+    }
+}
+
+// SGF with the chat of both channels as comments
+fn game_record(room: &GameRoom) -> String {
+    room.board
+        .get_game_sgf_with_comments(&sgf_comments(room.chat.messages()))
+}
+
+// Moves expired rooms from GAME_ROOMS to the archive, returns a copy of the archive
+// to write if it changed
+fn sweep_rooms(lifetime: &RoomLifetime, now: SystemTime) -> Result<Option<ArchiveStore>, Error> {
+    let mut rooms = lock_rooms()?;
+
+    let expired: Vec<(String, ExpiryReason)> = rooms
+        .iter()
+        .filter_map(|(match_string, room)| {
+            room.expiry(lifetime, now)
+                .map(|reason| (match_string.clone(), reason))
+        })
+        .collect();
+
+    if expired.is_empty() {
+        return Ok(None);
+    }
+
+    let mut guess_stones = lock_guess_stones()?;
+    let archived_at = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let mut archived = Vec::new();
+
+    for (match_string, reason) in expired {
+        let Some(room) = rooms.remove(&match_string) else {
+            continue;
+        };
+        guess_stones.remove(&match_string);
+
+        info!(
+            room = match_string,
+            game_id = room.game_id,
+            ?reason,
+            "archiving room"
+        );
+        archived.push(ArchivedGame {
+            game_id: room.game_id.clone(),
+            match_string,
+            result: room.board.get_winner(),
+            black_account: room
+                .players
+                .black
+                .as_ref()
+                .and_then(|seat| seat.account_id.clone()),
+            white_account: room
+                .players
+                .white
+                .as_ref()
+                .and_then(|seat| seat.account_id.clone()),
+            reason,
+            archived_at,
+            sgf: game_record(&room),
+            events: room.board.events.clone(),
+        });
+    }
+
+    // Locked before the rooms are let go so the games are never in neither
+    let mut archive = lock_archive()?;
+    drop(guess_stones);
+    drop(rooms);
+    archive.add(archived, lifetime.max_archived_games);
+    Ok(Some(archive.clone()))
+}
+
+// Writes the sweeper's copy of the archive off the executor. The sweeper waits
+// for it, so an older copy never lands after a newer one
+async fn save_archive(archive: ArchiveStore) {
+    match task::spawn_blocking(move || archive.save()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => error!(%err, "failed to save the archive"),
+        Err(err) => error!(%err, "archive writer failed"),
+    }
+}
+
+// Challenges nobody accepted are dropped like rooms that never started
+fn sweep_lobby(lifetime: &RoomLifetime, now: SystemTime) -> Result<(), Error> {
+    if let Some(cutoff) = now.checked_sub(lifetime.never_started) {
+        lock_lobby()?.remove_created_before(cutoff);
+    }
+    Ok(())
 }
 
-fn schedule_room_cleanup(match_string: String, delay_seconds: u64) {
+fn spawn_room_sweeper(lifetime: RoomLifetime) {
     spawn(async move {
-        sleep(Duration::from_secs(delay_seconds)).await;
-        if let Ok(mut rooms) = lock_rooms() {
-            rooms.remove(&match_string);
+        loop {
+            sleep(lifetime.sweep_interval).await;
+            let now = SystemTime::now();
+            let swept = sweep_rooms(&lifetime, now);
+            if swept.is_err() || sweep_lobby(&lifetime, now).is_err() {
+                warn!("room sweep failed, retrying next interval");
+            }
+            if let Ok(Some(archive)) = swept {
+                save_archive(archive).await;
+            }
+            rate_limit::prune(Instant::now());
         }
     });
 }
 
+// Rooms only live in memory, so live games are written out as SGF before exiting.
+// Accounts and ratings are saved on every change and need no flush
+fn flush_rooms(config: &ShutdownConfig) {
+    let Ok(rooms) = GAME_ROOMS.lock() else {
+        error!("room store poisoned, rooms not flushed");
+        return;
+    };
+    let Some(dir) = &config.flush_dir else {
+        info!(
+            rooms = rooms.len(),
+            "no SHUTDOWN_FLUSH_DIR, rooms not flushed"
+        );
+        return;
+    };
+    if let Err(err) = std::fs::create_dir_all(dir) {
+        error!(dir = %dir.display(), %err, "failed to create flush dir");
+        return;
+    }
+
+    let mut flushed = 0;
+    for (match_string, room) in rooms.iter() {
+        if room.board.game_history.is_empty() {
+            continue;
+        }
+        let path = dir.join(shutdown::file_name(match_string));
+        match storage::save_text(&path, &game_record(room)) {
+            Ok(()) => flushed += 1,
+            Err(err) => error!(room = match_string, %err, "failed to flush room"),
+        }
+    }
+    info!(flushed, dir = %dir.display(), "rooms flushed");
+}
+
 #[handler]
 async fn index() -> Redirect {
     Redirect::moved_permanent("/frontend/index.html")
 }
 
+#[utoipa::path(
+    post,
+    path = "/dimensions",
+    tag = "game",
+    summary = "Playable board size",
+    request_body = MatchStringPayload,
+    responses(
+        (status = 200, body = BoardDimensions),
+        (status = 404, description = "`room_not_found`", body = ErrorBody)
+    )
+)]
 #[handler]
 async fn get_dimensions(payload: Json<MatchStringPayload>) -> Result<Json<BoardDimensions>, Error> {
     let rooms = lock_rooms()?;
 
     let room = rooms
         .get(&payload.match_string)
-        .ok_or_else(|| json_error("Game room not found", StatusCode::NOT_FOUND))?;
+        .ok_or(ApiError::RoomNotFound)?;
 
     let (rows, cols) = get_playable_dimensions(&room.board);
     Ok(Json(BoardDimensions { rows, cols }))
 }
 
 // Convert board state to string format for frontend, excluding sentinel borders
+// The seat's own stones until the board is revealed for counting
+fn seat_board(room: &GameRoom, player: Player) -> Vec<Vec<String>> {
+    let board_state = get_board_state(&room.board);
+    if board_is_revealed(room) {
+        return board_state;
+    }
+
+    let own_color = player.to_string();
+    board_state
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|color| {
+                    if color == own_color {
+                        color
+                    } else {
+                        "empty".to_string()
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
 fn get_board_state(board: &Board) -> Vec<Vec<String>> {
     let (rows, cols) = get_playable_dimensions(board);
     board.fields[1..=rows]
@@ -335,40 +826,62 @@ fn get_board_state(board: &Board) -> Vec<Vec<String>> {
 // 2. Validate move
 // 3. Update game state
 // 4. Return updated game state to frontend
+#[utoipa::path(
+    post,
+    path = "/cell-click",
+    tag = "game",
+    summary = "Play a stone",
+    request_body = CellClick,
+    responses(
+        (status = 200, body = GameState),
+        (status = 401, description = "`unauthorized`: missing session token", body = ErrorBody),
+        (status = 403, description = "`forbidden`: the token doesn't own a seat", body = ErrorBody),
+        (status = 404, description = "`room_not_found`", body = ErrorBody),
+        (status = 409, description = "`not_your_turn` or `wrong_phase`", body = ErrorBody),
+        (status = 422, description = "`illegal_move`", body = ErrorBody)
+    )
+)]
 #[handler]
 async fn cell_click(payload: Json<CellClick>) -> Result<Json<GameState>, Error> {
     let mut rooms = lock_rooms()?;
-    let mut room = get_room(&mut rooms, &payload.match_string)?;
+    let room = get_room(&mut rooms, &payload.match_string)?;
+    let player = authorize_player(room, payload.session_token.as_deref())?;
 
-    let frontend_board = derive_player(room.clone(), payload.session_token.clone());
-    let current_player = room.board.get_current_player();
-    let board_state: Vec<Vec<String>> = get_board_state(&room.board);
+    play_stone(room, player, payload.row, payload.col).map(Json)
+}
 
-    let correct_board = match current_player {
-        Player::Black => "black",
-        Player::White => "white",
+// Shared by every route that can end a turn
+fn check_can_move(room: &GameRoom, player: Player) -> Result<(), Error> {
+    let refusal = if room.board.get_winner().is_some() {
+        ApiError::GameOver
+    } else if room.board.is_counting() {
+        ApiError::WrongPhase("Game is in counting phase".to_string())
+    } else if player != room.board.get_current_player() {
+        ApiError::NotYourTurn
+    } else {
+        return Ok(());
     };
+    metrics::move_refused(refusal.code());
+    Err(refusal.into())
+}
 
-    if correct_board != frontend_board && frontend_board != "main" {
-        println!("NOT YOUR TURN!!!");
-        return Ok(Json(
-            GameState::new(
-                format!("It's not your turn!"),
-                board_state,
-                &room.board,
-                payload.board_generation_number,
-            )
-            .with_stones_in_atari(room.board.stones_in_atari.clone()),
-        ));
-    }
+// Row and col are frontend coordinates, without the sentinel border
+fn play_stone(
+    room: &mut GameRoom,
+    player: Player,
+    row: usize,
+    col: usize,
+) -> Result<GameState, Error> {
+    check_can_move(room, player)?;
+    let board_state: Vec<Vec<String>> = get_board_state(&room.board);
+    let captures_before = total_captures(&room.board);
 
-    // Create move from payload
     let move_attempt = Move {
-        player: current_player,
+        player,
         loc: Loc {
             // Add 1 to skip sentinel border
-            row: payload.row + 1,
-            col: payload.col + 1,
+            row: row + 1,
+            col: col + 1,
         },
     };
 
@@ -376,114 +889,143 @@ async fn cell_click(payload: Json<CellClick>) -> Result<Json<GameState>, Error>
     room.board.play(&move_attempt);
 
     if board_state == get_board_state(&room.board) {
-        println!("ILLEGAL MOVE");
-        return Ok(Json(
-            GameState::new(
-                format!("Move attempted at ({}, {})", payload.row, payload.col),
-                board_state,
-                &room.board,
-                payload.board_generation_number,
-            )
-            .with_stones_in_atari(room.board.stones_in_atari.clone()),
-        ));
+        // Never log the location, players could read the opponent's stones from it
+        info!(
+            move_number = room.board.game_history.len() + 1,
+            "illegal move refused"
+        );
+        metrics::move_refused(ApiError::IllegalMove.code());
+        return Err(ApiError::IllegalMove.into());
     }
 
     let board_state: Vec<Vec<String>> = get_board_state(&room.board);
+    sync_move_times(room);
+    info!(move_number = room.board.game_history.len(), "stone played");
+    metrics::move_played(false);
+    announce(room, player, AnnouncementKind::Played, captures_before);
 
+    // Playing on means the opponent's undo request is declined
+    room.pending_undo = None;
     room.game_generation_number += 1;
 
-    Ok(Json(
-        GameState::new(
-            format!("Move attempted at ({}, {})", payload.row, payload.col),
-            board_state,
-            &room.board,
-            room.game_generation_number,
-        )
-        .with_stones_in_atari(room.board.stones_in_atari.clone()),
-    ))
+    Ok(GameState::new(
+        format!("Move attempted at ({}, {})", row, col),
+        board_state,
+        &room.board,
+        room.game_generation_number,
+    )
+    .with_stones_in_atari(room.board.stones_in_atari.clone())
+    .for_seat(room, player))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct GetGroupPayload {
     row: usize,
     col: usize,
     match_string: String,
-    session_token: String,
+    #[serde(default)]
+    session_token: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct GroupsToRemove {
     selected: HashSet<Vec<Loc>>,
     toggle: Vec<Loc>,
+    version: usize,
 }
 
-// Returns clicked group of stones during counting
+// Toggles clicked group of stones in the room's dead stone selection during counting
+#[utoipa::path(
+    post,
+    path = "/get-group",
+    tag = "counting",
+    summary = "Toggle a group as dead during counting",
+    request_body = GetGroupPayload,
+    responses(
+        (status = 200, body = GroupsToRemove),
+        (status = "4XX", description = "Branch on `code`", body = ErrorBody)
+    )
+)]
 #[handler]
 async fn get_group(payload: Json<GetGroupPayload>) -> Result<Json<GroupsToRemove>, Error> {
     let mut rooms = lock_rooms()?;
-    let mut room = get_room(&mut rooms, &payload.match_string)?;
+    let room = get_room(&mut rooms, &payload.match_string)?;
 
-    let derived_player = derive_player(room.clone(), payload.session_token.clone());
+    authorize_player(room, payload.session_token.as_deref())?;
 
-    if derived_player == "spectator" {
-        return Err(json_error("Not a player!", StatusCode::UNAUTHORIZED));
+    if !room.board.is_counting() || room.board.get_winner().is_some() {
+        return Err(ApiError::WrongPhase("Game is not in counting phase".to_string()).into());
     }
 
-    {
-        let mut other_player_wants_to_count_lock = lock_ready_to_count()?;
-        let mut other_player_wants_to_count = other_player_wants_to_count_lock
-            .entry(payload.match_string.clone())
-            .or_insert_with(|| ReadyToCount::new());
+    let loc = Loc {
+        row: payload.row + 1,
+        col: payload.col + 1,
+    };
 
-        *other_player_wants_to_count = ReadyToCount::new();
+    if !matches!(
+        room.board.fields[loc.row][loc.col],
+        Color::Black | Color::White
+    ) {
+        return Err(ApiError::InvalidRequest("No stones at this point".to_string()).into());
     }
 
-    let group = room.board.group_stones(Loc {
-        row: payload.row + 1,
-        col: payload.col + 1,
-    });
+    let group = room.board.group_stones(loc);
 
-    let mut groups = lock_groups_to_remove()?;
+    // Any change to the selection invalidates both players' acceptance
+    room.counting.toggle(group.clone());
+    room.game_generation_number += 1;
 
-    let mut groups = groups
-        .entry(payload.match_string.clone())
-        .or_insert_with(|| HashSet::new());
+    Ok(Json(room.counting.groups_to_remove(group)))
+}
 
-    if groups.contains(&group) {
-        groups.remove(&group);
+// Get player identity from session_token instead of trusting client
+fn seat_for_token(room: &GameRoom, session_token: &str) -> Option<Player> {
+    let owns_seat = |seat: &Option<PlayerSession>| {
+        seat.as_ref()
+            .is_some_and(|p| p.session_token == session_token)
+    };
+
+    if owns_seat(&room.players.black) {
+        Some(Player::Black)
+    } else if owns_seat(&room.players.white) {
+        Some(Player::White)
     } else {
-        groups.insert(group.clone());
+        None
     }
+}
 
-    let data = GroupsToRemove {
-        selected: groups.clone(),
-        toggle: group,
+fn seat_for_account(room: &GameRoom, account_id: &str) -> Option<Player> {
+    let owns_seat = |seat: &Option<PlayerSession>| {
+        seat.as_ref()
+            .is_some_and(|p| p.account_id.as_deref() == Some(account_id))
     };
 
-    Ok(Json(data))
-}
-
-// Get player identity from session_token instead of trusting client
-fn derive_player(room: GameRoom, session_token: String) -> String {
-    if room
-        .players
-        .black
-        .as_ref()
-        .map_or(false, |b| b.session_token == session_token)
-    {
-        "black".to_string()
-    } else if room
-        .players
-        .white
-        .as_ref()
-        .map_or(false, |w| w.session_token == session_token)
-    {
-        "white".to_string()
+    if owns_seat(&room.players.black) {
+        Some(Player::Black)
+    } else if owns_seat(&room.players.white) {
+        Some(Player::White)
     } else {
-        "spectator".to_string()
+        None
     }
 }
 
+// Shared authorization for every endpoint that changes a room on behalf of a player.
+// Missing token -> 401, token that doesn't own a seat in this room -> 403
+fn authorize_player(room: &GameRoom, session_token: Option<&str>) -> Result<Player, Error> {
+    let session_token = session_token
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| ApiError::Unauthorized("Missing session token".to_string()))?;
+
+    let player = seat_for_token(room, session_token).ok_or_else(|| {
+        Error::from(ApiError::Forbidden(
+            "Session token doesn't own a seat in this game".to_string(),
+        ))
+    })?;
+    logging::record_seat(player);
+    rate_limit::check_session(session_token)?;
+    Ok(player)
+}
+
 fn resolve_spectator_session(
     room: &GameRoom,
     match_string: &str,
@@ -519,253 +1061,510 @@ fn resolve_spectator_session(
     (color, redirect_url, session_token)
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct GetScorePayload {
     match_string: String,
-    session_token: String,
-    groups_to_remove: Vec<Vec<Loc>>,
+    #[serde(default)]
+    session_token: Option<String>,
+    // Version of the dead stone selection the player is accepting
+    #[serde(default)]
+    version: usize,
 }
 
+#[utoipa::path(
+    post,
+    path = "/get-score",
+    tag = "counting",
+    summary = "Accept the dead stones, the game ends once both players did",
+    request_body = GetScorePayload,
+    responses(
+        (status = 200, body = String),
+        (status = "4XX", description = "Branch on `code`", body = ErrorBody)
+    )
+)]
 #[handler]
-async fn get_score(payload: Json<GetScorePayload>) -> Result<Json<String>, Error> {
+async fn get_score(
+    remote_addr: &RemoteAddr,
+    payload: Json<GetScorePayload>,
+) -> Result<Json<String>, Error> {
     let mut rooms = lock_rooms()?;
     let room = get_room(&mut rooms, &payload.match_string)?;
 
-    let derived_player = derive_player(room.clone(), payload.session_token.clone());
+    let seat = payload
+        .session_token
+        .as_deref()
+        .and_then(|token| seat_for_token(room, token));
+
+    let Some(player) = seat else {
+        // Score of the position spectators may see, with stones as they stand
+        refuse_spectator(
+            room,
+            payload.session_token.as_deref(),
+            remote_ip(remote_addr),
+        )?;
+        let moves = spectator_moves(room, SystemTime::now());
+        let score = if moves < room.board.game_history.len() {
+            room.board.position_after(moves).count_score()
+        } else {
+            room.board.count_score()
+        };
+        return Ok(Json(score.to_string()));
+    };
 
-    if derived_player == "spectator" {
-        // Return current score, with stones on the board as they stand atm
-        return Ok(Json(room.board.count_score().to_string()));
+    if let Some(winner) = room.board.get_winner() {
+        return Ok(Json(winner.to_string()));
     }
 
-    let mut ready_to_count = lock_ready_to_count()?;
+    if !room.board.is_counting() {
+        return Err(ApiError::WrongPhase("Game is not in counting phase".to_string()).into());
+    }
 
-    let mut ready_to_count = ready_to_count
-        .entry(payload.match_string.clone())
-        .or_insert_with(|| ReadyToCount::new());
+    if payload.version != room.counting.version {
+        return Err(ApiError::Conflict(
+            "Dead stone selection changed, review it before accepting".to_string(),
+        )
+        .into());
+    }
 
-    match derived_player.as_ref() {
-        "black" => ready_to_count.black = true,
-        "white" => ready_to_count.white = true,
-        _ => unreachable!("Request sent from a spectator handled in the if statement above"),
+    match accept_selection(&payload.match_string, room, player, payload.version) {
+        Some(score) => Ok(Json(score.to_string())),
+        None => Ok(Json("Waiting for other player".to_string())),
     }
+}
 
-    let is_counting_finished = ready_to_count.black && ready_to_count.white;
+// Ends the game with the score once both players accepted the same selection
+fn accept_selection(
+    match_string: &str,
+    room: &mut GameRoom,
+    player: Player,
+    version: usize,
+) -> Option<GameResult> {
+    room.counting.accept(player, version);
+    room.game_generation_number += 1;
 
-    if !is_counting_finished {
-        return Ok(Json("Waiting for other player".to_string()));
+    if !room.counting.is_agreed() {
+        return None;
     }
 
-    remove_dead_groups(&mut room.board, payload.groups_to_remove.clone());
+    let agreed = room.counting.selected.clone();
+    remove_dead_groups(&mut room.board, &agreed);
 
     let score = room.board.count_score();
-    room.board.set_winner(score.clone());
-
-    let match_string = payload.match_string.clone();
-    schedule_room_cleanup(match_string, 60);
-
-    Ok(Json(score.to_string()))
+    finish_game(match_string, room, score.clone());
+    Some(score)
 }
 
-#[derive(Deserialize)]
-struct ResignPayload {
+#[derive(Deserialize, ToSchema)]
+struct ResumePlayPayload {
     match_string: String,
-    player: String,
+    #[serde(default)]
+    session_token: Option<String>,
 }
 
+// Either player can leave counting when they disagree about dead stones. The selection is
+// dropped and play continues with the player who passed first
+#[utoipa::path(
+    post,
+    path = "/resume-play",
+    tag = "counting",
+    summary = "Leave counting and keep playing",
+    request_body = ResumePlayPayload,
+    responses(
+        (status = 200, body = GameState),
+        (status = "4XX", description = "Branch on `code`", body = ErrorBody)
+    )
+)]
 #[handler]
-async fn handle_resignation(payload: Json<ResignPayload>) -> Result<Json<GameState>, Error> {
+async fn resume_play(payload: Json<ResumePlayPayload>) -> Result<Json<GameState>, Error> {
     let mut rooms = lock_rooms()?;
     let room = get_room(&mut rooms, &payload.match_string)?;
+    let player = authorize_player(room, payload.session_token.as_deref())?;
 
-    let loser = match payload.player.as_str() {
-        "black" => Player::Black,
-        _ => Player::White,
-    };
-
-    room.board
-        .set_winner(GameResult::Resignation(loser.opponent()));
+    if !room.board.is_counting() || room.board.get_winner().is_some() {
+        return Err(ApiError::WrongPhase("Game is not in counting phase".to_string()).into());
+    }
 
+    room.board.resume_play();
+    room.counting = CountingState::default();
     room.game_generation_number += 1;
+    let captures = total_captures(&room.board);
+    announce(room, player, AnnouncementKind::ResumedPlay, captures);
 
-    let game_state = GameState::new(
-        format!("Player {:?} resigned. Game over!", loser),
-        get_board_state(&room.board),
-        &room.board,
+    Ok(Json(
+        GameState::new(
+            format!("Player {:?} resumed play", player),
+            get_board_state(&room.board),
+            &room.board,
+            room.game_generation_number,
+        )
+        .with_stones_in_atari(room.board.stones_in_atari.clone())
+        .for_seat(room, player),
+    ))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ResignPayload {
+    match_string: String,
+    #[serde(default)]
+    session_token: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/resign",
+    tag = "game",
+    summary = "Resign the game",
+    request_body = ResignPayload,
+    responses(
+        (status = 200, body = GameState),
+        (status = 401, description = "`unauthorized`: missing session token", body = ErrorBody),
+        (status = 403, description = "`forbidden`: the token doesn't own a seat", body = ErrorBody),
+        (status = 404, description = "`room_not_found`", body = ErrorBody),
+        (status = 409, description = "`game_over`", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn handle_resignation(payload: Json<ResignPayload>) -> Result<Json<GameState>, Error> {
+    let mut rooms = lock_rooms()?;
+    let room = get_room(&mut rooms, &payload.match_string)?;
+
+    let loser = authorize_player(room, payload.session_token.as_deref())?;
+
+    if room.board.get_winner().is_some() {
+        return Err(ApiError::GameOver.into());
+    }
+
+    finish_game(
+        &payload.match_string,
+        room,
+        GameResult::Resignation(loser.opponent()),
+    );
+
+    room.game_generation_number += 1;
+
+    let game_state = GameState::new(
+        format!("Player {:?} resigned. Game over!", loser),
+        get_board_state(&room.board),
+        &room.board,
         room.game_generation_number,
     )
     .with_winner(loser.opponent().to_string());
 
-    // Schedule room cleanup after 1 hour
-    let match_string = payload.match_string.clone();
-    schedule_room_cleanup(match_string, 60);
-
     Ok(Json(game_state))
 }
 
-fn remove_dead_groups(board: &mut Board, groups: Vec<Vec<Loc>>) {
-    for group in groups.iter() {
+// Only ever called with the selection both players accepted
+fn remove_dead_groups(board: &mut Board, agreed: &HashSet<Vec<Loc>>) {
+    for group in agreed.iter() {
         let loc = group[0];
         board.remove_group(loc);
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct PassPayload {
     match_string: String,
-    player: String,
+    #[serde(default)]
+    session_token: Option<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/pass",
+    tag = "game",
+    summary = "Pass, two passes in a row start counting",
+    request_body = PassPayload,
+    responses(
+        (status = 200, body = GameState),
+        (status = 401, description = "`unauthorized`: missing session token", body = ErrorBody),
+        (status = 403, description = "`forbidden`: the token doesn't own a seat", body = ErrorBody),
+        (status = 404, description = "`room_not_found`", body = ErrorBody),
+        (status = 409, description = "`not_your_turn` or `wrong_phase`", body = ErrorBody)
+    )
+)]
 #[handler]
 async fn pass(payload: Json<PassPayload>) -> Result<Json<GameState>, Error> {
     let mut rooms = lock_rooms()?;
     let room = get_room(&mut rooms, &payload.match_string)?;
-    // Getting player here, because of ownership - coudn't borrow it immutably during board.play() (mutable borrow);
-    let player = room.board.get_current_player();
-    let frontend_player = &payload.player;
-
-    if player.to_string() != frontend_player.to_string() && frontend_player != "spectator" {
-        return Ok(Json(
-            GameState::new(
-                "It's not your turn to pass!".to_string(),
-                vec![],
-                &room.board,
-                room.game_generation_number,
-            )
-            .with_stones_in_atari(StonesInAtari { black: 0, white: 0 }),
-        ));
-    }
+    let player = authorize_player(room, payload.session_token.as_deref())?;
+
+    pass_turn(room, player).map(Json)
+}
+
+fn pass_turn(room: &mut GameRoom, player: Player) -> Result<GameState, Error> {
+    check_can_move(room, player)?;
 
     room.board.play(&Move {
         player,
         loc: Loc::pass(),
     });
+    sync_move_times(room);
+    info!(move_number = room.board.game_history.len(), "passed");
+    metrics::move_played(true);
+    let captures = total_captures(&room.board);
+    announce(room, player, AnnouncementKind::Passed, captures);
 
+    room.pending_undo = None;
     room.game_generation_number += 1;
 
-    let game_is_over = room.board.last_two_moves_are_pass();
+    let game_is_over = room.board.is_counting();
+    if game_is_over {
+        room.counting = CountingState::default();
+    }
 
     if !game_is_over {
-        Ok(Json(GameState::new(
-            format!(
-                "Player {:?} passed",
-                room.board.get_current_player().opponent()
-            ),
-            vec![],
+        Ok(GameState::new(
+            format!("Player {:?} passed", player),
+            get_board_state(&room.board),
             &room.board,
             room.game_generation_number,
-        )))
+        )
+        .for_seat(room, player))
     } else {
         let mut game_state = GameState::new(
-            format!("Both players passed. Game over!"),
-            vec![],
+            "Both players passed. Game over!".to_string(),
+            get_board_state(&room.board),
             &room.board,
             room.game_generation_number,
         );
         game_state.current_player = "counting".to_string();
         game_state.counting = true;
-        Ok(Json(game_state))
+        Ok(game_state)
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct UndoPayload {
     match_string: String,
-    player: String,
-    board_generation_number: usize,
+    #[serde(default)]
+    session_token: Option<String>,
+}
+
+fn apply_undo(room: &mut GameRoom) {
+    let undone = room.board.game_history.last().map(|last| last.player);
+    room.board.undo();
+    sync_move_times(room);
+    if let Some(player) = undone {
+        let captures = total_captures(&room.board);
+        announce(room, player, AnnouncementKind::Undone, captures);
+    }
+    room.pending_undo = None;
+    room.game_generation_number += 1;
 }
 
+// Keeps one timestamp per move in the game history
+fn sync_move_times(room: &mut GameRoom) {
+    let moves = room.board.game_history.len();
+    room.move_times.truncate(moves);
+    room.move_times.resize(moves, SystemTime::now());
+}
+
+#[utoipa::path(
+    post,
+    path = "/undo",
+    tag = "game",
+    summary = "Take back your last move, or ask to",
+    request_body = UndoPayload,
+    responses(
+        (status = 200, body = GameState),
+        (status = 401, description = "`unauthorized`: missing session token", body = ErrorBody),
+        (status = 403, description = "`forbidden`: the token doesn't own a seat, or undo is disabled in this room", body = ErrorBody),
+        (status = 404, description = "`room_not_found`", body = ErrorBody),
+        (status = 409, description = "`not_your_turn`: only the player who moved last can undo, `game_over` or `wrong_phase` during counting", body = ErrorBody)
+    )
+)]
 #[handler]
 async fn undo(payload: Json<UndoPayload>) -> Result<Json<GameState>, Error> {
     let mut rooms = lock_rooms()?;
     let room = get_room(&mut rooms, &payload.match_string)?;
+    let frontend_player = authorize_player(room, payload.session_token.as_deref())?;
+    check_can_undo(room)?;
     let player = room.board.get_current_player();
-    let frontend_player = &payload.player;
-    let game_history_len = room.board.game_history.clone().len();
+    let game_history_len = room.board.game_history.len();
 
-    if player.to_string() == frontend_player.to_string() && frontend_player != "spectator"
-        || game_history_len == 0
-    {
-        return Ok(Json(GameState::new(
-            "It's not your turn to undo!".to_string(),
-            vec![],
-            &room.board,
-            payload.board_generation_number,
-        )));
+    // Only the player who made the last move can take it back
+    if player == frontend_player || game_history_len == 0 {
+        return Err(ApiError::NotYourTurn.into());
     }
 
-    room.board.undo();
-    room.game_generation_number += 1;
-
-    // Get the playable board dimensions
-    let (rows, cols) = get_playable_dimensions(&room.board);
-
-    // Convert board state to string format for frontend, excluding sentinel borders
-    let board_state: Vec<Vec<String>> = room.board.fields[1..=rows]
-        .iter()
-        .map(|row| {
-            row[1..=cols]
-                .iter()
-                .map(|&color| color_to_string(color))
-                .collect()
-        })
-        .collect();
+    let message = match room.settings.undo_policy {
+        UndoPolicy::Disabled => {
+            return Err(ApiError::Forbidden("Undo is disabled in this game".to_string()).into())
+        }
+        UndoPolicy::Free => {
+            apply_undo(room);
+            "Undo successful"
+        }
+        UndoPolicy::Ask => {
+            room.pending_undo = Some(UndoRequest {
+                requested_by: frontend_player,
+                move_number: game_history_len,
+            });
+            room.game_generation_number += 1;
+            "Undo requested, waiting for opponent"
+        }
+    };
 
     Ok(Json(
         GameState::new(
-            "Undo successful".to_string(),
-            board_state,
+            message.to_string(),
+            get_board_state(&room.board),
             &room.board,
             room.game_generation_number,
         )
-        .with_stones_in_atari(room.board.stones_in_atari.clone()),
+        .with_stones_in_atari(room.board.stones_in_atari.clone())
+        .with_pending_undo(room.pending_undo)
+        .for_seat(room, frontend_player),
     ))
 }
 
-fn game_data_not_accessible() -> Result<Json<GameState>> {
+#[derive(Deserialize, ToSchema)]
+struct UndoResponsePayload {
+    match_string: String,
+    #[serde(default)]
+    session_token: Option<String>,
+    accept: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/undo-response",
+    tag = "game",
+    summary = "Answer the opponent's undo request",
+    request_body = UndoResponsePayload,
+    responses(
+        (status = 200, body = GameState),
+        (status = 401, description = "`unauthorized`: missing session token", body = ErrorBody),
+        (status = 403, description = "`forbidden`: the token doesn't own a seat", body = ErrorBody),
+        (status = 404, description = "`room_not_found`", body = ErrorBody),
+        (status = 409, description = "`conflict`: no undo request to answer, `game_over` or `wrong_phase` during counting", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn undo_response(payload: Json<UndoResponsePayload>) -> Result<Json<GameState>, Error> {
+    let mut rooms = lock_rooms()?;
+    let room = get_room(&mut rooms, &payload.match_string)?;
+    let responder = authorize_player(room, payload.session_token.as_deref())?;
+    check_can_undo(room)?;
+
+    let request = match room.pending_undo {
+        Some(request) if request.requested_by != responder => request,
+        _ => return Err(ApiError::Conflict("No undo request to answer".to_string()).into()),
+    };
+
+    let message = answer_undo(room, request, payload.accept);
+
     Ok(Json(
         GameState::new(
-            "Game data not accessible".to_string(),
-            vec![],
-            &Board::new(15, 15, 1.5),
-            0,
+            message.to_string(),
+            get_board_state(&room.board),
+            &room.board,
+            room.game_generation_number,
         )
-        .with_rejoin_required(),
+        .with_stones_in_atari(room.board.stones_in_atari.clone())
+        .for_seat(room, responder),
     ))
 }
 
-#[derive(Deserialize)]
+// Board::undo replays the game from scratch, which would bring a finished game back to life
+fn check_can_undo(room: &GameRoom) -> Result<(), Error> {
+    if room.board.get_winner().is_some() {
+        Err(ApiError::GameOver.into())
+    } else if room.board.is_counting() {
+        Err(ApiError::WrongPhase("Game is in counting phase".to_string()).into())
+    } else {
+        Ok(())
+    }
+}
+
+fn answer_undo(room: &mut GameRoom, request: UndoRequest, accept: bool) -> &'static str {
+    if accept && request.move_number == room.board.game_history.len() {
+        apply_undo(room);
+        "Undo accepted"
+    } else {
+        room.pending_undo = None;
+        room.game_generation_number += 1;
+        "Undo declined"
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
 struct ShouldSyncPayload {
     match_string: String,
     player: String,
     frontend_board_generation_number: usize,
+    // Doubles as the seat's heartbeat
+    #[serde(default)]
+    session_token: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct GameInfo {
     should_sync: bool,
     move_number: usize,
     board_generation_number: usize,
     winner: Option<GameResult>,
     rejoin_required: bool,
+    black_presence: Option<SeatPresence>,
+    white_presence: Option<SeatPresence>,
+}
+
+fn seat_presence(seat: &Option<PlayerSession>, now: SystemTime) -> Option<SeatPresence> {
+    seat.as_ref()
+        .map(|seat| SeatPresence::at(seat.last_seen, now, &PRESENCE))
+}
+
+fn mark_present(room: &mut GameRoom, session_token: &str, now: SystemTime) {
+    for seat in [&mut room.players.black, &mut room.players.white]
+        .into_iter()
+        .flatten()
+    {
+        if seat.session_token == session_token {
+            seat.last_seen = now;
+        }
+    }
 }
 
+#[utoipa::path(
+    post,
+    path = "/get-board-interaction-number",
+    tag = "sync",
+    summary = "Poll for changes, doubles as the seat heartbeat",
+    request_body = ShouldSyncPayload,
+    responses(
+        (status = 200, body = GameInfo),
+        (status = 404, description = "`room_not_found`", body = ErrorBody)
+    )
+)]
 #[handler]
 async fn should_sync(payload: Json<ShouldSyncPayload>) -> Result<Json<GameInfo>, Error> {
-    let rooms = lock_rooms()?;
+    let mut rooms = lock_rooms()?;
 
     let room = rooms
-        .get(&payload.match_string)
-        .ok_or_else(|| json_error("Game room not found", StatusCode::NOT_FOUND))?;
+        .get_mut(&payload.match_string)
+        .ok_or(ApiError::RoomNotFound)?;
+
+    let now = SystemTime::now();
+    if let Some(token) = payload.session_token.as_deref() {
+        mark_present(room, token, now);
+    }
 
     let move_number = room.board.game_history.len();
     let board_generation_number = room.game_generation_number;
     let winner = room.board.get_winner();
     let should_sync = board_generation_number > payload.frontend_board_generation_number;
 
+    // The seat is gone, or the token this page holds doesn't own it anymore
+    let lost_seat = |player: Player, seat: &Option<PlayerSession>| {
+        seat.is_none()
+            || payload
+                .session_token
+                .as_deref()
+                .is_some_and(|token| seat_for_token(room, token) != Some(player))
+    };
     let rejoin_required = match payload.player.as_ref() {
-        "black" => room.players.black.is_none(),
-        "white" => room.players.white.is_none(),
+        "black" => lost_seat(Player::Black, &room.players.black),
+        "white" => lost_seat(Player::White, &room.players.white),
         _ => false,
     };
 
@@ -775,113 +1574,385 @@ async fn should_sync(payload: Json<ShouldSyncPayload>) -> Result<Json<GameInfo>,
         board_generation_number,
         winner,
         rejoin_required,
+        black_presence: seat_presence(&room.players.black, now),
+        white_presence: seat_presence(&room.players.white, now),
     }))
 }
 
-#[derive(Deserialize)]
-struct SyncBoardsPayload {
+#[derive(Deserialize, ToSchema)]
+struct ClaimForfeitPayload {
     match_string: String,
-    player: String,
+    #[serde(default)]
+    session_token: Option<String>,
 }
 
+// The player who stayed wins once the opponent's reconnect grace period has run out
+#[utoipa::path(
+    post,
+    path = "/claim-forfeit",
+    tag = "game",
+    summary = "Win against an opponent who abandoned their seat",
+    request_body = ClaimForfeitPayload,
+    responses(
+        (status = 200, body = GameState),
+        (status = 401, description = "`unauthorized`: missing session token", body = ErrorBody),
+        (status = 403, description = "`forbidden`: the token doesn't own a seat", body = ErrorBody),
+        (status = 404, description = "`room_not_found`", body = ErrorBody),
+        (status = 409, description = "`game_over`, or `conflict` while the opponent is within the reconnect grace period", body = ErrorBody)
+    )
+)]
 #[handler]
-async fn sync_boards(payload: Json<SyncBoardsPayload>) -> Result<Json<GameState>, Error> {
+async fn claim_forfeit(payload: Json<ClaimForfeitPayload>) -> Result<Json<GameState>, Error> {
     let mut rooms = lock_rooms()?;
+    let room = get_room(&mut rooms, &payload.match_string)?;
+    let player = authorize_player(room, payload.session_token.as_deref())?;
 
-    let room = rooms
-        .get_mut(&payload.match_string)
-        .ok_or_else(|| json_error("Game room not found", StatusCode::NOT_FOUND))?;
+    if room.board.get_winner().is_some() {
+        return Err(ApiError::GameOver.into());
+    }
 
-    let mut guess_stones = lock_guess_stones()?;
+    let now = SystemTime::now();
+    mark_present(
+        room,
+        payload.session_token.as_deref().unwrap_or_default(),
+        now,
+    );
 
-    let (rows, cols) = get_playable_dimensions(&room.board);
+    let opponent_seat = match player {
+        Player::Black => &room.players.white,
+        Player::White => &room.players.black,
+    };
+    let abandoned = seat_presence(opponent_seat, now)
+        .is_some_and(|presence| presence.status == PresenceStatus::Abandoned);
 
-    let board_state: Vec<Vec<String>> = room.board.fields[1..=rows]
-        .iter()
-        .map(|row| {
-            row[1..=cols]
+    if !abandoned {
+        return Err(ApiError::Conflict(
+            "Opponent is still within the reconnect grace period".to_string(),
+        )
+        .into());
+    }
+
+    finish_game(&payload.match_string, room, GameResult::Forfeit(player));
+    room.game_generation_number += 1;
+
+    let result = GameResult::Forfeit(player);
+    Ok(Json(
+        GameState::new(
+            format!("Player {:?} wins by forfeit", player),
+            get_board_state(&room.board),
+            &room.board,
+            room.game_generation_number,
+        )
+        .with_winner(result.to_string()),
+    ))
+}
+
+fn remote_ip(remote_addr: &RemoteAddr) -> Option<IpAddr> {
+    remote_addr.as_socket_addr().map(|addr| addr.ip())
+}
+
+// Once the board is revealed for counting or the game is over, there's nothing left to protect
+fn board_is_revealed(room: &GameRoom) -> bool {
+    room.board.is_counting() || room.board.get_winner().is_some()
+}
+
+// Players must not get the spectator view of their own live game, neither with their token
+// nor from the address they play from. Loopback is exempt, local setups run every seat
+// from one machine
+fn refuse_spectator(
+    room: &GameRoom,
+    session_token: Option<&str>,
+    ip: Option<IpAddr>,
+) -> Result<(), Error> {
+    if board_is_revealed(room) {
+        return Ok(());
+    }
+
+    if !room.settings.allow_spectators {
+        return Err(ApiError::Forbidden("Spectators are disabled in this game".to_string()).into());
+    }
+
+    let seated_token = session_token.is_some_and(|token| seat_for_token(room, token).is_some());
+    let seated_ip = config::get().spectator_ip_check
+        && ip.is_some_and(|ip| {
+            !ip.is_loopback()
+                && [&room.players.black, &room.players.white]
+                    .iter()
+                    .any(|seat| seat.as_ref().is_some_and(|seat| seat.ip == Some(ip)))
+        });
+
+    if seated_token || seated_ip {
+        return Err(
+            ApiError::Forbidden("Players can't spectate their own game".to_string()).into(),
+        );
+    }
+
+    Ok(())
+}
+
+// How many moves spectators get to see right now
+fn spectator_moves(room: &GameRoom, now: SystemTime) -> usize {
+    let moves = room.board.game_history.len();
+
+    if board_is_revealed(room) {
+        return moves;
+    }
+
+    match room.settings.spectator_delay {
+        SpectatorDelay::None => moves,
+        SpectatorDelay::Moves(delay) => moves.saturating_sub(delay),
+        SpectatorDelay::Seconds(delay) => match now.checked_sub(Duration::from_secs(delay)) {
+            Some(cutoff) => room
+                .move_times
                 .iter()
-                .map(|&color| color_to_string(color))
-                .collect()
-        })
+                .take_while(|played_at| **played_at <= cutoff)
+                .count(),
+            None => 0,
+        },
+    }
+}
+
+// Seats read their own game, anyone else has to pass the spectator rules
+fn record_reader(
+    room: &GameRoom,
+    session_token: Option<&str>,
+    ip: Option<IpAddr>,
+) -> Result<Option<Player>, Error> {
+    let seat = session_token.and_then(|token| seat_for_token(room, token));
+    if seat.is_none() {
+        refuse_spectator(room, session_token, ip)?;
+    }
+    Ok(seat)
+}
+
+// Until the board is revealed, a seat gets its own moves and the opponent's passes, and
+// spectators the delayed position without the undone moves
+fn readable_events(room: &GameRoom, reader: Option<Player>) -> Vec<GameEvent> {
+    if board_is_revealed(room) {
+        return room.board.events.clone();
+    }
+
+    match reader {
+        Some(player) => room
+            .board
+            .events
+            .iter()
+            .filter(|event| match event {
+                GameEvent::Move(mv) => mv.player == player || mv.loc == Loc::pass(),
+                GameEvent::Undo(mv) => mv.player == player,
+                GameEvent::ResumePlay => true,
+            })
+            .copied()
+            .collect(),
+        None => match spectator_moves(room, SystemTime::now()) {
+            moves if moves < room.board.game_history.len() => {
+                room.board.position_after(moves).events
+            }
+            _ => room.board.events.clone(),
+        },
+    }
+}
+
+// A seat gets no record until the board is revealed, it would hold the opponent's moves.
+// Spectators get the delayed position
+fn readable_record(room: &GameRoom, reader: Option<Player>) -> Result<String, Error> {
+    if reader.is_some() && !board_is_revealed(room) {
+        return Err(ApiError::Forbidden(
+            "Players get the game record once the board is revealed".to_string(),
+        )
+        .into());
+    }
+
+    let moves = spectator_moves(room, SystemTime::now());
+    let delayed_board;
+    let board = if moves < room.board.game_history.len() {
+        delayed_board = room.board.position_after(moves);
+        &delayed_board
+    } else {
+        &room.board
+    };
+
+    // Players can't read the spectators until the game is over
+    let messages: Vec<ChatMessage> = room
+        .chat
+        .visible_to(reader.is_none() || room.board.get_winner().is_some())
+        .into_iter()
+        .filter(|message| message.move_number <= moves)
         .collect();
 
+    Ok(board.get_game_sgf_with_comments(&sgf_comments(&messages)))
+}
+
+// The seat comes from the session token, the color the page thinks it plays isn't trusted
+#[derive(Deserialize, ToSchema)]
+struct SyncBoardsPayload {
+    match_string: String,
+    #[serde(default)]
+    session_token: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/sync-boards",
+    tag = "sync",
+    summary = "Current board as seen by the caller",
+    request_body = SyncBoardsPayload,
+    responses(
+        (status = 200, body = GameState),
+        (status = "4XX", description = "Branch on `code`", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn sync_boards(
+    remote_addr: &RemoteAddr,
+    payload: Json<SyncBoardsPayload>,
+) -> Result<Json<GameState>, Error> {
+    let mut rooms = lock_rooms()?;
+    let room = get_room(&mut rooms, &payload.match_string)?;
+
+    room_view(
+        &payload.match_string,
+        room,
+        payload.session_token.as_deref(),
+        remote_ip(remote_addr),
+    )
+    .map(Json)
+}
+
+// The room as the seat owning `session_token` sees it, anyone else is a spectator
+fn room_view(
+    match_string: &str,
+    room: &mut GameRoom,
+    session_token: Option<&str>,
+    ip: Option<IpAddr>,
+) -> Result<GameState, Error> {
+    let seat = session_token.and_then(|token| seat_for_token(room, token));
+    let is_player = seat.is_some();
+    if !is_player {
+        refuse_spectator(room, session_token, ip)?;
+    }
+
+    // Spectators may be kept some moves behind the live game
+    let moves = spectator_moves(room, SystemTime::now());
+    let delayed = !is_player && moves < room.board.game_history.len();
+    let delayed_board;
+    let view_board = if delayed {
+        delayed_board = room.board.position_after(moves);
+        &delayed_board
+    } else {
+        &room.board
+    };
+
+    let mut guess_stones = lock_guess_stones()?;
+
+    let board_state: Vec<Vec<String>> = get_board_state(view_board);
+
     let (black_stones, white_stones) = guess_stones
-        .entry(payload.match_string.clone())
+        .entry(match_string.to_string())
         .or_insert((Vec::new(), Vec::new()));
 
+    // Players can't read the spectators until the game is over, spectators can't read past
+    // their delay
+    let mut chat = room
+        .chat
+        .visible_to(!is_player || room.board.get_winner().is_some());
+    if !is_player {
+        chat.retain(|message| message.move_number <= moves);
+    }
+
     let game_state = match room.board.get_winner() {
         Some(winner) => GameState::new(
-            format!("Game over! Winner: {}", winner.to_string()),
+            format!("Game over! Winner: {}", winner),
             board_state.clone(),
             &room.board,
             0,
         )
-        .with_winner(winner.to_string()),
+        .with_winner(winner.to_string())
+        .with_chat(chat),
         // TODO: analyze this arm for it's possibility in producing bugs with adding/removing Guess Stones tests
         None => {
-            let board_int_num = match payload.player.as_ref() {
-                "black" => {
-                    if let Some(_player) = &room.players.black {
-                        room.game_generation_number
-                    } else {
-                        return game_data_not_accessible();
-                    }
-                }
-                "white" => {
-                    if let Some(_player) = &room.players.white {
-                        room.game_generation_number
-                    } else {
-                        return game_data_not_accessible();
-                    }
-                }
-                _ => 0,
-            };
-
-            let mut groups_to_remove = lock_groups_to_remove()?;
-
-            let mut groups_to_remove = groups_to_remove
-                .entry(payload.match_string.clone())
-                .or_insert_with(|| HashSet::new());
-
-            let groups = GroupsToRemove {
-                selected: groups_to_remove.clone(),
-                toggle: vec![Loc::from_string("100, 100").unwrap()],
+            let board_int_num = if is_player {
+                room.game_generation_number
+            } else {
+                0
             };
 
-            let mut ready_to_count = lock_ready_to_count()?;
-            let mut ready_to_count = ready_to_count
-                .entry(payload.match_string.clone())
-                .or_insert_with(|| ReadyToCount::new());
+            let groups = room
+                .counting
+                .groups_to_remove(vec![Loc::from_string("100, 100").unwrap()]);
 
-            GameState::new(
+            let game_state = GameState::new(
                 "Current board state sent".to_string(),
                 board_state.clone(),
-                &room.board,
+                view_board,
                 board_int_num,
             )
-            .with_guess_stones(black_stones.clone(), white_stones.clone())
-            .with_stones_in_atari(room.board.stones_in_atari.clone())
-            .with_groups_selected_during_counting(groups)
-            .with_ready_to_count(ready_to_count.clone())
+            .with_stones_in_atari(view_board.stones_in_atari.clone())
+            .with_chat(chat);
+
+            // The rest belongs to the live game, it would show how far ahead that is
+            if delayed {
+                game_state
+            } else {
+                game_state
+                    .with_guess_stones(black_stones.clone(), white_stones.clone())
+                    .with_groups_selected_during_counting(groups)
+                    .with_ready_to_count(room.counting.ready_to_count())
+                    .with_pending_undo(room.pending_undo)
+            }
         }
     };
 
-    Ok(Json(game_state))
+    Ok(match seat {
+        Some(player) => game_state.for_seat(room, player),
+        None => game_state,
+    })
 }
 
+#[utoipa::path(
+    post,
+    path = "/join-game",
+    tag = "game",
+    summary = "Create a room or take a seat in one",
+    request_body = JoinGameRequest,
+    responses(
+        (status = 200, body = JoinGameResponse),
+        (status = "4XX", description = "Branch on `code`", body = ErrorBody)
+    )
+)]
 #[handler]
-async fn join_game(payload: Json<JoinGameRequest>) -> Result<Json<JoinGameResponse>, Error> {
+async fn join_game(
+    remote_addr: &RemoteAddr,
+    payload: Json<JoinGameRequest>,
+) -> Result<Json<JoinGameResponse>, Error> {
+    let ip = remote_ip(remote_addr);
+    rate_limit::check_match_string(&payload.match_string)?;
+    let account_id = account_for_login(payload.login_token.as_deref())?.map(|account| account.id);
     let mut rooms = lock_rooms()?;
 
+    let creates_room = !rooms.contains_key(&payload.match_string);
+    if creates_room {
+        refuse_new_games()?;
+        rate_limit::check_new_room(ip, rooms.len())?;
+        payload
+            .settings
+            .validate()
+            .map_err(ApiError::InvalidRequest)?;
+    }
+
     let room = rooms
         .entry(payload.match_string.clone())
-        .or_insert_with(GameRoom::new);
+        .or_insert_with(|| GameRoom::new(payload.settings.clone()));
 
     let (spectator_color, spectator_redirect_url, spectator_session_token) =
-        resolve_spectator_session(&room, &payload.match_string, &payload.session_token);
+        resolve_spectator_session(room, &payload.match_string, &payload.session_token);
 
     if payload.is_spectator {
+        // Seat owners are sent back to their seat instead
+        if spectator_color == "spectator" {
+            refuse_spectator(room, None, ip)?;
+        }
+
         return Ok(Json(JoinGameResponse {
             color: spectator_color,
             redirect_url: spectator_redirect_url,
@@ -889,6 +1960,29 @@ async fn join_game(payload: Json<JoinGameRequest>) -> Result<Json<JoinGameRespon
         }));
     }
 
+    // A logged in player gets their seat back even without the session token
+    if let Some(player) = account_id
+        .as_deref()
+        .and_then(|account_id| seat_for_account(room, account_id))
+    {
+        let seat = match player {
+            Player::Black => room.players.black.as_mut(),
+            Player::White => room.players.white.as_mut(),
+        };
+        let session_token = seat
+            .map(|seat| {
+                seat.ip = ip;
+                seat.session_token.clone()
+            })
+            .unwrap_or_default();
+
+        return Ok(Json(seated_join_response(
+            player,
+            &payload.match_string,
+            &session_token,
+        )));
+    }
+
     let (color, url, session_token) = match (&room.players.black, &room.players.white) {
         (None, None) => {
             // First player - random color
@@ -896,14 +1990,18 @@ async fn join_game(payload: Json<JoinGameRequest>) -> Result<Json<JoinGameRespon
             let new_token = uuid::Uuid::new_v4().to_string();
 
             if is_black {
-                room.players.black = Some(PlayerSession {
-                    session_token: new_token.clone(),
-                });
+                room.players.black = Some(PlayerSession::new(
+                    new_token.clone(),
+                    account_id.clone(),
+                    ip,
+                ));
                 ("black", "/frontend/black.html", new_token)
             } else {
-                room.players.white = Some(PlayerSession {
-                    session_token: new_token.clone(),
-                });
+                room.players.white = Some(PlayerSession::new(
+                    new_token.clone(),
+                    account_id.clone(),
+                    ip,
+                ));
                 ("white", "/frontend/white.html", new_token)
             }
         }
@@ -917,18 +2015,22 @@ async fn join_game(payload: Json<JoinGameRequest>) -> Result<Json<JoinGameRespon
                 } else {
                     // Otherwise seat a new player
                     let new_token = uuid::Uuid::new_v4().to_string();
-                    room.players.white = Some(PlayerSession {
-                        session_token: new_token.clone(),
-                    });
+                    room.players.white = Some(PlayerSession::new(
+                        new_token.clone(),
+                        account_id.clone(),
+                        ip,
+                    ));
                     ("white", "/frontend/white.html", new_token)
                 }
             // If there isn't a session token
             } else {
                 // Seat a new player
                 let new_token = uuid::Uuid::new_v4().to_string();
-                room.players.white = Some(PlayerSession {
-                    session_token: new_token.clone(),
-                });
+                room.players.white = Some(PlayerSession::new(
+                    new_token.clone(),
+                    account_id.clone(),
+                    ip,
+                ));
                 ("white", "/frontend/white.html", new_token)
             }
         }
@@ -942,18 +2044,22 @@ async fn join_game(payload: Json<JoinGameRequest>) -> Result<Json<JoinGameRespon
                 } else {
                     // Otherwise seat a new player
                     let new_token = uuid::Uuid::new_v4().to_string();
-                    room.players.black = Some(PlayerSession {
-                        session_token: new_token.clone(),
-                    });
+                    room.players.black = Some(PlayerSession::new(
+                        new_token.clone(),
+                        account_id.clone(),
+                        ip,
+                    ));
                     ("black", "/frontend/black.html", new_token)
                 }
             // If there isn't a session token
             } else {
                 // Seat a new player
                 let new_token = uuid::Uuid::new_v4().to_string();
-                room.players.black = Some(PlayerSession {
-                    session_token: new_token.clone(),
-                });
+                room.players.black = Some(PlayerSession::new(
+                    new_token.clone(),
+                    account_id.clone(),
+                    ip,
+                ));
                 ("black", "/frontend/black.html", new_token)
             }
         }
@@ -973,6 +2079,11 @@ async fn join_game(payload: Json<JoinGameRequest>) -> Result<Json<JoinGameRespon
         }
     };
 
+    if let Some(kind) = payload.bot.filter(|_| creates_room) {
+        let bot_token = seat_bot(room, kind);
+        spawn_bot(payload.match_string.clone(), bot_token);
+    }
+
     // Return the response with session token
     Ok(Json(JoinGameResponse {
         color: color.to_string(),
@@ -984,30 +2095,917 @@ async fn join_game(payload: Json<JoinGameRequest>) -> Result<Json<JoinGameRespon
     }))
 }
 
-#[derive(Deserialize)]
-struct ValidateSpectatorPayload {
-    match_string: String,
-    session_token: Option<String>,
+fn seated_join_response(
+    player: Player,
+    match_string: &str,
+    session_token: &str,
+) -> JoinGameResponse {
+    JoinGameResponse {
+        color: player.to_string(),
+        redirect_url: format!(
+            "/frontend/{}.html?match={}&token={}",
+            player, match_string, session_token
+        ),
+        session_token: session_token.to_string(),
+    }
 }
 
-// ANTI-CHEATING:
-// Handler used by `games.html` (or any caller) to check whether a client
-// opening a game as a spectator actually owns a seat in that game.
-// Returns same shape as `JoinGameResponse` so the frontend can decide where to
-// navigate (`main.html` for spectators or the player's specific board page).
-#[handler]
+// How often a bot looks at its room, and so roughly how fast it answers
+const BOT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// The bot takes whichever seat the creator didn't. Returns its session token
+fn seat_bot(room: &mut GameRoom, kind: BotKind) -> String {
+    let session_token = uuid::Uuid::new_v4().to_string();
+    let seat = Some(PlayerSession::new(session_token.clone(), None, None));
+    let player = if room.players.black.is_none() {
+        room.players.black = seat;
+        Player::Black
+    } else {
+        room.players.white = seat;
+        Player::White
+    };
+
+    room.bot = Some(BotSeat {
+        player,
+        brain: Bot::new(kind, MctsConfig::from_config(config::get())),
+        session_token: session_token.clone(),
+        refused: HashSet::new(),
+        refused_at_move: 0,
+    });
+    session_token
+}
+
+enum BotStep {
+    // Nothing to do until the human acts
+    Wait,
+    Move {
+        view: SeatView,
+        brain: Box<Bot>,
+        generation: usize,
+    },
+    // The game is over, or the room is gone
+    Stop,
+}
+
+// Plays the bot's seat until the game ends. Thinking happens without the room lock,
+// a move is only applied if nothing changed in the room meanwhile
+fn spawn_bot(match_string: String, session_token: String) {
+    // Outlives the request that seated it
+    let span = tracing::info_span!(parent: None, "bot", room = %match_string);
+    spawn(
+        async move {
+            let mut retry_now = false;
+            loop {
+                if !retry_now {
+                    sleep(BOT_POLL_INTERVAL).await;
+                }
+                let (view, brain, generation) = match bot_step(&match_string, &session_token) {
+                    Ok(BotStep::Move {
+                        view,
+                        brain,
+                        generation,
+                    }) => (view, brain, generation),
+                    Ok(BotStep::Wait) => {
+                        retry_now = false;
+                        continue;
+                    }
+                    Ok(BotStep::Stop) => break,
+                    Err(_) => {
+                        warn!("bot can't reach its room, leaving");
+                        break;
+                    }
+                };
+
+                let thinking =
+                    task::spawn_blocking(move || brain.choose_move(&view, &mut rand::thread_rng()));
+                let Ok(choice) = thinking.await else {
+                    error!("bot panicked while thinking, leaving");
+                    break;
+                };
+                match play_bot_move(&match_string, &session_token, generation, choice) {
+                    Ok(refused) => retry_now = refused,
+                    Err(_) => {
+                        warn!("bot can't reach its room, leaving");
+                        break;
+                    }
+                }
+            }
+            debug!("bot left");
+        }
+        .instrument(span),
+    );
+}
+
+// Everything the bot does that needs no thinking, and the seat view when it's its turn
+fn bot_step(match_string: &str, session_token: &str) -> Result<BotStep, Error> {
+    let mut rooms = lock_rooms()?;
+    let Some(room) = rooms.get_mut(match_string) else {
+        return Ok(BotStep::Stop);
+    };
+    // A room recreated under the same name isn't this bot's anymore
+    let Some(seat) = room
+        .bot
+        .clone()
+        .filter(|seat| seat.session_token == session_token)
+    else {
+        return Ok(BotStep::Stop);
+    };
+    if room.board.get_winner().is_some() {
+        return Ok(BotStep::Stop);
+    }
+    mark_present(room, session_token, SystemTime::now());
+
+    // A practice partner lets the human take moves back
+    if let Some(request) = room
+        .pending_undo
+        .filter(|request| request.requested_by != seat.player)
+    {
+        answer_undo(room, request, true);
+        return Ok(BotStep::Wait);
+    }
+
+    // The bot can't judge dead stones. Until the human's selection is one it can live
+    // with, it doesn't accept and the human can change it or resume play
+    if room.board.is_counting() {
+        let ready = room.counting.ready_to_count();
+        let accepted = match seat.player {
+            Player::Black => ready.black,
+            Player::White => ready.white,
+        };
+        if !accepted && bot_accepts_selection(&room.board, &room.counting.selected, seat.player) {
+            let version = room.counting.version;
+            accept_selection(match_string, room, seat.player, version);
+        }
+        return Ok(BotStep::Wait);
+    }
+
+    if room.board.get_current_player() != seat.player {
+        return Ok(BotStep::Wait);
+    }
+
+    // Refusals only tell something about the position they were made in
+    let moves = room.board.game_history.len();
+    let refused = if seat.refused_at_move == moves {
+        seat.refused
+    } else {
+        HashSet::new()
+    };
+    let view = SeatView::new(&room.board, seat.player, refused);
+
+    let Some(seat) = room.bot.as_mut() else {
+        return Ok(BotStep::Stop);
+    };
+    seat.brain.observe(&view);
+    Ok(BotStep::Move {
+        view,
+        brain: Box::new(seat.brain.clone()),
+        generation: room.game_generation_number,
+    })
+}
+
+// The human's groups are theirs to give up, the bot's own only count as dead when
+// they're down to their last liberty. The whole board is revealed while counting
+fn bot_accepts_selection(board: &Board, selected: &HashSet<Vec<Loc>>, bot: Player) -> bool {
+    let own_color = match bot {
+        Player::Black => Color::Black,
+        Player::White => Color::White,
+    };
+    selected.iter().all(|group| {
+        let loc = group[0];
+        board.fields[loc.row][loc.col] != own_color || board.count_liberties(loc) <= 1
+    })
+}
+
+// True when the referee refused the point and the bot should try again
+fn play_bot_move(
+    match_string: &str,
+    session_token: &str,
+    generation: usize,
+    choice: BotMove,
+) -> Result<bool, Error> {
+    let mut rooms = lock_rooms()?;
+    let Some(room) = rooms.get_mut(match_string) else {
+        return Ok(false);
+    };
+    let Some(player) = seat_for_token(room, session_token) else {
+        return Ok(false);
+    };
+    // The human undid, resigned or the room changed otherwise while the bot was thinking
+    if room.game_generation_number != generation {
+        return Ok(false);
+    }
+
+    let result = match choice {
+        BotMove::Pass => pass_turn(room, player),
+        BotMove::Play(point) => play_stone(room, player, point.row, point.col),
+    };
+    match result {
+        Ok(_) => Ok(false),
+        Err(err) if matches!(err.downcast_ref::<ApiError>(), Some(ApiError::IllegalMove)) => {
+            let BotMove::Play(point) = choice else {
+                return Err(err);
+            };
+            let moves = room.board.game_history.len();
+            if let Some(seat) = room.bot.as_mut() {
+                if seat.refused_at_move != moves {
+                    seat.refused.clear();
+                    seat.refused_at_move = moves;
+                }
+                seat.refused.insert(point);
+            }
+            Ok(true)
+        }
+        // Not the bot's turn after all, it looks again next poll
+        Err(_) => Ok(false),
+    }
+}
+
+fn lock_lobby() -> Result<MutexGuard<'static, Lobby>, Error> {
+    LOBBY
+        .lock()
+        .map_err(|_| ApiError::Internal("Failed to lock lobby".to_string()).into())
+}
+
+fn lobby_error(err: LobbyError) -> Error {
+    match err {
+        LobbyError::NotFound => ApiError::NotFound("Challenge not found".to_string()).into(),
+        LobbyError::AlreadyAccepted => {
+            ApiError::Conflict("Challenge was already accepted".to_string()).into()
+        }
+        LobbyError::NotOwner => ApiError::Forbidden("Not your challenge".to_string()).into(),
+        LobbyError::OwnChallenge => {
+            ApiError::Conflict("Can't accept your own challenge".to_string()).into()
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct LobbyResponse {
+    challenge_id: String,
+    // Only sent to whoever posted the challenge, it's their future session token
+    owner_token: Option<String>,
+    // Set once the challenge is accepted and the room is ready
+    join: Option<JoinGameResponse>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct PostChallengePayload {
+    #[serde(default = "default_room_settings")]
+    settings: RoomSettings,
+    #[serde(default)]
+    login_token: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ChallengePayload {
+    challenge_id: String,
+    #[serde(default)]
+    owner_token: Option<String>,
+    #[serde(default)]
+    login_token: Option<String>,
+}
+
+// Creates a room for an accepted challenge with both seats taken, colors are random.
+// Returns the acceptor's seat
+fn seat_challenge(
+    lobby: &mut Lobby,
+    challenge_id: &str,
+    acceptor_account: Option<String>,
+    acceptor_ip: Option<IpAddr>,
+) -> Result<JoinGameResponse, Error> {
+    refuse_new_games()?;
+    let mut rooms = lock_rooms()?;
+    rate_limit::check_new_room(acceptor_ip, rooms.len())?;
+
+    let match_string = uuid::Uuid::new_v4().to_string();
+    let challenge: Challenge = lobby
+        .accept(challenge_id, &match_string, acceptor_account.as_deref())
+        .map_err(lobby_error)?;
+    let owner_account = challenge.owner.as_ref().map(|owner| owner.id.clone());
+
+    let acceptor_token = uuid::Uuid::new_v4().to_string();
+    let acceptor = if random::<bool>() {
+        Player::Black
+    } else {
+        Player::White
+    };
+
+    let mut room = GameRoom::new(challenge.settings.clone());
+    let acceptor_seat = PlayerSession::new(acceptor_token.clone(), acceptor_account, acceptor_ip);
+    // The poster's address is filled in when they pick up the seat through challenge_status
+    let owner_seat = PlayerSession::new(challenge.owner_token.clone(), owner_account, None);
+    let (black, white) = match acceptor {
+        Player::Black => (acceptor_seat, owner_seat),
+        Player::White => (owner_seat, acceptor_seat),
+    };
+    room.players.black = Some(black);
+    room.players.white = Some(white);
+    rooms.insert(match_string.clone(), room);
+
+    Ok(seated_join_response(
+        acceptor,
+        &match_string,
+        &acceptor_token,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/post-challenge",
+    tag = "lobby",
+    summary = "Post an open challenge",
+    request_body = PostChallengePayload,
+    responses(
+        (status = 200, body = LobbyResponse),
+        (status = "4XX", description = "Branch on `code`", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn post_challenge(payload: Json<PostChallengePayload>) -> Result<Json<LobbyResponse>, Error> {
+    refuse_new_games()?;
+    payload
+        .settings
+        .validate()
+        .map_err(ApiError::InvalidRequest)?;
+
+    let owner = account_for_login(payload.login_token.as_deref())?.map(|account| account.public());
+    let challenge = lock_lobby()?.post(payload.settings.clone(), false, owner);
+
+    Ok(Json(LobbyResponse {
+        challenge_id: challenge.id,
+        owner_token: Some(challenge.owner_token),
+        join: None,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/get-open-challenges",
+    tag = "lobby",
+    summary = "Challenges waiting for an opponent",
+    responses(
+        (status = 200, body = Vec<Challenge>)
+    )
+)]
+#[handler]
+fn get_open_challenges() -> Result<Json<Vec<Challenge>>, Error> {
+    Ok(Json(lock_lobby()?.open_challenges()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/accept-challenge",
+    tag = "lobby",
+    summary = "Accept a challenge and take a seat",
+    request_body = ChallengePayload,
+    responses(
+        (status = 200, body = LobbyResponse),
+        (status = "4XX", description = "Branch on `code`", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn accept_challenge(
+    remote_addr: &RemoteAddr,
+    payload: Json<ChallengePayload>,
+) -> Result<Json<LobbyResponse>, Error> {
+    let account_id = account_for_login(payload.login_token.as_deref())?.map(|account| account.id);
+    let mut lobby = lock_lobby()?;
+    let join = seat_challenge(
+        &mut lobby,
+        &payload.challenge_id,
+        account_id,
+        remote_ip(remote_addr),
+    )?;
+
+    Ok(Json(LobbyResponse {
+        challenge_id: payload.challenge_id.clone(),
+        owner_token: None,
+        join: Some(join),
+    }))
+}
+
+// Polled by whoever posted the challenge until someone accepts it
+#[utoipa::path(
+    post,
+    path = "/challenge-status",
+    tag = "lobby",
+    summary = "Poll your challenge, returns the seat once accepted",
+    request_body = ChallengePayload,
+    responses(
+        (status = 200, body = LobbyResponse),
+        (status = "4XX", description = "Branch on `code`", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn challenge_status(
+    remote_addr: &RemoteAddr,
+    payload: Json<ChallengePayload>,
+) -> Result<Json<LobbyResponse>, Error> {
+    let owner_token = payload.owner_token.clone().unwrap_or_default();
+    let lobby = lock_lobby()?;
+    let challenge = lobby
+        .status(&payload.challenge_id, &owner_token)
+        .map_err(lobby_error)?;
+
+    let join = match &challenge.match_string {
+        Some(match_string) => {
+            let mut rooms = lock_rooms()?;
+            rooms.get_mut(match_string).and_then(|room| {
+                let player = seat_for_token(room, &owner_token)?;
+                let seat = match player {
+                    Player::Black => room.players.black.as_mut(),
+                    Player::White => room.players.white.as_mut(),
+                };
+                if let Some(seat) = seat {
+                    seat.ip = remote_ip(remote_addr);
+                }
+                Some(seated_join_response(player, match_string, &owner_token))
+            })
+        }
+        None => None,
+    };
+
+    Ok(Json(LobbyResponse {
+        challenge_id: challenge.id.clone(),
+        owner_token: None,
+        join,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/cancel-challenge",
+    tag = "lobby",
+    summary = "Withdraw your challenge",
+    request_body = ChallengePayload,
+    responses(
+        (status = 200, body = String),
+        (status = "4XX", description = "Branch on `code`", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn cancel_challenge(payload: Json<ChallengePayload>) -> Result<Json<String>, Error> {
+    let owner_token = payload.owner_token.clone().unwrap_or_default();
+    lock_lobby()?
+        .cancel(&payload.challenge_id, &owner_token)
+        .map_err(lobby_error)?;
+
+    Ok(Json("Challenge cancelled".to_string()))
+}
+
+// Joins the oldest open challenge with the same settings, or waits in the queue as a new one
+#[utoipa::path(
+    post,
+    path = "/quick-match",
+    tag = "lobby",
+    summary = "Accept a compatible challenge or post one",
+    request_body = PostChallengePayload,
+    responses(
+        (status = 200, body = LobbyResponse),
+        (status = "4XX", description = "Branch on `code`", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn quick_match(
+    remote_addr: &RemoteAddr,
+    payload: Json<PostChallengePayload>,
+) -> Result<Json<LobbyResponse>, Error> {
+    refuse_new_games()?;
+    payload
+        .settings
+        .validate()
+        .map_err(ApiError::InvalidRequest)?;
+
+    let account = account_for_login(payload.login_token.as_deref())?;
+    let account_id = account.as_ref().map(|account| account.id.clone());
+    let mut lobby = lock_lobby()?;
+
+    if let Some(challenge_id) = lobby.find_compatible(&payload.settings, account_id.as_deref()) {
+        let join = seat_challenge(
+            &mut lobby,
+            &challenge_id,
+            account_id,
+            remote_ip(remote_addr),
+        )?;
+        return Ok(Json(LobbyResponse {
+            challenge_id,
+            owner_token: None,
+            join: Some(join),
+        }));
+    }
+
+    let owner = account.map(|account| account.public());
+    let challenge = lobby.post(payload.settings.clone(), true, owner);
+
+    Ok(Json(LobbyResponse {
+        challenge_id: challenge.id,
+        owner_token: Some(challenge.owner_token),
+        join: None,
+    }))
+}
+
+fn lock_accounts() -> Result<MutexGuard<'static, AccountStore>, Error> {
+    ACCOUNTS
+        .lock()
+        .map_err(|_| ApiError::Internal("Failed to lock account store".to_string()).into())
+}
+
+fn account_error(err: AccountError) -> Error {
+    match err {
+        AccountError::InvalidName => ApiError::InvalidRequest(format!(
+            "Display name must be 1 to {} characters",
+            MAX_DISPLAY_NAME_LENGTH
+        ))
+        .into(),
+        AccountError::NameTaken => ApiError::Conflict("Display name is taken".to_string()).into(),
+        AccountError::InvalidCredentials => {
+            ApiError::Unauthorized("Wrong display name or password".to_string()).into()
+        }
+        AccountError::UnknownToken => {
+            ApiError::Unauthorized("Unknown login token".to_string()).into()
+        }
+        AccountError::Storage(msg) => {
+            error!(%msg, "account store error");
+            ApiError::Internal("Failed to save account store".to_string()).into()
+        }
+    }
+}
+
+// Playing as a guest is still allowed, but a login token that was sent has to be valid
+fn account_for_login(login_token: Option<&str>) -> Result<Option<Account>, Error> {
+    match login_token {
+        None => Ok(None),
+        Some(login_token) => lock_accounts()?
+            .account_for_token(login_token)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| account_error(AccountError::UnknownToken)),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct RegisterPayload {
+    display_name: String,
+    #[serde(default)]
+    password: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct LoginPayload {
+    display_name: String,
+    password: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct LoginTokenPayload {
+    login_token: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct LoginResponse {
+    account: PublicAccount,
+    login_token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/register",
+    tag = "accounts",
+    summary = "Create an account",
+    request_body = RegisterPayload,
+    responses(
+        (status = 200, body = LoginResponse),
+        (status = "4XX", description = "Branch on `code`", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn register(payload: Json<RegisterPayload>) -> Result<Json<LoginResponse>, Error> {
+    let password_hash = match payload
+        .password
+        .clone()
+        .filter(|password| !password.is_empty())
+    {
+        Some(password) => Some(
+            task::spawn_blocking(move || hash_password(&password))
+                .await
+                .map_err(|_| ApiError::Internal("Failed to hash the password".to_string()))?
+                .map_err(account_error)?,
+        ),
+        None => None,
+    };
+
+    let (account, login_token) = lock_accounts()?
+        .register(&payload.display_name, password_hash)
+        .map_err(account_error)?;
+
+    Ok(Json(LoginResponse {
+        account: account.public(),
+        login_token,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "accounts",
+    summary = "Get a login token",
+    request_body = LoginPayload,
+    responses(
+        (status = 200, body = LoginResponse),
+        (status = 401, description = "`unauthorized`: wrong display name or password", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn login(payload: Json<LoginPayload>) -> Result<Json<LoginResponse>, Error> {
+    let credentials = lock_accounts()?.credentials(&payload.display_name);
+    let Some((account_id, password_hash)) = credentials else {
+        return Err(account_error(AccountError::InvalidCredentials));
+    };
+
+    let password = payload.password.clone();
+    let verified = task::spawn_blocking(move || verify_password(&password, &password_hash))
+        .await
+        .map_err(|_| ApiError::Internal("Failed to check the password".to_string()))?;
+    if !verified {
+        return Err(account_error(AccountError::InvalidCredentials));
+    }
+
+    let (account, login_token) = lock_accounts()?.login(&account_id).map_err(account_error)?;
+
+    Ok(Json(LoginResponse {
+        account: account.public(),
+        login_token,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/logout",
+    tag = "accounts",
+    summary = "Revoke a login token",
+    request_body = LoginTokenPayload,
+    responses(
+        (status = 200, body = String),
+        (status = 401, description = "`unauthorized`: unknown login token", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn logout(payload: Json<LoginTokenPayload>) -> Result<Json<String>, Error> {
+    lock_accounts()?
+        .logout(&payload.login_token)
+        .map_err(account_error)?;
+
+    Ok(Json("Logged out".to_string()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/get-account",
+    tag = "accounts",
+    summary = "Account behind a login token",
+    request_body = LoginTokenPayload,
+    responses(
+        (status = 200, body = PublicAccount),
+        (status = 401, description = "`unauthorized`: unknown login token", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn get_account(payload: Json<LoginTokenPayload>) -> Result<Json<PublicAccount>, Error> {
+    lock_accounts()?
+        .account_for_token(&payload.login_token)
+        .map(|account| Json(account.public()))
+        .ok_or_else(|| account_error(AccountError::UnknownToken))
+}
+
+#[derive(Serialize, ToSchema)]
+struct MyGame {
+    game_id: String,
+    match_string: String,
+    color: String,
+    opponent: Option<PublicAccount>,
+    result: Option<GameResult>,
+    archived: bool,
+}
+
+// Games in progress and archived games where the account owns a seat
+#[utoipa::path(
+    post,
+    path = "/get-my-games",
+    tag = "accounts",
+    summary = "Live and archived games of an account",
+    request_body = LoginTokenPayload,
+    responses(
+        (status = 200, body = Vec<MyGame>),
+        (status = 401, description = "`unauthorized`: unknown login token", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn get_my_games(payload: Json<LoginTokenPayload>) -> Result<Json<Vec<MyGame>>, Error> {
+    let account = lock_accounts()?
+        .account_for_token(&payload.login_token)
+        .cloned()
+        .ok_or_else(|| account_error(AccountError::UnknownToken))?;
+
+    // (game_id, match_string, color, opponent account id, result, archived)
+    let mut games: Vec<(
+        String,
+        String,
+        Player,
+        Option<String>,
+        Option<GameResult>,
+        bool,
+    )> = Vec::new();
+
+    {
+        let rooms = lock_rooms()?;
+        for (match_string, room) in rooms.iter() {
+            if let Some(player) = seat_for_account(room, &account.id) {
+                let opponent = match player {
+                    Player::Black => room.players.white.as_ref(),
+                    Player::White => room.players.black.as_ref(),
+                }
+                .and_then(|seat| seat.account_id.clone());
+                games.push((
+                    room.game_id.clone(),
+                    match_string.clone(),
+                    player,
+                    opponent,
+                    room.board.get_winner(),
+                    false,
+                ));
+            }
+        }
+    }
+
+    {
+        let archive = lock_archive()?;
+        for game in archive.games() {
+            let seat = if game.black_account.as_deref() == Some(account.id.as_str()) {
+                Some((Player::Black, game.white_account.clone()))
+            } else if game.white_account.as_deref() == Some(account.id.as_str()) {
+                Some((Player::White, game.black_account.clone()))
+            } else {
+                None
+            };
+
+            if let Some((player, opponent)) = seat {
+                games.push((
+                    game.game_id.clone(),
+                    game.match_string.clone(),
+                    player,
+                    opponent,
+                    game.result.clone(),
+                    true,
+                ));
+            }
+        }
+    }
+
+    let accounts = lock_accounts()?;
+    let my_games = games
+        .into_iter()
+        .map(
+            |(game_id, match_string, player, opponent, result, archived)| MyGame {
+                game_id,
+                match_string,
+                color: player.to_string(),
+                opponent: opponent
+                    .and_then(|id| accounts.get(&id))
+                    .map(|account| account.public()),
+                result,
+                archived,
+            },
+        )
+        .collect();
+
+    Ok(Json(my_games))
+}
+
+fn lock_ratings() -> Result<MutexGuard<'static, RatingStore>, Error> {
+    RATINGS
+        .lock()
+        .map_err(|_| ApiError::Internal("Failed to lock ratings".to_string()).into())
+}
+
+const DEFAULT_LEADERBOARD_SIZE: usize = 50;
+
+#[derive(Deserialize, ToSchema)]
+struct RatingHistoryPayload {
+    account_id: String,
+    #[serde(default)]
+    pool: RatingPool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/get-rating-history",
+    tag = "ratings",
+    summary = "Rating and its history in one pool",
+    request_body = RatingHistoryPayload,
+    responses(
+        (status = 200, body = PlayerRating),
+        (status = 404, description = "`not_found`: no rated games in this pool", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn get_rating_history(
+    payload: Json<RatingHistoryPayload>,
+) -> Result<Json<PlayerRating>, Error> {
+    lock_ratings()?
+        .rating(&payload.pool, &payload.account_id)
+        .cloned()
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound("No rated games in this pool".to_string()).into())
+}
+
+#[derive(Deserialize, ToSchema)]
+struct LeaderboardPayload {
+    #[serde(default)]
+    pool: RatingPool,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct LeaderboardEntry {
+    rank: usize,
+    account: Option<PublicAccount>,
+    rating: f64,
+    games: u32,
+}
+
+#[utoipa::path(
+    post,
+    path = "/get-leaderboard",
+    tag = "ratings",
+    summary = "Top rated accounts in one pool",
+    request_body = LeaderboardPayload,
+    responses(
+        (status = 200, body = Vec<LeaderboardEntry>)
+    )
+)]
+#[handler]
+async fn get_leaderboard(
+    payload: Json<LeaderboardPayload>,
+) -> Result<Json<Vec<LeaderboardEntry>>, Error> {
+    let limit = payload.limit.unwrap_or(DEFAULT_LEADERBOARD_SIZE);
+    let entries = lock_ratings()?.leaderboard(&payload.pool, limit);
+
+    let accounts = lock_accounts()?;
+    let leaderboard = entries
+        .into_iter()
+        .enumerate()
+        .map(|(i, (account_id, rating))| LeaderboardEntry {
+            rank: i + 1,
+            account: accounts.get(&account_id).map(|account| account.public()),
+            rating: rating.rating,
+            games: rating.games,
+        })
+        .collect();
+
+    Ok(Json(leaderboard))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ValidateSpectatorPayload {
+    match_string: String,
+    session_token: Option<String>,
+}
+
+// ANTI-CHEATING:
+// Handler used by `games.html` (or any caller) to check whether a client
+// opening a game as a spectator actually owns a seat in that game.
+// Returns same shape as `JoinGameResponse` so the frontend can decide where to
+// navigate (`main.html` for spectators or the player's specific board page).
+#[utoipa::path(
+    post,
+    path = "/validate-spectator",
+    tag = "game",
+    summary = "Open the spectator view of a room",
+    request_body = ValidateSpectatorPayload,
+    responses(
+        (status = 200, body = JoinGameResponse),
+        (status = "4XX", description = "Branch on `code`", body = ErrorBody)
+    )
+)]
+#[handler]
 async fn validate_spectator_open(
+    remote_addr: &RemoteAddr,
     payload: Json<ValidateSpectatorPayload>,
 ) -> Result<Json<JoinGameResponse>, Error> {
     let rooms = lock_rooms()?;
 
     let room = rooms
         .get(&payload.match_string)
-        .ok_or_else(|| json_error("Game room not found", StatusCode::NOT_FOUND))?;
+        .ok_or(ApiError::RoomNotFound)?;
 
     let (color, redirect_url, session_token) =
         resolve_spectator_session(room, &payload.match_string, &payload.session_token);
 
+    if color == "spectator" {
+        refuse_spectator(room, None, remote_ip(remote_addr))?;
+    }
+
     Ok(Json(JoinGameResponse {
         color,
         redirect_url,
@@ -1016,7 +3014,7 @@ async fn validate_spectator_open(
 }
 
 fn get_stones_mut<'a>(
-    guess_stones: &'a mut HashMap<String, (Vec<Vec<usize>>, Vec<Vec<usize>>)>,
+    guess_stones: &'a mut GuessStones,
     match_string: &str,
     color: &str,
 ) -> &'a mut Vec<Vec<usize>> {
@@ -1030,55 +3028,159 @@ fn get_stones_mut<'a>(
     }
 }
 
-// TODO: analyze this handler usage for potentially incorrect generation number counting
+// Guess stones are private to the player, so the game generation stays as it is. A bot
+// thinking meanwhile still gets to play its move
+#[utoipa::path(
+    post,
+    path = "/sync-guess-stones",
+    tag = "game",
+    summary = "Store a player's guess stones",
+    request_body = GuessStonesSync,
+    responses(
+        (status = 200, body = String),
+        (status = "4XX", description = "Branch on `code`", body = ErrorBody)
+    )
+)]
 #[handler]
 async fn sync_guess_stones(payload: Json<GuessStonesSync>) -> Result<Json<String>, Error> {
-    {
+    let player = {
         let mut rooms = lock_rooms()?;
-        let mut room = get_room(&mut rooms, &payload.match_string)?;
-
-        room.game_generation_number += 1;
-    }
+        let room = get_room(&mut rooms, &payload.match_string)?;
+        authorize_player(room, payload.session_token.as_deref())?
+    };
 
     {
         let mut guess_stones = lock_guess_stones()?;
-        let stones = get_stones_mut(&mut guess_stones, &payload.match_string, &payload.color);
+        // Guess stones mark where a player thinks the opponent's stones are
+        let color = player.opponent().to_string();
+        let stones = get_stones_mut(&mut guess_stones, &payload.match_string, &color);
         *stones = payload.stones.clone();
     }
 
     Ok(Json("Stones synced".to_string()))
 }
 
-#[derive(Serialize)]
-struct ErrorResponse {
-    error: String,
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "admin",
+    summary = "Liveness, answers as long as the process serves requests",
+    responses(
+        (status = 200, body = String)
+    )
+)]
+#[handler]
+fn healthz() -> &'static str {
+    "ok"
 }
 
-fn json_error(msg: &str, status: StatusCode) -> Error {
-    Error::from_response(
-        Response::builder()
-            .status(status)
-            .content_type("application/json")
-            .body(
-                serde_json::to_string(&ErrorResponse {
-                    error: msg.to_string(),
-                })
-                .unwrap(),
-            ),
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "admin",
+    summary = "Readiness, fails while draining for shutdown",
+    responses(
+        (status = 200, body = String),
+        (status = 503, description = "`unavailable`: draining or the room store is poisoned", body = ErrorBody)
     )
+)]
+#[handler]
+fn readyz() -> Result<&'static str, Error> {
+    refuse_new_games()?;
+    if GAME_ROOMS.is_poisoned() {
+        return Err(ApiError::Unavailable("Room store is unavailable".to_string()).into());
+    }
+    Ok("ready")
 }
 
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "admin",
+    summary = "Prometheus metrics",
+    responses(
+        (status = 200, body = String, content_type = "text/plain; version=0.0.4")
+    )
+)]
 #[handler]
-async fn reset_memory() {
+fn get_metrics() -> Result<Response, Error> {
+    {
+        let rooms = lock_rooms()?;
+        let seated_players: usize = rooms
+            .values()
+            .map(|room| {
+                usize::from(room.players.black.is_some())
+                    + usize::from(room.players.white.is_some())
+            })
+            .sum();
+        metrics::ACTIVE_ROOMS.set(rooms.len() as i64);
+        metrics::SEATED_PLAYERS.set(seated_players as i64);
+    }
+
+    Ok(Response::builder()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics::render()))
+}
+
+// Admin routes stay open when no admin token is configured
+fn authorize_admin(req: &Request) -> Result<(), Error> {
+    let Some(admin_token) = &config::get().admin_token else {
+        return Ok(());
+    };
+    match api_v1::bearer_token(req) {
+        Some(token) if token == admin_token => Ok(()),
+        Some(_) => Err(ApiError::Forbidden("Wrong admin token".to_string()).into()),
+        None => Err(ApiError::Unauthorized("Missing admin token".to_string()).into()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/reset-memory",
+    tag = "admin",
+    summary = "Drop every room and challenge",
+    params(("Authorization" = Option<String>, Header, description = "Bearer admin token")),
+    responses(
+        (status = 200),
+        (status = 401, description = "`unauthorized`: missing admin token", body = ErrorBody),
+        (status = 403, description = "`forbidden`: wrong admin token", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn reset_memory(req: &Request) -> Result<(), Error> {
+    authorize_admin(req)?;
+
+    // The lobby is always locked before the rooms
+    let mut lobby = LOBBY.lock().unwrap();
+    lobby.clear();
+
     let mut rooms = GAME_ROOMS.lock().unwrap();
     rooms.clear();
 
     let mut guess_stones = GUESS_STONES.lock().unwrap();
     guess_stones.clear();
+    Ok(())
 }
 
+#[utoipa::path(
+    post,
+    path = "/remove-game",
+    tag = "admin",
+    summary = "Drop one room",
+    params(("Authorization" = Option<String>, Header, description = "Bearer admin token")),
+    request_body = MatchStringPayload,
+    responses(
+        (status = 200, body = String),
+        (status = 401, description = "`unauthorized`: missing admin token", body = ErrorBody),
+        (status = 403, description = "`forbidden`: wrong admin token", body = ErrorBody)
+    )
+)]
 #[handler]
-async fn remove_game(payload: Json<MatchStringPayload>) -> Result<Json<String>, Error> {
+async fn remove_game(
+    req: &Request,
+    payload: Json<MatchStringPayload>,
+) -> Result<Json<String>, Error> {
+    authorize_admin(req)?;
     let mut rooms = GAME_ROOMS.lock().unwrap();
     rooms.remove(&payload.match_string);
 
@@ -1088,6 +3190,15 @@ async fn remove_game(payload: Json<MatchStringPayload>) -> Result<Json<String>,
     Ok(Json("Game removed".to_string()))
 }
 
+#[utoipa::path(
+    post,
+    path = "/get-all-games",
+    tag = "admin",
+    summary = "Ids of every live room",
+    responses(
+        (status = 200, body = Vec<String>)
+    )
+)]
 #[handler]
 fn get_all_games() -> Result<Json<Vec<String>>, Error> {
     let rooms = lock_rooms()?;
@@ -1095,15 +3206,28 @@ fn get_all_games() -> Result<Json<Vec<String>>, Error> {
     Ok(Json(match_strings))
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 struct GameInfoAdmin {
     match_string: String,
     last_move_time_elapsed: usize,
     last_move_string: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/get-all-games-admin",
+    tag = "admin",
+    summary = "Every live room with its state",
+    params(("Authorization" = Option<String>, Header, description = "Bearer admin token")),
+    responses(
+        (status = 200, body = Vec<GameInfoAdmin>),
+        (status = 401, description = "`unauthorized`: missing admin token", body = ErrorBody),
+        (status = 403, description = "`forbidden`: wrong admin token", body = ErrorBody)
+    )
+)]
 #[handler]
-fn get_all_games_admin() -> Result<Json<Vec<GameInfoAdmin>>, Error> {
+fn get_all_games_admin(req: &Request) -> Result<Json<Vec<GameInfoAdmin>>, Error> {
+    authorize_admin(req)?;
     let rooms = lock_rooms()?;
 
     let mut games_info: Vec<GameInfoAdmin> = vec![];
@@ -1125,7 +3249,7 @@ fn get_all_games_admin() -> Result<Json<Vec<GameInfoAdmin>>, Error> {
             last_move_string,
         };
 
-        println!("{:?}", game_info);
+        debug!(?game_info, "admin game info");
 
         games_info.push(game_info);
     }
@@ -1172,12 +3296,219 @@ fn format_elapsed(elapsed: usize) -> String {
     parts.join(", ")
 }
 
+#[derive(Deserialize, ToSchema)]
+struct ChatPayload {
+    match_string: String,
+    #[serde(default)]
+    session_token: Option<String>,
+    text: String,
+}
+
+fn chat_error(err: ChatError) -> Error {
+    match err {
+        ChatError::Empty => ApiError::InvalidRequest("Message is empty".to_string()).into(),
+        ChatError::TooLong => ApiError::InvalidRequest(format!(
+            "Message is longer than {} characters",
+            MAX_MESSAGE_LENGTH
+        ))
+        .into(),
+        ChatError::RateLimited => {
+            ApiError::RateLimited("Too many messages, slow down".to_string()).into()
+        }
+    }
+}
+
+// The channel comes from who is sending: a seated token talks to the players channel,
+// anyone else to the spectators channel. Spectators have no way to reach the players
+#[utoipa::path(
+    post,
+    path = "/send-chat-message",
+    tag = "chat",
+    summary = "Post to the room chat",
+    request_body = ChatPayload,
+    responses(
+        (status = 200, body = String),
+        (status = "4XX", description = "Branch on `code`", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn send_chat_message(
+    remote_addr: &RemoteAddr,
+    payload: Json<ChatPayload>,
+) -> Result<Json<String>, Error> {
+    let mut rooms = lock_rooms()?;
+    let room = get_room(&mut rooms, &payload.match_string)?;
+
+    let seat = payload
+        .session_token
+        .as_deref()
+        .and_then(|token| seat_for_token(room, token));
+
+    let (channel, author, sender) = match seat {
+        Some(player) => (ChatChannel::Players, player.to_string(), player.to_string()),
+        // Keyed by IP, a new connection gets a new port
+        None => (
+            ChatChannel::Spectators,
+            "spectator".to_string(),
+            format!(
+                "spectator {}",
+                remote_ip(remote_addr)
+                    .map(|ip| ip.to_string())
+                    .unwrap_or_else(|| remote_addr.to_string())
+            ),
+        ),
+    };
+
+    let message = ChatMessage {
+        channel,
+        author,
+        text: payload.text.clone(),
+        move_number: room.board.game_history.len(),
+        sent_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    };
+
+    room.chat
+        .post(&sender, message, Instant::now())
+        .map_err(chat_error)?;
+    // Chat is delivered through sync
+    room.game_generation_number += 1;
+
+    Ok(Json("Message sent".to_string()))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct GameRecordPayload {
+    match_string: String,
+    #[serde(default)]
+    session_token: Option<String>,
+}
+
+// Full event log, including moves that were later undone, once the board is revealed
+#[utoipa::path(
+    post,
+    path = "/get-game-events",
+    tag = "sync",
+    summary = "Every move and pass so far",
+    description = "While the game is live players only get their own moves and spectators the delayed position.",
+    request_body = GameRecordPayload,
+    responses(
+        (status = 200, body = Vec<GameEvent>),
+        (status = 403, description = "`forbidden`: spectators can't watch this game", body = ErrorBody),
+        (status = 404, description = "`room_not_found`", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn get_game_events(
+    remote_addr: &RemoteAddr,
+    payload: Json<GameRecordPayload>,
+) -> Result<Json<Vec<GameEvent>>, Error> {
+    let mut rooms = lock_rooms()?;
+    let room = get_room(&mut rooms, &payload.match_string)?;
+    let reader = record_reader(
+        room,
+        payload.session_token.as_deref(),
+        remote_ip(remote_addr),
+    )?;
+
+    Ok(Json(readable_events(room, reader)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/get-game-record",
+    tag = "archive",
+    summary = "SGF of a live game",
+    description = "Spectators get the delayed position, players have to wait until the board is revealed.",
+    request_body = GameRecordPayload,
+    responses(
+        (status = 200, body = String),
+        (status = 403, description = "`forbidden`: spectators can't watch this game, or a player asked before the board was revealed", body = ErrorBody),
+        (status = 404, description = "`room_not_found`", body = ErrorBody)
+    )
+)]
 #[handler]
-async fn send_game_record(payload: Json<MatchStringPayload>) -> Result<String, Error> {
+async fn send_game_record(
+    remote_addr: &RemoteAddr,
+    payload: Json<GameRecordPayload>,
+) -> Result<String, Error> {
     let mut rooms = lock_rooms()?;
-    let mut room = get_room(&mut rooms, &payload.match_string)?;
+    let room = get_room(&mut rooms, &payload.match_string)?;
+    let reader = record_reader(
+        room,
+        payload.session_token.as_deref(),
+        remote_ip(remote_addr),
+    )?;
+
+    readable_record(room, reader)
+}
+
+#[utoipa::path(
+    post,
+    path = "/get-archived-games",
+    tag = "archive",
+    summary = "Games moved out of memory",
+    responses(
+        (status = 200, body = Vec<ArchivedGameSummary>)
+    )
+)]
+#[handler]
+fn get_archived_games() -> Result<Json<Vec<ArchivedGameSummary>>, Error> {
+    let archive = lock_archive()?;
+    Ok(Json(archive.games().map(ArchivedGame::summary).collect()))
+}
 
-    Ok(room.board.get_game_sgf().clone())
+#[derive(Deserialize, ToSchema)]
+struct ArchivedGamePayload {
+    // A match string finds the latest game archived under it
+    #[serde(alias = "match_string")]
+    game_id: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/get-archived-game-record",
+    tag = "archive",
+    summary = "SGF of an archived game",
+    request_body = ArchivedGamePayload,
+    responses(
+        (status = 200, body = String),
+        (status = 404, description = "`not_found`", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn send_archived_game_record(payload: Json<ArchivedGamePayload>) -> Result<String, Error> {
+    let archive = lock_archive()?;
+    let game = archive
+        .get(&payload.game_id)
+        .ok_or_else(|| ApiError::NotFound("Archived game not found".to_string()))?;
+
+    Ok(game.sgf.clone())
+}
+
+#[utoipa::path(
+    post,
+    path = "/get-archived-game-events",
+    tag = "archive",
+    summary = "Moves of an archived game",
+    request_body = ArchivedGamePayload,
+    responses(
+        (status = 200, body = Vec<GameEvent>),
+        (status = 404, description = "`not_found`", body = ErrorBody)
+    )
+)]
+#[handler]
+async fn get_archived_game_events(
+    payload: Json<ArchivedGamePayload>,
+) -> Result<Json<Vec<GameEvent>>, Error> {
+    let archive = lock_archive()?;
+    let game = archive
+        .get(&payload.game_id)
+        .ok_or_else(|| ApiError::NotFound("Archived game not found".to_string()))?;
+
+    Ok(Json(game.events.clone()))
 }
 
 #[derive(rust_embed::Embed)]
@@ -1224,39 +3555,637 @@ impl Endpoint for StaticEmbed {
     }
 }
 
-pub async fn start_server() -> Result<(), std::io::Error> {
-    // Load .env file if it exists
-    let _ = dotenv::dotenv();
-
-    // Get bind addr from environment variable, fallback to default
-    let bind_addr = env::var("BIND_ADDR").unwrap_or_else(|_| "0.0.0.0:8000".to_string());
-
-    let cors = Cors::new()
-        .allow_methods(vec!["POST", "GET"])
-        .allow_headers(vec!["Content-Type"]); // Allow Content-Type header
-
-    let app = Route::new()
+fn routes() -> Route {
+    Route::new()
         .at("/join-game", poem::post(join_game))
         .at("/cell-click", poem::post(cell_click))
         .at("/dimensions", poem::post(get_dimensions))
         .at("/undo", poem::post(undo))
+        .at("/undo-response", poem::post(undo_response))
         .at("/pass", poem::post(pass))
         .at("/get-group", poem::post(get_group))
         .at("/get-score", poem::post(get_score))
+        .at("/resume-play", poem::post(resume_play))
         .at("/sync-guess-stones", poem::post(sync_guess_stones))
         .at("/get-board-interaction-number", poem::post(should_sync))
         .at("/sync-boards", poem::post(sync_boards))
         .at("/resign", poem::post(handle_resignation))
+        .at("/claim-forfeit", poem::post(claim_forfeit))
         .at("/reset-memory", poem::post(reset_memory))
         .at("/remove-game", poem::post(remove_game))
         .at("/get-all-games", poem::post(get_all_games))
         .at("/get-all-games-admin", poem::post(get_all_games_admin))
         .at("/get-game-record", poem::post(send_game_record))
+        .at("/get-game-events", poem::post(get_game_events))
+        .at("/send-chat-message", poem::post(send_chat_message))
+        .at("/get-archived-games", poem::post(get_archived_games))
+        .at(
+            "/get-archived-game-record",
+            poem::post(send_archived_game_record),
+        )
+        .at(
+            "/get-archived-game-events",
+            poem::post(get_archived_game_events),
+        )
         .at("/validate-spectator", poem::post(validate_spectator_open))
+        .at("/post-challenge", poem::post(post_challenge))
+        .at("/get-open-challenges", poem::post(get_open_challenges))
+        .at("/accept-challenge", poem::post(accept_challenge))
+        .at("/challenge-status", poem::post(challenge_status))
+        .at("/cancel-challenge", poem::post(cancel_challenge))
+        .at("/quick-match", poem::post(quick_match))
+        .at("/register", poem::post(register))
+        .at("/login", poem::post(login))
+        .at("/logout", poem::post(logout))
+        .at("/get-account", poem::post(get_account))
+        .at("/get-my-games", poem::post(get_my_games))
+        .at("/get-rating-history", poem::post(get_rating_history))
+        .at("/get-leaderboard", poem::post(get_leaderboard))
+        .nest("/api/v1", api_v1::routes())
+        .at(SPEC_PATH, poem::get(openapi_spec))
+        .at("/metrics", poem::get(get_metrics))
+        .at("/healthz", poem::get(healthz))
+        .at("/readyz", poem::get(readyz))
+        .nest("/docs", SwaggerUi)
         .at("/", poem::get(index))
         .nest("/frontend", StaticEmbed)
-        .with(cors);
+}
+
+pub async fn start_server(config: &'static Config) -> Result<(), std::io::Error> {
+    logging::init(config);
+    if config.admin_token.is_none() {
+        warn!("no admin token configured, admin routes are open to everyone");
+    }
+
+    // Fail at startup rather than on the first login if the store can't be read
+    lazy_static::initialize(&ACCOUNTS);
+    lazy_static::initialize(&RATINGS);
+    lazy_static::initialize(&ARCHIVED_GAMES);
+
+    spawn_room_sweeper(RoomLifetime::from_config(config));
+    let shutdown_config = ShutdownConfig::from_config(config);
+
+    // Without configured origins any site may call the API, like before
+    let cors = Cors::new()
+        .allow_origins(config.cors_origins.iter().map(String::as_str))
+        .allow_methods(vec!["POST", "GET"])
+        .allow_headers(vec!["Content-Type", "Authorization"]);
+
+    let app = routes()
+        .with(cors)
+        .around(rate_limit::limit_requests)
+        .around(metrics::observe_request)
+        .around(logging::trace_request);
+
+    let bind_addr = config.bind_addr;
+    let listener = TcpListener::bind(bind_addr);
+    // Fails here rather than on the first connection when the certificate doesn't load
+    let listener = match TlsFiles::from_config(config) {
+        Some(files) => {
+            let initial = files.load()?;
+            info!(%bind_addr, cert = %files.cert_path.display(), "serving HTTPS");
+            listener.rustls(tls::config_stream(files, initial)).boxed()
+        }
+        None => listener.boxed(),
+    };
+
+    info!(%bind_addr, "server running");
+    Server::new(listener)
+        .run_with_graceful_shutdown(app, shutdown::signal(), Some(shutdown_config.timeout))
+        .await?;
 
-    println!("Server running at {}", bind_addr);
-    Server::new(TcpListener::bind(bind_addr)).run(app).await
+    flush_rooms(&shutdown_config);
+    info!("shutdown complete");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{Board, GameResult, Loc, Move, Player};
+    use crate::ratings::RatingPool;
+    use crate::room_settings::RoomSettings;
+    use crate::server::{
+        bot_accepts_selection, lock_ratings, rate_game, routes, GameRoom, PlayerSession,
+    };
+    use poem::http::StatusCode;
+    use poem::test::TestClient;
+    use poem::Route;
+    use serde_json::{json, Value};
+    use std::time::SystemTime;
+
+    // Every test gets its own room, they share the server's globals
+    struct Game {
+        match_string: String,
+        black: String,
+        white: String,
+    }
+
+    impl Game {
+        fn body(&self, session_token: &str, extra: Value) -> Value {
+            let mut body = json!({
+                "match_string": self.match_string,
+                "session_token": session_token,
+            });
+            if let (Some(body), Value::Object(extra)) = (body.as_object_mut(), extra) {
+                body.extend(extra);
+            }
+            body
+        }
+    }
+
+    async fn post(cli: &TestClient<Route>, path: &str, body: Value) -> (StatusCode, Value) {
+        let response = cli.post(path).body_json(&body).send().await;
+        let status = response.0.status();
+        let body = response
+            .0
+            .into_body()
+            .into_json()
+            .await
+            .unwrap_or(Value::Null);
+        (status, body)
+    }
+
+    async fn join(cli: &TestClient<Route>, match_string: &str, settings: &Value) -> Value {
+        let (status, body) = post(
+            cli,
+            "/join-game",
+            json!({
+                "match_string": match_string,
+                "session_token": null,
+                "is_spectator": false,
+                "settings": settings,
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        body
+    }
+
+    // The first player gets a random color
+    async fn start_game(cli: &TestClient<Route>, settings: Value) -> Game {
+        let match_string = format!("test-{}", uuid::Uuid::new_v4());
+        let first = join(cli, &match_string, &settings).await;
+        let second = join(cli, &match_string, &settings).await;
+        let token = |seat: &Value| seat["session_token"].as_str().unwrap().to_string();
+
+        let (black, white) = match first["color"].as_str() {
+            Some("black") => (token(&first), token(&second)),
+            _ => (token(&second), token(&first)),
+        };
+        assert_ne!(first["color"], second["color"]);
+        Game {
+            match_string,
+            black,
+            white,
+        }
+    }
+
+    #[tokio::test]
+    async fn undo_cannot_reopen_a_finished_game() {
+        let cli = TestClient::new(routes());
+        let game = start_game(&cli, json!({ "board_size": 5, "undo_policy": "free" })).await;
+
+        let click = game.body(&game.black, json!({ "row": 0, "col": 0 }));
+        assert_eq!(post(&cli, "/cell-click", click).await.0, StatusCode::OK);
+        let resign = game.body(&game.white, json!({}));
+        assert_eq!(post(&cli, "/resign", resign).await.0, StatusCode::OK);
+
+        // Black moved last, so an undo would otherwise apply right away
+        let (status, body) = post(&cli, "/undo", game.body(&game.black, json!({}))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "game_over");
+    }
+
+    #[tokio::test]
+    async fn undo_is_refused_while_counting() {
+        let cli = TestClient::new(routes());
+        let game = start_game(&cli, json!({ "board_size": 5, "undo_policy": "ask" })).await;
+
+        let click = game.body(&game.black, json!({ "row": 0, "col": 0 }));
+        assert_eq!(post(&cli, "/cell-click", click).await.0, StatusCode::OK);
+        let (status, _) = post(&cli, "/undo", game.body(&game.black, json!({}))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Playing on declines the request
+        for token in [&game.white, &game.black] {
+            let pass = game.body(token, json!({}));
+            assert_eq!(post(&cli, "/pass", pass).await.0, StatusCode::OK);
+        }
+
+        let (status, body) = post(&cli, "/undo", game.body(&game.black, json!({}))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "wrong_phase");
+
+        let answer = game.body(&game.white, json!({ "accept": true }));
+        let (status, body) = post(&cli, "/undo-response", answer).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "wrong_phase");
+    }
+
+    #[tokio::test]
+    async fn sync_takes_the_seat_from_the_token_not_the_claimed_color() {
+        let cli = TestClient::new(routes());
+        let settings = json!({ "board_size": 5, "spectator_delay": { "moves": 1 } });
+        let game = start_game(&cli, settings).await;
+
+        let click = game.body(&game.black, json!({ "row": 0, "col": 0 }));
+        assert_eq!(post(&cli, "/cell-click", click).await.0, StatusCode::OK);
+
+        // Claiming black without its token is the delayed spectator view
+        let claim = json!({ "match_string": game.match_string, "player": "black" });
+        let (status, body) = post(&cli, "/sync-boards", claim).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["board"][0][0], "empty");
+
+        let (_, body) = post(&cli, "/sync-boards", game.body(&game.black, json!({}))).await;
+        assert_eq!(body["board"][0][0], "black");
+
+        // White only gets their own stones
+        let (_, body) = post(&cli, "/sync-boards", game.body(&game.white, json!({}))).await;
+        assert_eq!(body["board"][0][0], "empty");
+        assert_eq!(body["black_player_board"][0][0], "empty");
+    }
+
+    #[tokio::test]
+    async fn a_claimed_seat_is_refused_when_spectators_are_off() {
+        let cli = TestClient::new(routes());
+        let game = start_game(&cli, json!({ "board_size": 5, "allow_spectators": false })).await;
+
+        let claim = json!({ "match_string": game.match_string, "player": "black" });
+        let (status, body) = post(&cli, "/sync-boards", claim).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "forbidden");
+    }
+
+    #[tokio::test]
+    async fn live_records_follow_the_spectator_rules() {
+        let cli = TestClient::new(routes());
+        let settings = json!({ "board_size": 5, "spectator_delay": { "moves": 1 } });
+        let game = start_game(&cli, settings).await;
+
+        for (token, row) in [(&game.black, 0), (&game.white, 1)] {
+            let click = game.body(token, json!({ "row": row, "col": row }));
+            assert_eq!(post(&cli, "/cell-click", click).await.0, StatusCode::OK);
+        }
+
+        let tokenless = json!({ "match_string": game.match_string });
+        let (_, events) = post(&cli, "/get-game-events", tokenless.clone()).await;
+        assert_eq!(events.as_array().unwrap().len(), 1);
+
+        let (_, events) = post(&cli, "/get-game-events", game.body(&game.white, json!({}))).await;
+        assert_eq!(events.as_array().unwrap().len(), 1);
+        assert_eq!(events[0]["Move"]["player"], "White");
+
+        let record = game.body(&game.black, json!({}));
+        let (status, _) = post(&cli, "/get-game-record", record.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let resign = game.body(&game.white, json!({}));
+        assert_eq!(post(&cli, "/resign", resign).await.0, StatusCode::OK);
+
+        let response = cli.post("/get-game-record").body_json(&record).send().await;
+        response.assert_status_is_ok();
+        response
+            .assert_text("(;GM[1]SZ[5]KM[1.5];B[aa];W[bb])")
+            .await;
+        let (_, events) = post(&cli, "/get-game-events", tokenless).await;
+        assert_eq!(events.as_array().unwrap().len(), 2);
+    }
+
+    async fn get(cli: &TestClient<Route>, path: &str, bearer: Option<&str>) -> (StatusCode, Value) {
+        let mut request = cli.get(path);
+        if let Some(token) = bearer {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+        let response = request.send().await;
+        let status = response.0.status();
+        let body = response
+            .0
+            .into_body()
+            .into_json()
+            .await
+            .unwrap_or(Value::Null);
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn v1_players_only_read_their_own_moves() {
+        let cli = TestClient::new(routes());
+        let game = start_game(&cli, json!({ "board_size": 5 })).await;
+        let path = format!("/api/v1/games/{}", game.match_string);
+
+        for (token, row) in [(&game.black, 0), (&game.white, 1)] {
+            let click = game.body(token, json!({ "row": row, "col": row }));
+            assert_eq!(post(&cli, "/cell-click", click).await.0, StatusCode::OK);
+        }
+
+        let (status, moves) = get(&cli, &format!("{}/moves", path), Some(&game.black)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(moves.as_array().unwrap().len(), 1);
+        let (status, _) = get(&cli, &format!("{}/sgf", path), Some(&game.black)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (_, state) = get(&cli, &path, Some(&game.black)).await;
+        assert_eq!(state["board"][0][0], "black");
+        assert_eq!(state["board"][1][1], "empty");
+    }
+
+    #[tokio::test]
+    async fn moves_need_the_token_of_the_seat_to_move() {
+        let cli = TestClient::new(routes());
+        let game = start_game(&cli, json!({ "board_size": 5 })).await;
+        let click = |token: Value| json!({ "match_string": game.match_string, "session_token": token, "row": 0, "col": 0 });
+
+        let (status, body) = post(&cli, "/cell-click", click(Value::Null)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], "unauthorized");
+
+        let (status, body) = post(&cli, "/cell-click", click(json!("not-a-seat"))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "forbidden");
+
+        // White's token plays white, whatever the page thinks it is
+        let (status, body) = post(&cli, "/cell-click", click(json!(game.white))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "not_your_turn");
+
+        let (status, _) = post(&cli, "/cell-click", click(json!(game.black))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = post(&cli, "/pass", game.body("not-a-seat", json!({}))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let tokenless = json!({ "match_string": game.match_string });
+        assert_eq!(
+            post(&cli, "/resign", tokenless).await.0,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn every_live_read_refuses_spectators_when_they_are_off() {
+        let cli = TestClient::new(routes());
+        let game = start_game(&cli, json!({ "board_size": 5, "allow_spectators": false })).await;
+        let click = game.body(&game.black, json!({ "row": 0, "col": 0 }));
+        assert_eq!(post(&cli, "/cell-click", click).await.0, StatusCode::OK);
+
+        let tokenless = json!({ "match_string": game.match_string });
+        for path in [
+            "/sync-boards",
+            "/get-game-events",
+            "/get-game-record",
+            "/get-score",
+        ] {
+            let (status, _) = post(&cli, path, tokenless.clone()).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", path);
+        }
+
+        let spectate = json!({
+            "match_string": game.match_string,
+            "session_token": null,
+            "is_spectator": true,
+        });
+        assert_eq!(
+            post(&cli, "/join-game", spectate).await.0,
+            StatusCode::FORBIDDEN
+        );
+
+        let path = format!("/api/v1/games/{}", game.match_string);
+        for read in ["", "/moves", "/sgf"] {
+            let (status, _) = get(&cli, &format!("{}{}", path, read), None).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", read);
+        }
+    }
+
+    #[tokio::test]
+    async fn every_live_read_keeps_spectators_behind() {
+        let cli = TestClient::new(routes());
+        let settings = json!({ "board_size": 5, "spectator_delay": { "moves": 1 } });
+        let game = start_game(&cli, settings).await;
+
+        for (token, row) in [(&game.black, 0), (&game.white, 1)] {
+            let click = game.body(token, json!({ "row": row, "col": row }));
+            assert_eq!(post(&cli, "/cell-click", click).await.0, StatusCode::OK);
+        }
+
+        let delayed_sgf = "(;GM[1]SZ[5]KM[1.5];B[aa])";
+        let tokenless = json!({ "match_string": game.match_string });
+        let path = format!("/api/v1/games/{}", game.match_string);
+
+        let (_, state) = post(&cli, "/sync-boards", tokenless.clone()).await;
+        assert_eq!(state["board"][0][0], "black");
+        assert_eq!(state["board"][1][1], "empty");
+        let (_, state) = get(&cli, &path, None).await;
+        assert_eq!(state["board"][0][0], "black");
+        assert_eq!(state["board"][1][1], "empty");
+
+        let (_, events) = post(&cli, "/get-game-events", tokenless.clone()).await;
+        assert_eq!(events.as_array().unwrap().len(), 1);
+        let (_, events) = get(&cli, &format!("{}/moves", path), None).await;
+        assert_eq!(events.as_array().unwrap().len(), 1);
+
+        // Only black's stone is on the delayed board, the rest is black territory
+        let (_, score) = post(&cli, "/get-score", tokenless.clone()).await;
+        assert_eq!(score, "Black +22.5");
+
+        let response = cli
+            .post("/get-game-record")
+            .body_json(&tokenless)
+            .send()
+            .await;
+        response.assert_text(delayed_sgf).await;
+        let response = cli.get(format!("{}/sgf", path)).send().await;
+        response.assert_text(delayed_sgf).await;
+    }
+
+    #[tokio::test]
+    async fn delayed_spectators_get_nothing_from_past_the_delay() {
+        let cli = TestClient::new(routes());
+        let settings = json!({
+            "board_size": 5,
+            "undo_policy": "ask",
+            "spectator_delay": { "moves": 1 },
+        });
+        let game = start_game(&cli, settings).await;
+
+        for (token, row) in [(&game.black, 0), (&game.white, 1)] {
+            let click = game.body(token, json!({ "row": row, "col": row }));
+            assert_eq!(post(&cli, "/cell-click", click).await.0, StatusCode::OK);
+        }
+        let chat = game.body(&game.white, json!({ "text": "oops" }));
+        assert_eq!(
+            post(&cli, "/send-chat-message", chat).await.0,
+            StatusCode::OK
+        );
+        let undo = game.body(&game.white, json!({}));
+        assert_eq!(post(&cli, "/undo", undo).await.0, StatusCode::OK);
+
+        let tokenless = json!({ "match_string": game.match_string });
+        let (_, state) = post(&cli, "/sync-boards", tokenless).await;
+        assert_eq!(state["pending_undo"], Value::Null);
+        assert_eq!(state["chat"], json!([]));
+
+        let (_, state) = post(&cli, "/sync-boards", game.body(&game.black, json!({}))).await;
+        assert_eq!(state["pending_undo"], "white");
+        assert_eq!(state["chat"][0]["text"], "oops");
+    }
+
+    #[tokio::test]
+    async fn bot_tokens_only_stand_in_for_their_own_seat() {
+        let cli = TestClient::new(routes());
+        let mut bot_tokens = Vec::new();
+        for _ in 0..2 {
+            let name = format!("bot-{}", &uuid::Uuid::new_v4().to_string()[..8]);
+            let response = cli
+                .post("/api/v1/bots")
+                .body_json(&json!({ "display_name": name }))
+                .send()
+                .await;
+            response.assert_status(StatusCode::CREATED);
+            let body: Value = response.0.into_body().into_json().await.unwrap();
+            bot_tokens.push(body["bot_token"].as_str().unwrap().to_string());
+        }
+
+        let match_string = format!("test-{}", uuid::Uuid::new_v4());
+        let (status, seat) = post(
+            &cli,
+            "/join-game",
+            json!({
+                "match_string": match_string,
+                "session_token": null,
+                "is_spectator": false,
+                "login_token": bot_tokens[0],
+                "settings": { "board_size": 5 },
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let guest = join(&cli, &match_string, &json!({})).await;
+
+        // The bot moves second when it got white
+        if seat["color"] == "white" {
+            let pass =
+                json!({ "match_string": match_string, "session_token": guest["session_token"] });
+            assert_eq!(post(&cli, "/pass", pass).await.0, StatusCode::OK);
+        }
+
+        let turn = format!("/api/v1/games/{}/turn", match_string);
+        let play_turn = |bearer: Option<&str>| {
+            let mut request = cli.post(&turn).body_json(&json!({ "type": "pass" }));
+            if let Some(token) = bearer {
+                request = request.header("Authorization", format!("Bearer {}", token));
+            }
+            request.send()
+        };
+
+        play_turn(None)
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        play_turn(Some(&bot_tokens[1]))
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        play_turn(Some("not-a-seat"))
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        play_turn(Some(&bot_tokens[0]))
+            .await
+            .assert_status(StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn guess_stones_leave_the_game_generation_alone() {
+        let cli = TestClient::new(routes());
+        let game = start_game(&cli, json!({ "board_size": 5 })).await;
+        let should_sync = || {
+            let extra = json!({ "player": "white", "frontend_board_generation_number": 0 });
+            game.body(&game.white, extra)
+        };
+
+        let (_, before) = post(&cli, "/get-board-interaction-number", should_sync()).await;
+        let sync = game.body(&game.white, json!({ "stones": [[1, 1]] }));
+        assert_eq!(
+            post(&cli, "/sync-guess-stones", sync).await.0,
+            StatusCode::OK
+        );
+
+        // A bot thinking about its move would otherwise throw it away
+        let (_, after) = post(&cli, "/get-board-interaction-number", should_sync()).await;
+        assert_eq!(
+            before["board_generation_number"],
+            after["board_generation_number"]
+        );
+    }
+
+    #[test]
+    fn the_bot_only_gives_up_its_own_stones_in_atari() {
+        let mut board = Board::new(7, 7, 1.5);
+        for (player, row, col) in [
+            (Player::Black, 1, 1),
+            (Player::White, 1, 2),
+            (Player::Black, 3, 3),
+        ] {
+            board.play(&Move {
+                player,
+                loc: Loc { row, col },
+            });
+        }
+        let selection = |locs: &[(usize, usize)]| {
+            locs.iter()
+                .map(|&(row, col)| board.group_stones(Loc { row, col }))
+                .collect()
+        };
+
+        assert!(bot_accepts_selection(
+            &board,
+            &selection(&[(1, 1), (1, 2)]),
+            Player::Black
+        ));
+        assert!(!bot_accepts_selection(
+            &board,
+            &selection(&[(3, 3)]),
+            Player::Black
+        ));
+        assert!(bot_accepts_selection(
+            &board,
+            &selection(&[(3, 3)]),
+            Player::White
+        ));
+    }
+
+    #[test]
+    fn games_are_rated_outside_a_runtime_too() {
+        let mut room = GameRoom::new(RoomSettings::default());
+        let black = uuid::Uuid::new_v4().to_string();
+        let white = uuid::Uuid::new_v4().to_string();
+        room.players.black = Some(PlayerSession::new("b".into(), Some(black.clone()), None));
+        room.players.white = Some(PlayerSession::new("w".into(), Some(white), None));
+
+        let result = GameResult::Resignation(Player::Black);
+        rate_game("test-outside-runtime", &room, &result, SystemTime::now());
+
+        let pool = RatingPool::for_settings(&room.settings);
+        assert_eq!(
+            lock_ratings().unwrap().rating(&pool, &black).unwrap().games,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn finished_rooms_are_revalidated_until_archived() {
+        let cli = TestClient::new(routes());
+        let game = start_game(&cli, json!({ "board_size": 5 })).await;
+        let resign = game.body(&game.black, json!({}));
+        assert_eq!(post(&cli, "/resign", resign).await.0, StatusCode::OK);
+
+        // Chat still lands in the record until the sweeper archives the room
+        let path = format!("/api/v1/games/{}", game.match_string);
+        for read in ["/moves", "/sgf"] {
+            let response = cli
+                .get(format!("{}{}", path, read))
+                .header("Authorization", format!("Bearer {}", game.black))
+                .send()
+                .await;
+            response.assert_status_is_ok();
+            response.assert_header("cache-control", "private, no-cache");
+        }
+    }
 }
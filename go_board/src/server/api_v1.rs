@@ -0,0 +1,586 @@
+// Versioned, resource oriented routes, nested under /api/v1.
+//
+// Lingo:
+//     game - a room, addressed by its match string
+//     bearer token - the seat's session token, sent as `Authorization: Bearer <token>`
+//                    instead of in the body. Bots may send their bot token instead, it
+//                    stands for every seat their account holds
+//     turn - long poll for bots, answers once the seat has something to do
+//
+// The POST routes at the root are kept as aliases of these while clients migrate.
+
+use super::{
+    account_error, authorize_player, get_room, lock_accounts, lock_archive, lock_rooms,
+    mark_present, pass_turn, play_stone, readable_events, readable_record, record_reader,
+    remote_ip, room_view, seat_board, seat_for_account, seat_for_token, Announcement, GameRoom,
+    GameState,
+};
+use crate::accounts::PublicAccount;
+use crate::api_error::{ApiError, ErrorBody};
+use crate::board::{GameEvent, Player};
+use crate::logging;
+use crate::rate_limit;
+use poem::{
+    handler,
+    http::{header, StatusCode},
+    web::{Json, Path, Query, RemoteAddr},
+    Error, IntoResponse, Request, Response, Route,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::time::sleep;
+use utoipa::ToSchema;
+
+// Archived games don't change anymore. Finished rooms still do until they're swept,
+// players keep chatting into the record
+const ARCHIVED_CACHE_CONTROL: &str = "public, max-age=3600";
+// Live games can change any time, clients revalidate with the ETag
+const LIVE_CACHE_CONTROL: &str = "no-cache";
+// Live views that depend on who's asking, shared caches must not keep them
+const VIEWER_CACHE_CONTROL: &str = "private, no-cache";
+// Longest a turn request is held open before it answers `wait`
+const MAX_TURN_WAIT: Duration = Duration::from_secs(30);
+// How often a held turn request looks at its room
+const TURN_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+pub fn routes() -> Route {
+    Route::new()
+        .at("/games", poem::get(list_games))
+        .at("/games/:id", poem::get(get_game))
+        .at("/games/:id/moves", poem::get(list_moves).post(create_move))
+        .at("/games/:id/sgf", poem::get(get_sgf))
+        .at("/games/:id/turn", poem::get(wait_for_turn).post(play_turn))
+        .at("/bots", poem::post(create_bot))
+}
+
+pub(super) fn bearer_token(req: &Request) -> Option<&str> {
+    req.header(header::AUTHORIZATION)?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+// Answers 304 when the client already has this exact body
+fn cached(req: &Request, body: Vec<u8>, content_type: &str, cache_control: &str) -> Response {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    let etag = format!("\"{:016x}\"", hasher.finish());
+
+    let not_modified = req
+        .header(header::IF_NONE_MATCH)
+        .is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == etag));
+
+    let response = Response::builder()
+        .header(header::ETAG, etag)
+        .header(header::CACHE_CONTROL, cache_control)
+        .header(header::VARY, "Authorization");
+
+    if not_modified {
+        response.status(StatusCode::NOT_MODIFIED).finish()
+    } else {
+        response.content_type(content_type).body(body)
+    }
+}
+
+fn cached_json<T: Serialize>(req: &Request, value: &T, cache_control: &str) -> Response {
+    let body = serde_json::to_vec(value).unwrap_or_default();
+    cached(req, body, "application/json", cache_control)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/games",
+    tag = "v1",
+    summary = "Match strings of every live game",
+    responses(
+        (status = 200, body = Vec<String>)
+    )
+)]
+#[handler]
+pub fn list_games(req: &Request) -> Result<Response, Error> {
+    let mut match_strings: Vec<String> = lock_rooms()?.keys().cloned().collect();
+    match_strings.sort();
+    Ok(cached_json(req, &match_strings, LIVE_CACHE_CONTROL))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}",
+    tag = "v1",
+    summary = "The game as the caller sees it",
+    description = "Players see their seat's view, anyone without a seated bearer token gets the spectator view.",
+    params(
+        ("id" = String, Path, description = "Match string"),
+        ("Authorization" = Option<String>, Header, description = "Bearer session token")
+    ),
+    responses(
+        (status = 200, body = GameState),
+        (status = 304, description = "Unchanged since the ETag in If-None-Match"),
+        (status = 403, description = "`forbidden`: spectators can't watch this game", body = ErrorBody),
+        (status = 404, description = "`room_not_found`", body = ErrorBody)
+    )
+)]
+#[handler]
+pub fn get_game(
+    req: &Request,
+    remote_addr: &RemoteAddr,
+    Path(match_string): Path<String>,
+) -> Result<Response, Error> {
+    let session_token = bearer_token(req);
+
+    let mut rooms = lock_rooms()?;
+    let room = get_room(&mut rooms, &match_string)?;
+
+    let game_state = room_view(&match_string, room, session_token, remote_ip(remote_addr))?;
+    Ok(cached_json(req, &game_state, VIEWER_CACHE_CONTROL))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}/moves",
+    tag = "v1",
+    summary = "Every move and pass, including undone ones",
+    description = "While the game is live players only get their own moves and passes, anyone without a seated bearer token gets the spectator view.",
+    params(
+        ("id" = String, Path, description = "Match string, or the game id of an archived game"),
+        ("Authorization" = Option<String>, Header, description = "Bearer session token")
+    ),
+    responses(
+        (status = 200, body = Vec<GameEvent>),
+        (status = 304, description = "Unchanged since the ETag in If-None-Match"),
+        (status = 403, description = "`forbidden`: spectators can't watch this game", body = ErrorBody),
+        (status = 404, description = "`room_not_found`", body = ErrorBody)
+    )
+)]
+#[handler]
+pub fn list_moves(
+    req: &Request,
+    remote_addr: &RemoteAddr,
+    Path(match_string): Path<String>,
+) -> Result<Response, Error> {
+    if let Some(room) = lock_rooms()?.get(&match_string) {
+        let reader = record_reader(room, bearer_token(req), remote_ip(remote_addr))?;
+        return Ok(cached_json(
+            req,
+            &readable_events(room, reader),
+            VIEWER_CACHE_CONTROL,
+        ));
+    }
+
+    let archive = lock_archive()?;
+    let game = archive.get(&match_string).ok_or(ApiError::RoomNotFound)?;
+    Ok(cached_json(req, &game.events, ARCHIVED_CACHE_CONTROL))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MoveRequest {
+    // Row and col count from the top left corner, starting at 0
+    Play { row: usize, col: usize },
+    Pass,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{id}/moves",
+    tag = "v1",
+    summary = "Play a stone or pass",
+    params(
+        ("id" = String, Path, description = "Match string"),
+        ("Authorization" = String, Header, description = "Bearer session token")
+    ),
+    request_body = MoveRequest,
+    responses(
+        (status = 201, description = "The move was played", body = GameState),
+        (status = 401, description = "`unauthorized`: missing session token", body = ErrorBody),
+        (status = 403, description = "`forbidden`: the token doesn't own a seat", body = ErrorBody),
+        (status = 404, description = "`room_not_found`", body = ErrorBody),
+        (status = 409, description = "`not_your_turn`, `wrong_phase` or `game_over`", body = ErrorBody),
+        (status = 422, description = "`illegal_move`", body = ErrorBody)
+    )
+)]
+#[handler]
+pub fn create_move(
+    req: &Request,
+    Path(match_string): Path<String>,
+    payload: Json<MoveRequest>,
+) -> Result<Response, Error> {
+    let mut rooms = lock_rooms()?;
+    let room = get_room(&mut rooms, &match_string)?;
+    let player = authorize_player(room, bearer_token(req))?;
+
+    let game_state = match *payload {
+        MoveRequest::Play { row, col } => play_stone(room, player, row, col)?,
+        MoveRequest::Pass => pass_turn(room, player)?,
+    };
+
+    Ok(Json(game_state)
+        .with_status(StatusCode::CREATED)
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}/sgf",
+    tag = "v1",
+    summary = "Game record of a live or archived game",
+    description = "Spectators get the delayed position of a live game, players have to wait until the board is revealed.",
+    params(
+        ("id" = String, Path, description = "Match string, or the game id of an archived game"),
+        ("Authorization" = Option<String>, Header, description = "Bearer session token")
+    ),
+    responses(
+        (status = 200, body = String, content_type = "application/x-go-sgf"),
+        (status = 304, description = "Unchanged since the ETag in If-None-Match"),
+        (status = 403, description = "`forbidden`: spectators can't watch this game, or a player asked before the board was revealed", body = ErrorBody),
+        (status = 404, description = "`room_not_found`", body = ErrorBody)
+    )
+)]
+#[handler]
+pub fn get_sgf(
+    req: &Request,
+    remote_addr: &RemoteAddr,
+    Path(match_string): Path<String>,
+) -> Result<Response, Error> {
+    let (sgf, cache_control) = match lock_rooms()?.get(&match_string) {
+        Some(room) => {
+            let reader = record_reader(room, bearer_token(req), remote_ip(remote_addr))?;
+            (readable_record(room, reader)?, VIEWER_CACHE_CONTROL)
+        }
+        None => {
+            let archive = lock_archive()?;
+            let game = archive.get(&match_string).ok_or(ApiError::RoomNotFound)?;
+            (game.sgf.clone(), ARCHIVED_CACHE_CONTROL)
+        }
+    };
+
+    let mut response = cached(req, sgf.into_bytes(), "application/x-go-sgf", cache_control);
+    response.headers_mut().insert(
+        header::CONTENT_DISPOSITION,
+        format!(
+            "attachment; filename=\"{}.sgf\"",
+            match_string.replace('"', "")
+        )
+        .parse()
+        .map_err(|_| ApiError::InvalidRequest("Invalid match string".to_string()))?,
+    );
+    Ok(response)
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateBotRequest {
+    display_name: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BotAccountResponse {
+    account: PublicAccount,
+    // Send it as `login_token` to /join-game, then as the bearer token here
+    bot_token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/bots",
+    tag = "bots",
+    summary = "Create a bot account",
+    request_body = CreateBotRequest,
+    responses(
+        (status = 201, body = BotAccountResponse),
+        (status = 400, description = "`invalid_request`: bad display name", body = ErrorBody),
+        (status = 409, description = "`conflict`: display name is taken", body = ErrorBody)
+    )
+)]
+#[handler]
+pub fn create_bot(payload: Json<CreateBotRequest>) -> Result<Response, Error> {
+    let (account, bot_token) = lock_accounts()?
+        .register_bot(&payload.display_name)
+        .map_err(account_error)?;
+
+    Ok(Json(BotAccountResponse {
+        account: account.public(),
+        bot_token,
+    })
+    .with_status(StatusCode::CREATED)
+    .into_response())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SeatAction {
+    // Play a stone or pass through POST /turn
+    Move,
+    // The opponent asked to take their move back, answer through /undo-response
+    AnswerUndo,
+    // Accept the dead stone selection through /get-score, or leave counting through /resume-play
+    Count,
+    Wait,
+    GameOver,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SeatTurn {
+    action: SeatAction,
+    color: String,
+    // For the routes that still take it in the body
+    session_token: String,
+    // Only the seat's own stones while the game is on, every stone once counting starts
+    board: Vec<Vec<String>>,
+    current_player: String,
+    move_number: usize,
+    black_captures: isize,
+    white_captures: isize,
+    // Version to accept through /get-score during counting
+    counting_version: usize,
+    // Stones selected as dead during counting, as [row, col]
+    dead_stones: Vec<Vec<usize>>,
+    winner: Option<String>,
+    announcements: Vec<Announcement>,
+    // Send as `since` to only get newer announcements
+    next_since: usize,
+}
+
+#[derive(Deserialize)]
+pub struct TurnQuery {
+    since: Option<usize>,
+    wait_secs: Option<u64>,
+}
+
+// Login tokens are never session tokens, so a bearer token is one or the other
+fn bearer_account(bearer: Option<&str>) -> Result<Option<String>, Error> {
+    let Some(token) = bearer else {
+        return Ok(None);
+    };
+    Ok(lock_accounts()?
+        .account_for_token(token)
+        .map(|account| account.id.clone()))
+}
+
+fn seat_for_bearer(
+    room: &GameRoom,
+    bearer: Option<&str>,
+    account_id: Option<&str>,
+) -> Option<Player> {
+    account_id
+        .and_then(|id| seat_for_account(room, id))
+        .or_else(|| bearer.and_then(|token| seat_for_token(room, token)))
+}
+
+// Same as authorize_player, with bot tokens standing in for their seat's session token
+fn authorize_bearer(
+    room: &GameRoom,
+    bearer: Option<&str>,
+    account_id: Option<&str>,
+) -> Result<Player, Error> {
+    match account_id.and_then(|id| seat_for_account(room, id)) {
+        Some(player) => {
+            logging::record_seat(player);
+            rate_limit::check_session(bearer.unwrap_or_default())?;
+            Ok(player)
+        }
+        None => authorize_player(room, bearer),
+    }
+}
+
+fn seat_session(room: &GameRoom, player: Player) -> String {
+    let seat = match player {
+        Player::Black => &room.players.black,
+        Player::White => &room.players.white,
+    };
+    seat.as_ref()
+        .map(|seat| seat.session_token.clone())
+        .unwrap_or_default()
+}
+
+fn seat_action(room: &GameRoom, player: Player) -> SeatAction {
+    if room.board.get_winner().is_some() {
+        SeatAction::GameOver
+    } else if room
+        .pending_undo
+        .is_some_and(|request| request.requested_by != player)
+    {
+        SeatAction::AnswerUndo
+    } else if room.board.is_counting() {
+        let ready = room.counting.ready_to_count();
+        let accepted = match player {
+            Player::Black => ready.black,
+            Player::White => ready.white,
+        };
+        if accepted {
+            SeatAction::Wait
+        } else {
+            SeatAction::Count
+        }
+    } else if room.board.get_current_player() == player {
+        SeatAction::Move
+    } else {
+        SeatAction::Wait
+    }
+}
+
+fn seat_turn(room: &GameRoom, player: Player, since: usize) -> SeatTurn {
+    let mut dead_stones: Vec<Vec<usize>> = room
+        .counting
+        .selected
+        .iter()
+        .flatten()
+        // Skip the sentinel border
+        .map(|loc| vec![loc.row - 1, loc.col - 1])
+        .collect();
+    dead_stones.sort();
+
+    let current_player = if room.board.is_counting() {
+        "counting".to_string()
+    } else {
+        room.board.get_current_player().to_string()
+    };
+
+    SeatTurn {
+        action: seat_action(room, player),
+        color: player.to_string(),
+        session_token: seat_session(room, player),
+        board: seat_board(room, player),
+        current_player,
+        move_number: room.board.game_history.len(),
+        black_captures: room.board.get_black_captures(),
+        white_captures: room.board.get_white_captures(),
+        counting_version: room.counting.version,
+        dead_stones,
+        winner: room.board.get_winner().map(|winner| winner.to_string()),
+        announcements: room.announcements.get(since..).unwrap_or_default().to_vec(),
+        next_since: room.announcements.len(),
+    }
+}
+
+// None while there's nothing for the seat to do and time is left
+fn poll_turn(
+    match_string: &str,
+    bearer: Option<&str>,
+    account_id: Option<&str>,
+    since: usize,
+    first_poll: bool,
+    last_poll: bool,
+) -> Result<Option<SeatTurn>, Error> {
+    let mut rooms = lock_rooms()?;
+    let room = get_room(&mut rooms, match_string)?;
+    // Only the request itself counts against the rate limit, not every look at the room
+    let player = if first_poll {
+        authorize_bearer(room, bearer, account_id)?
+    } else {
+        seat_for_bearer(room, bearer, account_id).ok_or_else(|| {
+            Error::from(ApiError::Forbidden(
+                "Token doesn't own a seat in this game".to_string(),
+            ))
+        })?
+    };
+
+    // A waiting bot is still at the board
+    let session_token = seat_session(room, player);
+    mark_present(room, &session_token, SystemTime::now());
+
+    let has_news = room.announcements.len() > since;
+    if seat_action(room, player) == SeatAction::Wait && !has_news && !last_poll {
+        return Ok(None);
+    }
+    Ok(Some(seat_turn(room, player, since)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}/turn",
+    tag = "bots",
+    summary = "Wait until the seat has something to do",
+    description = "Held open until it's the seat's turn, counting or an undo request needs an answer, the game ends, or announcements newer than `since` arrive. Answers `wait` after `wait_secs`.",
+    params(
+        ("id" = String, Path, description = "Match string"),
+        ("since" = Option<usize>, Query, description = "`next_since` of the previous answer"),
+        ("wait_secs" = Option<u64>, Query, description = "At most 30, the default"),
+        ("Authorization" = String, Header, description = "Bearer bot token or session token")
+    ),
+    responses(
+        (status = 200, body = SeatTurn),
+        (status = 401, description = "`unauthorized`: missing token", body = ErrorBody),
+        (status = 403, description = "`forbidden`: the token doesn't own a seat", body = ErrorBody),
+        (status = 404, description = "`room_not_found`", body = ErrorBody)
+    )
+)]
+#[handler]
+pub async fn wait_for_turn(
+    req: &Request,
+    Path(match_string): Path<String>,
+    Query(query): Query<TurnQuery>,
+) -> Result<Json<SeatTurn>, Error> {
+    let bearer = bearer_token(req);
+    let account_id = bearer_account(bearer)?;
+    let since = query.since.unwrap_or_default();
+    let wait = query
+        .wait_secs
+        .map_or(MAX_TURN_WAIT, Duration::from_secs)
+        .min(MAX_TURN_WAIT);
+    let deadline = Instant::now() + wait;
+
+    let mut first_poll = true;
+    loop {
+        let last_poll = Instant::now() >= deadline;
+        let turn = poll_turn(
+            &match_string,
+            bearer,
+            account_id.as_deref(),
+            since,
+            first_poll,
+            last_poll,
+        )?;
+        if let Some(turn) = turn {
+            return Ok(Json(turn));
+        }
+        first_poll = false;
+        sleep(TURN_POLL_INTERVAL).await;
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{id}/turn",
+    tag = "bots",
+    summary = "Play a stone or pass as a bot",
+    description = "Validated like /cell-click and /pass. Answers with the seat's view after the move.",
+    params(
+        ("id" = String, Path, description = "Match string"),
+        ("since" = Option<usize>, Query, description = "Defaults to the announcements of this move"),
+        ("Authorization" = String, Header, description = "Bearer bot token or session token")
+    ),
+    request_body = MoveRequest,
+    responses(
+        (status = 201, description = "The move was played", body = SeatTurn),
+        (status = 401, description = "`unauthorized`: missing token", body = ErrorBody),
+        (status = 403, description = "`forbidden`: the token doesn't own a seat", body = ErrorBody),
+        (status = 404, description = "`room_not_found`", body = ErrorBody),
+        (status = 409, description = "`not_your_turn`, `wrong_phase` or `game_over`", body = ErrorBody),
+        (status = 422, description = "`illegal_move`", body = ErrorBody)
+    )
+)]
+#[handler]
+pub fn play_turn(
+    req: &Request,
+    Path(match_string): Path<String>,
+    Query(query): Query<TurnQuery>,
+    payload: Json<MoveRequest>,
+) -> Result<Response, Error> {
+    let bearer = bearer_token(req);
+    let account_id = bearer_account(bearer)?;
+
+    let mut rooms = lock_rooms()?;
+    let room = get_room(&mut rooms, &match_string)?;
+    let player = authorize_bearer(room, bearer, account_id.as_deref())?;
+    let since = query.since.unwrap_or(room.announcements.len());
+
+    match *payload {
+        MoveRequest::Play { row, col } => play_stone(room, player, row, col)?,
+        MoveRequest::Pass => pass_turn(room, player)?,
+    };
+
+    Ok(Json(seat_turn(room, player, since))
+        .with_status(StatusCode::CREATED)
+        .into_response())
+}
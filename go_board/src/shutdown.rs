@@ -0,0 +1,93 @@
+// Graceful shutdown on SIGTERM or Ctrl-C.
+//
+// Lingo:
+//     draining - a stop signal was received. /readyz fails so the load balancer stops
+//                sending traffic, no new games can be created, games in progress can
+//                still be played until the listener closes
+//
+// Once the signal arrives the listener stops accepting connections, in-flight requests
+// get the shutdown timeout to finish, then the server flushes room state and exits.
+
+use crate::config::Config;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tracing::info;
+
+static DRAINING: AtomicBool = AtomicBool::new(false);
+
+pub fn is_draining() -> bool {
+    DRAINING.load(Ordering::SeqCst)
+}
+
+#[derive(Clone, Debug)]
+pub struct ShutdownConfig {
+    // How long in-flight requests get before their connections are dropped
+    pub timeout: Duration,
+    // Where live games are written as SGF on the way out, nothing is written when unset
+    pub flush_dir: Option<PathBuf>,
+}
+
+impl ShutdownConfig {
+    pub fn from_config(config: &Config) -> Self {
+        ShutdownConfig {
+            timeout: Duration::from_secs(config.shutdown.timeout_secs),
+            flush_dir: config.shutdown.flush_dir.clone(),
+        }
+    }
+}
+
+// Resolves on the first stop signal and marks the server as draining
+pub async fn signal() {
+    wait_for_signal().await;
+    DRAINING.store(true, Ordering::SeqCst);
+    info!("stop signal received, draining");
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut sigterm) => {
+            tokio::select! {
+                _ = sigterm.recv() => {}
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        }
+        Err(_) => {
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+// Match strings are picked by players, keep them from escaping the flush directory
+pub fn file_name(match_string: &str) -> String {
+    let name: String = match_string
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.sgf", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shutdown::file_name;
+
+    #[test]
+    fn match_strings_become_safe_file_names() {
+        assert_eq!(file_name("friday-game_2"), "friday-game_2.sgf");
+        assert_eq!(file_name("../../etc/passwd"), "______etc_passwd.sgf");
+    }
+}
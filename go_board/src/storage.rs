@@ -0,0 +1,31 @@
+// Small files the server keeps next to itself (accounts, ratings, flushed games)
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Starts from the default if the file doesn't exist yet
+pub fn load_json<T: Default + DeserializeOwned>(path: &Path) -> io::Result<T> {
+    match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(err) => Err(err),
+    }
+}
+
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let contents = serde_json::to_string_pretty(value)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    save_text(path, &contents)
+}
+
+// Write to a temporary file first, so a crash can't leave half a file behind
+pub fn save_text(path: &Path, contents: &str) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)
+}
@@ -0,0 +1,108 @@
+// Optional HTTPS, terminated by the server itself with rustls.
+//
+// Set both the certificate and key paths (PEM) to turn it on. The files are checked every
+// reload interval and re-read when either changed, so renewed certificates (e.g. from
+// certbot) are picked up without a restart. A renewal that doesn't load keeps the
+// previous certificate and is retried once the files change again.
+
+use crate::config::Config;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use poem::listener::{IntoTlsConfigStream, RustlsCertificate, RustlsConfig};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+#[derive(Clone, Debug)]
+pub struct TlsFiles {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    pub reload_interval: Duration,
+}
+
+impl TlsFiles {
+    // None serves plain HTTP
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let tls = &config.tls;
+        Some(TlsFiles {
+            cert_path: tls.cert_path.clone()?,
+            key_path: tls.key_path.clone()?,
+            reload_interval: Duration::from_secs(tls.reload_interval_secs),
+        })
+    }
+
+    fn modified(&self) -> Option<(SystemTime, SystemTime)> {
+        let modified = |path: &PathBuf| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        Some((modified(&self.cert_path)?, modified(&self.key_path)?))
+    }
+
+    // Fails on unreadable files and on certificates or keys rustls can't use
+    pub fn load(&self) -> io::Result<RustlsConfig> {
+        let read = |path: &PathBuf| {
+            fs::read(path).map_err(|err| {
+                io::Error::new(
+                    err.kind(),
+                    format!("can't read {}: {}", path.display(), err),
+                )
+            })
+        };
+
+        let cert = read(&self.cert_path)?;
+        let key = read(&self.key_path)?;
+        // rustls reads a file without any certificate as an empty chain
+        if !String::from_utf8_lossy(&cert).contains("-----BEGIN CERTIFICATE-----") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("no PEM certificate in {}", self.cert_path.display()),
+            ));
+        }
+        let config = |cert: &[u8], key: &[u8]| {
+            RustlsConfig::new().fallback(RustlsCertificate::new().cert(cert).key(key))
+        };
+
+        // Building the stream builds the rustls server config, which checks both files
+        let _ = config(&cert, &key).into_stream()?;
+        Ok(config(&cert, &key))
+    }
+}
+
+// Starts with the already loaded config, then yields every renewal that loads. Renewals
+// are only read once both files went a whole interval without changing, so a cert
+// written before its key isn't loaded with the old key
+pub fn config_stream(files: TlsFiles, initial: RustlsConfig) -> BoxStream<'static, RustlsConfig> {
+    let loaded = files.modified();
+
+    stream::unfold(
+        (files, loaded, loaded, Some(initial)),
+        |(files, mut loaded, mut seen, initial)| async move {
+            if let Some(initial) = initial {
+                return Some((initial, (files, loaded, seen, None)));
+            }
+
+            loop {
+                tokio::time::sleep(files.reload_interval).await;
+                let modified = files.modified();
+                if modified.is_none() || modified == loaded {
+                    continue;
+                }
+                if modified != seen {
+                    seen = modified;
+                    continue;
+                }
+                loaded = modified;
+
+                match files.load() {
+                    Ok(config) => {
+                        info!(cert = %files.cert_path.display(), "reloaded TLS certificate");
+                        return Some((config, (files, loaded, seen, None)));
+                    }
+                    Err(err) => {
+                        warn!(%err, "renewed TLS certificate doesn't load, keeping the old one")
+                    }
+                }
+            }
+        },
+    )
+    .boxed()
+}